@@ -0,0 +1,297 @@
+//! Contains code for constructing a graph of which functions call which from
+//! final IR instructions.
+
+use super::{ Instruction, Value };
+use std::fmt::Write;
+
+/// Call graph of a program. Functions are identified by their labels and each
+/// call is given as a pair of indices into `functions` (caller then callee).
+/// Every distinct call appears only once regardless of how many times the
+/// caller calls the callee.
+#[derive(Debug, PartialEq)]
+pub struct CallGraph {
+    pub functions: Vec<String>,
+    pub calls: Vec<(usize, usize)>,
+    /// Functions that take a reference to another function (so that it may
+    /// be called indirectly), given in the same way as `calls`.
+    pub references: Vec<(usize, usize)>,
+    /// Whether each function contains a conditional jump (i.e. an if or while
+    /// statement), indexed in the same way as `functions`.
+    pub branches: Vec<bool>
+}
+
+impl CallGraph {
+    /// Labels of the functions called by the function with the given label.
+    pub fn callees(&self, label: &str) -> Vec<&str> {
+        self.calls.iter()
+            .filter(|(caller, _)| self.functions[*caller] == label)
+            .map(|(_, callee)| self.functions[*callee].as_str())
+            .collect()
+    }
+
+    /// Check whether the function with the given label calls itself directly.
+    pub fn is_directly_recursive(&self, label: &str) -> bool {
+        self.callees(label).contains(&label)
+    }
+
+    /// Find groups of functions that call one another in a cycle (including
+    /// single functions that call themselves directly), given as indices into
+    /// `functions` in the order the functions were defined.
+    pub fn recursive_groups(&self) -> Vec<Vec<usize>> {
+        let mut search = ComponentSearch {
+            graph: self,
+            next_index: 0,
+            indices: vec![None; self.functions.len()],
+            low_links: vec![0; self.functions.len()],
+            stack: Vec::new(),
+            components: Vec::new()
+        };
+
+        for function in 0..self.functions.len() {
+            if search.indices[function].is_none() { search.visit(function); }
+        }
+
+        let mut groups: Vec<Vec<usize>> = search.components.into_iter()
+            .filter(|component| component.len() > 1 || self.calls.contains(&(component[0], component[0])))
+            .map(|mut component| { component.sort_unstable(); component })
+            .collect();
+
+        groups.sort_unstable();
+        groups
+    }
+
+    /// Find the recursive groups of functions (see `recursive_groups`) in which
+    /// no function contains a conditional jump. Calling any function in such a
+    /// group will always lead to another call within that group and so never
+    /// return.
+    pub fn unconditionally_recursive_groups(&self) -> Vec<Vec<usize>> {
+        self.recursive_groups().into_iter()
+            .filter(|group| group.iter().all(|function| !self.branches[*function]))
+            .collect()
+    }
+
+    /// Determine which functions may be executed when the function with the
+    /// given label is called, whether by being called directly or by being
+    /// referenced (and so possibly called indirectly). Indexed in the same way
+    /// as `functions`.
+    pub fn reachable_from(&self, label: &str) -> Vec<bool> {
+        let mut reachable = vec![false; self.functions.len()];
+        let mut to_visit: Vec<usize> = self.functions.iter().position(|x| x == label).into_iter().collect();
+
+        while let Some(function) = to_visit.pop() {
+            if reachable[function] { continue; }
+            reachable[function] = true;
+
+            to_visit.extend(self.calls.iter().chain(self.references.iter())
+                .filter(|(caller, _)| *caller == function)
+                .map(|(_, callee)| *callee));
+        }
+
+        reachable
+    }
+
+    /// Produce a description of the graph in the DOT language (as used by
+    /// Graphviz).
+    pub fn to_dot(&self) -> String {
+        let mut dot = "digraph calls {\n".to_string();
+
+        for function in &self.functions {
+            writeln!(dot, "    \"{}\";", function).unwrap();
+        }
+
+        for (caller, callee) in &self.calls {
+            writeln!(dot, "    \"{}\" -> \"{}\";", self.functions[*caller], self.functions[*callee]).unwrap();
+        }
+
+        dot.push('}');
+        dot
+    }
+}
+
+/// State of Tarjan's algorithm for finding the strongly-connected components of
+/// a call graph.
+struct ComponentSearch<'a> {
+    graph: &'a CallGraph,
+    next_index: usize,
+    indices: Vec<Option<usize>>,
+    low_links: Vec<usize>,
+    stack: Vec<usize>,
+    components: Vec<Vec<usize>>
+}
+
+impl ComponentSearch<'_> {
+    fn visit(&mut self, function: usize) {
+        self.indices[function] = Some(self.next_index);
+        self.low_links[function] = self.next_index;
+        self.next_index += 1;
+        self.stack.push(function);
+
+        let callees: Vec<usize> = self.graph.calls.iter()
+            .filter(|(caller, _)| *caller == function)
+            .map(|(_, callee)| *callee)
+            .collect();
+
+        for callee in callees {
+            match self.indices[callee] {
+                None => {
+                    self.visit(callee);
+                    self.low_links[function] = self.low_links[function].min(self.low_links[callee]);
+                }
+                Some(callee_index) if self.stack.contains(&callee) => {
+                    self.low_links[function] = self.low_links[function].min(callee_index);
+                }
+                Some(_) => {}
+            }
+        }
+
+        // Function is the root of a component so remove that component from
+        // the stack:
+        if Some(self.low_links[function]) == self.indices[function] {
+            let root_position = self.stack.iter().position(|x| *x == function).unwrap();
+            self.components.push(self.stack.split_off(root_position));
+        }
+    }
+}
+
+/// Build the call graph of the given instructions.
+pub fn build(instructions: &[Instruction]) -> CallGraph {
+    let functions: Vec<String> = instructions.iter()
+        .filter_map(|instruction| match instruction {
+            Instruction::Function { label, .. } => Some(label.clone()),
+            _ => None
+        })
+        .collect();
+
+    let mut calls = Vec::new();
+    let mut references = Vec::new();
+    let mut branches = vec![false; functions.len()];
+    let mut current_function = None;
+
+    for instruction in instructions {
+        match instruction {
+            Instruction::Function { label, .. } => current_function = functions.iter().position(|x| x == label),
+
+            Instruction::JumpIfTrue(_) | Instruction::JumpIfFalse(_) => {
+                if let Some(function) = current_function { branches[function] = true; }
+            }
+
+            Instruction::CallExpectingVoid(label) | Instruction::CallExpectingValue(label) => {
+                if let (Some(caller), Some(callee)) = (current_function, functions.iter().position(|x| x == label)) {
+                    if !calls.contains(&(caller, callee)) { calls.push((caller, callee)); }
+                }
+            }
+
+            Instruction::Push(Value::Function(label)) => {
+                if let (Some(referrer), Some(referenced)) = (current_function, functions.iter().position(|x| x == label)) {
+                    if !references.contains(&(referrer, referenced)) { references.push((referrer, referenced)); }
+                }
+            }
+
+            _ => {}
+        }
+    }
+
+    CallGraph { functions, calls, references, branches }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ lexing::lexer, parsing, checking, stream::Stream };
+
+    fn quick_check(inp: &str) -> Vec<checking::Instruction> {
+        let final_inp = inp.trim().replace("    ", "\t");
+        let tokens = lexer::input(Stream::from_str(&final_inp)).map(Result::unwrap);
+        let stmts = parsing::parser::input(tokens).map(Result::unwrap);
+        checking::checker::input(stmts).unwrap()
+    }
+
+    #[test]
+    fn call_graph() {
+        let graph = super::build(&quick_check("
+b(Num x) -> Num
+    return x + 1
+
+a(Num x) -> Num
+    return b(x) * b(x)
+
+countdown(Num x) -> Num
+    if x > 0
+        return countdown(x - 1)
+    return x
+
+main()
+    display a(1)
+    display countdown(3)"));
+
+        let (b, a, countdown) = (&graph.functions[0], &graph.functions[1], &graph.functions[2]);
+
+        assert_eq!(graph.callees(a), vec![b.as_str()]);
+        assert!(graph.callees(b).is_empty());
+        assert_eq!(graph.callees("main"), vec![a.as_str(), countdown.as_str()]);
+
+        assert!(graph.is_directly_recursive(countdown));
+        assert!(!graph.is_directly_recursive(a));
+
+        assert_eq!(graph.calls.len(), 4);
+        assert!(graph.to_dot().contains(&format!("    \"{}\" -> \"{}\";\n", a, b)));
+        assert!(graph.to_dot().contains(&format!("    \"{}\" -> \"{}\";\n", countdown, countdown)));
+
+        assert_eq!(graph.recursive_groups(), vec![vec![2]]);
+        assert!(graph.unconditionally_recursive_groups().is_empty());
+    }
+
+    #[test]
+    fn reachability() {
+        let graph = super::build(&quick_check("
+unused() -> Num
+    return 1
+
+called(Num x) -> Num
+    return x
+
+referenced(Num x) -> Num
+    return called(x)
+
+main()
+    Fn(Num) -> Num f = referenced
+    display f(2)"));
+
+        assert_eq!(graph.reachable_from("main"), vec![false, true, true, true]);
+        assert_eq!(graph.reachable_from(&graph.functions[2]), vec![false, true, true, false]);
+        assert_eq!(graph.calls, vec![(2, 1)]);
+        assert_eq!(graph.references, vec![(3, 2)]);
+    }
+
+    #[test]
+    fn mutual_recursion() {
+        use checking::{ Instruction, Value };
+
+        // Functions must be defined before they are called in till code so
+        // mutually recursive functions can only be expressed directly in IR:
+        let function = |label: &str| Instruction::Function { label: label.to_string(), local_variable_count: 0 };
+        let call = |label: &str| Instruction::CallExpectingValue(label.to_string());
+
+        let without_base_case = vec![
+            function("ping"), call("pong"), Instruction::ReturnValue,
+            function("pong"), call("ping"), Instruction::ReturnValue,
+            function("main"), call("ping"), Instruction::ReturnVoid
+        ];
+
+        let graph = super::build(&without_base_case);
+        assert_eq!(graph.recursive_groups(), vec![vec![0, 1]]);
+        assert_eq!(graph.unconditionally_recursive_groups(), vec![vec![0, 1]]);
+
+        let with_base_case = vec![
+            function("ping"), call("pong"), Instruction::ReturnValue,
+            function("pong"),
+            Instruction::Push(Value::Bool(true)), Instruction::JumpIfFalse(0),
+            Instruction::Push(Value::Num(0.0)), Instruction::ReturnValue,
+            Instruction::Label(0), call("ping"), Instruction::ReturnValue,
+            function("main"), call("ping"), Instruction::ReturnVoid
+        ];
+
+        let graph = super::build(&with_base_case);
+        assert_eq!(graph.recursive_groups(), vec![vec![0, 1]]);
+        assert!(graph.unconditionally_recursive_groups().is_empty());
+    }
+}
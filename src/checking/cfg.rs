@@ -0,0 +1,323 @@
+//! Contains code for constructing control-flow graphs from final IR
+//! instructions as well as analyses performed over those graphs.
+
+use super::Instruction;
+use std::{ collections::HashMap, fmt::Write, ops::Range };
+
+/// Control-flow graph of a single function. Each basic block is a range of
+/// indices into the instructions from which the graph was built. All return
+/// instructions lead to a single exit node.
+#[derive(Debug, PartialEq)]
+pub struct Graph {
+    /// Label of the function this graph represents.
+    pub label: String,
+    pub blocks: Vec<Range<usize>>,
+    /// Edges between blocks, given as indices into `blocks`. A destination of
+    /// `None` indicates the exit node.
+    pub edges: Vec<(usize, Option<usize>)>
+}
+
+impl Graph {
+    /// Calculate the cyclomatic complexity of the function (the number of edges
+    /// minus the number of nodes plus two). Blocks that cannot be reached from
+    /// the function entry are not included.
+    pub fn cyclomatic_complexity(&self) -> usize {
+        let reachable = self.reachable_blocks();
+
+        let edge_count = self.edges.iter().filter(|(from, _)| reachable[*from]).count();
+        let node_count = reachable.iter().filter(|x| **x).count() + 1; // Include exit node.
+
+        edge_count + 2 - node_count
+    }
+
+    /// Produce a description of the graph in the DOT language (as used by
+    /// Graphviz). Each block is labelled with its instructions, which are
+    /// taken from the given instructions (those from which the graph was
+    /// built).
+    pub fn to_dot(&self, instructions: &[Instruction]) -> String {
+        let mut dot = format!("digraph \"{}\" {{\n", self.label);
+
+        for (n, block) in self.blocks.iter().enumerate() {
+            let label: String = instructions[block.clone()].iter()
+                .map(|instruction| format!("{:?}\\l", instruction).replace('"', "\\\""))
+                .collect();
+            writeln!(dot, "    block{} [shape=box, label=\"{}\"];", n, label).unwrap();
+        }
+
+        writeln!(dot, "    exit;").unwrap();
+
+        for (from, to) in &self.edges {
+            match to {
+                Some(to) => writeln!(dot, "    block{} -> block{};", from, to).unwrap(),
+                None => writeln!(dot, "    block{} -> exit;", from).unwrap()
+            }
+        }
+
+        dot.push('}');
+        dot
+    }
+
+    /// Calculate the greatest number of values on the operand stack at any one
+    /// point during the execution of the function, given the instructions from
+    /// which the graph was built and the number of parameters of each function
+    /// by label. The arguments of the function are on the stack upon entry
+    /// (until taken by its parameter instructions), while the arguments of any
+    /// call the function makes are taken from the stack by that call.
+    pub fn max_stack_depth(&self, instructions: &[Instruction], parameter_counts: &HashMap<String, usize>) -> usize {
+        let mut entry_depths: Vec<Option<usize>> = vec![None; self.blocks.len()];
+        let mut to_visit = Vec::new();
+        let mut max_depth = parameter_counts.get(&self.label).copied().unwrap_or(0);
+
+        if !self.blocks.is_empty() {
+            entry_depths[0] = Some(max_depth);
+            to_visit.push(0);
+        }
+
+        while let Some(block) = to_visit.pop() {
+            let mut depth = entry_depths[block].unwrap();
+
+            for instruction in &instructions[self.blocks[block].clone()] {
+                let effect = instruction.stack_effect();
+
+                let arg_count = match instruction {
+                    Instruction::CallExpectingVoid(label) | Instruction::CallExpectingValue(label) =>
+                        parameter_counts.get(label).copied().unwrap_or(0),
+                    Instruction::CallIndirect(arg_count) => *arg_count,
+                    _ => 0
+                };
+
+                depth = depth.saturating_sub(effect.consumed.len() + arg_count) + effect.produced.len();
+                max_depth = max_depth.max(depth);
+            }
+
+            // The depth upon reaching a block is the same regardless of the
+            // path taken so each block need only be visited once:
+            for (_, to) in self.edges.iter().filter(|(from, _)| *from == block) {
+                if let Some(to) = to {
+                    if entry_depths[*to].is_none() {
+                        entry_depths[*to] = Some(depth);
+                        to_visit.push(*to);
+                    }
+                }
+            }
+        }
+
+        max_depth
+    }
+
+    fn reachable_blocks(&self) -> Vec<bool> {
+        let mut reachable = vec![false; self.blocks.len()];
+        let mut to_visit = if self.blocks.is_empty() { vec![] } else { vec![0] };
+
+        while let Some(block) = to_visit.pop() {
+            if !reachable[block] {
+                reachable[block] = true;
+
+                to_visit.extend(self.edges.iter()
+                    .filter(|(from, _)| *from == block)
+                    .filter_map(|(_, to)| *to));
+            }
+        }
+
+        reachable
+    }
+}
+
+/// Build a control-flow graph for each function in the given instructions.
+pub fn build(instructions: &[Instruction]) -> Vec<Graph> {
+    let function_starts: Vec<(usize, &String)> = instructions.iter().enumerate()
+        .filter_map(|(i, instruction)| match instruction {
+            Instruction::Function { label, .. } => Some((i, label)),
+            _ => None
+        })
+        .collect();
+
+    function_starts.iter().enumerate().map(|(n, (start, label))| {
+        let end = function_starts.get(n + 1).map(|(i, _)| *i).unwrap_or_else(|| instructions.len());
+        build_function_graph(label.to_string(), instructions, start + 1..end)
+    }).collect()
+}
+
+/// Calculate the maximum operand stack depth of each function in the given
+/// instructions (see `Graph::max_stack_depth`), returned alongside the label of
+/// that function.
+pub fn max_stack_depths(instructions: &[Instruction]) -> Vec<(String, usize)> {
+    let mut parameter_counts = HashMap::new();
+    let mut current_label = None;
+
+    for instruction in instructions {
+        match instruction {
+            Instruction::Function { label, .. } => {
+                parameter_counts.insert(label.clone(), 0);
+                current_label = Some(label);
+            }
+            Instruction::Parameter(_) => {
+                if let Some(count) = current_label.and_then(|label| parameter_counts.get_mut(label)) { *count += 1; }
+            }
+            _ => {}
+        }
+    }
+
+    build(instructions).into_iter().map(|graph| {
+        let depth = graph.max_stack_depth(instructions, &parameter_counts);
+        (graph.label, depth)
+    }).collect()
+}
+
+/// Calculate the cyclomatic complexity of each function in the given
+/// instructions, returned alongside the label of that function.
+pub fn cyclomatic_complexities(instructions: &[Instruction]) -> Vec<(String, usize)> {
+    build(instructions).into_iter().map(|graph| {
+        let complexity = graph.cyclomatic_complexity();
+        (graph.label, complexity)
+    }).collect()
+}
+
+fn build_function_graph(label: String, instructions: &[Instruction], body: Range<usize>) -> Graph {
+    // Find the first instruction of each basic block - the beginning of the
+    // function body, any label, and any instruction following a jump or return:
+    let mut leaders = vec![body.start];
+
+    for i in body.clone() {
+        match instructions[i] {
+            Instruction::Label(_) => leaders.push(i),
+            Instruction::Jump(_) | Instruction::JumpIfTrue(_) | Instruction::JumpIfFalse(_) |
+            Instruction::ReturnValue | Instruction::ReturnVoid | Instruction::AssertionFailed(_) => leaders.push(i + 1),
+            _ => {}
+        }
+    }
+
+    leaders.retain(|i| *i < body.end);
+    leaders.sort_unstable();
+    leaders.dedup();
+
+    let blocks: Vec<Range<usize>> = leaders.iter().enumerate()
+        .map(|(n, start)| *start..leaders.get(n + 1).copied().unwrap_or(body.end))
+        .collect();
+
+    let label_blocks: HashMap<super::Id, usize> = blocks.iter().enumerate()
+        .filter_map(|(n, block)| match instructions[block.start] {
+            Instruction::Label(id) => Some((id, n)),
+            _ => None
+        })
+        .collect();
+
+    let mut edges = Vec::new();
+
+    for (n, block) in blocks.iter().enumerate() {
+        // Block that follows this one (exit node should this be the last block):
+        let next = if n + 1 < blocks.len() { Some(n + 1) } else { None };
+
+        match instructions[block.end - 1] {
+            Instruction::Jump(id) => edges.push((n, Some(label_blocks[&id]))),
+
+            Instruction::JumpIfTrue(id) | Instruction::JumpIfFalse(id) => {
+                edges.push((n, Some(label_blocks[&id])));
+                edges.push((n, next));
+            }
+
+            // A failed assertion ends the program:
+            Instruction::ReturnValue | Instruction::ReturnVoid | Instruction::AssertionFailed(_) => edges.push((n, None)),
+
+            _ => edges.push((n, next))
+        }
+    }
+
+    Graph { label, blocks, edges }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ lexing::lexer, parsing, checking, stream::Stream };
+
+    fn quick_check(inp: &str) -> Vec<checking::Instruction> {
+        let final_inp = inp.trim().replace("    ", "\t");
+        let tokens = lexer::input(Stream::from_str(&final_inp)).map(Result::unwrap);
+        let stmts = parsing::parser::input(tokens).map(Result::unwrap);
+        checking::checker::input(stmts).unwrap()
+    }
+
+    #[test]
+    fn cyclomatic_complexity() {
+        let instructions = quick_check("
+straight(Num x) -> Num
+    Num y = x * 2
+    display y
+    return y
+
+branch(Num x) -> Num
+    if x > 1
+        return 1
+    return x
+
+loop(Num x)
+    while x > 0
+        if x == 5
+            display x
+        x = x - 1
+
+main()
+    display 1");
+
+        let complexities: Vec<usize> = super::cyclomatic_complexities(&instructions).into_iter()
+            .map(|(_, complexity)| complexity)
+            .collect();
+        assert_eq!(complexities, vec![1, 2, 3, 1]);
+    }
+
+    #[test]
+    fn max_stack_depth() {
+        let instructions = quick_check("
+f(Num a, Num b, Num c) -> Num
+    return a + b * c
+
+g(Num x) -> Num
+    while x > 0
+        x = x - f(x, 1, 2 * x)
+    return x
+
+main()
+    Fn(Num) -> Num h = g
+    display h(2) + f(1, 2, 3)");
+
+        let depths: Vec<usize> = super::max_stack_depths(&instructions).into_iter()
+            .map(|(_, depth)| depth)
+            .collect();
+
+        // f has its arguments upon entry and then the three operands, g has x
+        // along with the four values placed on the stack for the call to f,
+        // and main has the result of calling h along with the arguments of f:
+        assert_eq!(depths, vec![3, 5, 4]);
+    }
+
+    #[test]
+    fn graph() {
+        let instructions = quick_check("
+main()
+    if true
+        display 1");
+
+        let graphs = super::build(&instructions);
+        assert_eq!(graphs.len(), 1);
+
+        // Condition block, if body block, and block following the if statement:
+        assert_eq!(graphs[0].blocks.len(), 3);
+        assert_eq!(graphs[0].edges, vec![(0, Some(2)), (0, Some(1)), (1, Some(2)), (2, None)]);
+    }
+
+    #[test]
+    fn dot() {
+        let instructions = quick_check("
+main()
+    if true
+        print('\"')");
+
+        let dot = super::build(&instructions)[0].to_dot(&instructions);
+
+        assert!(dot.starts_with("digraph \"main\" {\n"));
+        assert_eq!(dot.matches("[shape=box").count(), 3);
+        assert_eq!(dot.matches(" -> ").count(), 4);
+        assert!(dot.contains("    block0 -> block2;\n    block0 -> block1;\n    block1 -> block2;\n    block2 -> exit;\n"));
+        assert!(dot.contains("[shape=box, label=\"Push(Char('\\\"'))\\lPrint(Char)\\l\"]"));
+    }
+}
@@ -2,10 +2,133 @@
 //! a final immediate representation of the input program.
 
 use crate::{ stream, parsing };
+use std::cell::{ Cell, RefCell };
 //use std::collections::HashMap;
 
+/// The final IR instructions, number of local variables declared, and optional
+/// return type and position produced by checking a statement within a function.
+type InnerStmtEvaluation = (Vec<super::Instruction>, usize, Option<(super::Type, stream::Position)>);
+
 pub fn input<T: Iterator<Item=parsing::Statement>>(stmts: T) -> super::Result<Vec<super::Instruction>> {
-    Checker::new(stmts).execute()
+    input_with_options(stmts, super::Options::default())
+}
+
+/// Perform checking on the given statements with the given options, returning
+/// the final IR instructions (warnings are discarded unless treated as errors).
+pub fn input_with_options<T: Iterator<Item=parsing::Statement>>(stmts: T, options: super::Options) -> super::Result<Vec<super::Instruction>> {
+    Checker::new(stmts, options).check().map(|(instructions, _)| instructions)
+}
+
+/// Perform checking on the given statements, returning any warnings produced
+/// alongside the final IR instructions.
+pub fn input_with_warnings<T: Iterator<Item=parsing::Statement>>(stmts: T, options: super::Options) -> super::Result<(Vec<super::Instruction>, Vec<super::Warning>)> {
+    Checker::new(stmts, options).check()
+}
+
+/// Perform checking on the given statements, continuing past any top-level
+/// statement that fails to be checked so that the failures of every such
+/// statement are returned (in the order they were encountered) rather than
+/// only the first.
+pub fn input_with_all_failures<T: Iterator<Item=parsing::Statement>>(stmts: T, options: super::Options) -> Result<(Vec<super::Instruction>, Vec<super::Warning>), Vec<super::Failure>> {
+    Checker::new(stmts, options).check_all()
+}
+
+/// Perform checking on the given statements, returning information about the
+/// program (e.g. all definitions made) rather than final IR instructions.
+pub fn analyse<T: Iterator<Item=parsing::Statement>>(stmts: T) -> super::Result<super::Analysis> {
+    let mut chkr = Checker::new(stmts, super::Options::default());
+    chkr.execute()?;
+
+    let mut analysis = chkr.analysis;
+    analysis.references = chkr.references.into_inner();
+    analysis.types = chkr.types.into_inner();
+    analysis.expression_types = chkr.expression_types.into_inner();
+    Ok(analysis)
+}
+
+/// Builds a `Checker` with options other than the defaults, for example:
+///
+/// ```
+/// use till::checking::checker::CheckerBuilder;
+///
+/// let checker = CheckerBuilder::new()
+///     .strict_types(false)
+///     .entry_point("start")
+///     .build(std::iter::empty());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CheckerBuilder { options: super::Options }
+
+impl CheckerBuilder {
+    pub fn new() -> Self { CheckerBuilder::default() }
+
+    /// See `Options::strict_types`.
+    pub fn strict_types(mut self, enabled: bool) -> Self {
+        self.options.strict_types = enabled;
+        self
+    }
+
+    /// See `Options::mark_lines`.
+    pub fn mark_lines(mut self, enabled: bool) -> Self {
+        self.options.mark_lines = enabled;
+        self
+    }
+
+    /// See `Options::remove_dead_branches`.
+    pub fn remove_dead_branches(mut self, enabled: bool) -> Self {
+        self.options.remove_dead_branches = enabled;
+        self
+    }
+
+    /// See `Options::entry_point`.
+    pub fn entry_point(mut self, identifier: &str) -> Self {
+        self.options.entry_point = identifier.to_string();
+        self
+    }
+
+    /// See `Options::max_expression_depth`.
+    pub fn max_expression_depth(mut self, depth: usize) -> Self {
+        self.options.max_expression_depth = depth;
+        self
+    }
+
+    /// See `Options::hoist_loop_invariants`.
+    pub fn hoist_loop_invariants(mut self, enabled: bool) -> Self {
+        self.options.hoist_loop_invariants = enabled;
+        self
+    }
+
+    /// See `Options::fold_boolean_identities`.
+    pub fn fold_boolean_identities(mut self, enabled: bool) -> Self {
+        self.options.fold_boolean_identities = enabled;
+        self
+    }
+
+    /// See `Options::propagate_constants`.
+    pub fn propagate_constants(mut self, enabled: bool) -> Self {
+        self.options.propagate_constants = enabled;
+        self
+    }
+
+    /// See `Options::warnings_as_errors`.
+    pub fn warnings_as_errors(mut self, enabled: bool) -> Self {
+        self.options.warnings_as_errors = enabled;
+        self
+    }
+
+    /// See `Options::library`.
+    pub fn library(mut self, enabled: bool) -> Self {
+        self.options.library = enabled;
+        self
+    }
+
+    /// The options the checker will be built with.
+    pub fn options(&self) -> &super::Options { &self.options }
+
+    /// Create a checker of the given statements.
+    pub fn build<T: Iterator<Item=parsing::Statement>>(self, stmts: T) -> Checker<T> {
+        Checker::new(stmts, self.options)
+    }
 }
 
 /// Performs scoping and type checking on a stream of parsed statements. Yields
@@ -13,6 +136,8 @@ pub fn input<T: Iterator<Item=parsing::Statement>>(stmts: T) -> super::Result<Ve
 pub struct Checker<T: Iterator<Item=parsing::Statement>> {
     /// Iterator of statements to be checked.
     stmts: T,
+    /// Rules to be enforced while checking.
+    options: super::Options,
     /// Contains all function definitions.
     functions: Vec<super::FunctionDef>,
     /// The scope stack. The scope at the end of this vector is the inner most
@@ -22,41 +147,209 @@ pub struct Checker<T: Iterator<Item=parsing::Statement>> {
     id_counter: super::Id,
     /// IDs of local variables that are no longer used (i.e. went out of scope).
     available_local_variable_ids: Vec<super::Id>,
-    /// Has the main function been defined?
-    main_defined: bool
+    /// Has the entry-point function been defined?
+    main_defined: bool,
+    /// Information gathered for tooling purposes.
+    analysis: super::Analysis,
+    /// References to definitions encountered so far. Held separately from the
+    /// rest of the analysis as references are recorded during the evaluation
+    /// of expressions which does not otherwise modify the checker.
+    references: RefCell<Vec<super::Reference>>,
+    /// Warnings about the program produced so far. Also produced during the
+    /// evaluation of expressions (see `references`).
+    warnings: RefCell<Vec<super::Warning>>,
+    /// Every distinct type encountered so far (see `Analysis::types`). Also
+    /// recorded during the evaluation of expressions.
+    types: RefCell<Vec<super::Type>>,
+    /// Type of every expression checked so far (see
+    /// `Analysis::expression_types`).
+    expression_types: RefCell<Vec<(stream::Position, super::Type)>>,
+    /// IDs of the variables in scope that may not yet have been given a value
+    /// (i.e. declared without an initial value and not assigned to on every
+    /// path through the function so far).
+    uninitialised: RefCell<Vec<super::Id>>,
+    /// Depth to which sub-expressions are currently being evaluated.
+    expression_depth: Cell<usize>,
+    /// Line number of the last statement checked so far (used to determine
+    /// the lines spanned by each scope).
+    last_line: u64
 }
 
 impl<T: Iterator<Item=parsing::Statement>> Checker<T> {
-    fn new(stmts: T) -> Self {
+    fn new(stmts: T, options: super::Options) -> Self {
         Checker {
             stmts,
+            options,
             //global_variables: HashMap::new(),
-            functions: Vec::new(),
+            functions: vec![approx_eq_def(), arg_count_def()],
             scopes: Vec::new(),
             id_counter: 0,
             available_local_variable_ids: Vec::new(),
-            main_defined: false
+            main_defined: false,
+            analysis: super::Analysis::default(),
+            references: RefCell::new(Vec::new()),
+            warnings: RefCell::new(Vec::new()),
+            types: RefCell::new(Vec::new()),
+            expression_types: RefCell::new(Vec::new()),
+            uninitialised: RefCell::new(Vec::new()),
+            expression_depth: Cell::new(0),
+            last_line: 0
+        }
+    }
+
+    /// Check all of the statements, producing the final IR alongside any
+    /// warnings. Only the first failure encountered is returned.
+    pub fn check(mut self) -> super::Result<(Vec<super::Instruction>, Vec<super::Warning>)> {
+        let instructions = self.execute()?;
+        self.check_all_warnings().map_err(|mut failures| failures.remove(0))?;
+        Ok((instructions, self.warnings.into_inner()))
+    }
+
+    /// Check all of the statements as with `check` but return all failures
+    /// encountered (see `input_with_all_failures`).
+    pub fn check_all(mut self) -> Result<(Vec<super::Instruction>, Vec<super::Warning>), Vec<super::Failure>> {
+        let instructions = self.execute_all()?;
+        self.check_all_warnings()?;
+        Ok((instructions, self.warnings.into_inner()))
+    }
+
+    /// Produce a failure for each warning should warnings be treated as errors.
+    fn check_all_warnings(&self) -> Result<(), Vec<super::Failure>> {
+        let warnings = self.warnings.borrow();
+
+        if self.options.warnings_as_errors && !warnings.is_empty() {
+            Err(warnings.iter().cloned().map(super::Failure::WarningAsError).collect())
         }
+        else { Ok(()) }
     }
 
     /// Perform scoping and type checking before yielding the final immediate
-    /// representation of the input program. This will consume the `Checker`
-    /// instance.
-    fn execute(mut self) -> super::Result<Vec<super::Instruction>> {
+    /// representation of the input program. Only the first failure encountered
+    /// is returned.
+    fn execute(&mut self) -> super::Result<Vec<super::Instruction>> {
+        self.execute_all().map_err(|mut failures| failures.remove(0))
+    }
+
+    /// Perform scoping and type checking as with `execute` but return all
+    /// failures encountered. Should a top-level statement fail to be checked,
+    /// checking resumes from the next top-level statement.
+    fn execute_all(&mut self) -> Result<Vec<super::Instruction>, Vec<super::Failure>> {
         // Holds the primitive instructions that will make up the final immediate
         // representation of the input program.
         let mut final_ir = Vec::new();
+        let mut failures = Vec::new();
+        let mut empty = true;
 
         // Evaluate top-level statements:
         while let Some(stmt) = self.stmts.next() {
-            let new_instructions = self.eval_top_level_stmt(stmt)?;
-            final_ir.extend(new_instructions);
+            match self.eval_top_level_stmt(stmt) {
+                Ok(new_instructions) => final_ir.extend(new_instructions),
+                Err(failure) => {
+                    log::trace!("Recovering from failure so as to check the next top-level statement: {}", failure);
+
+                    self.uninitialised.borrow_mut().clear();
+                    failures.push(failure);
+                }
+            }
+            empty = false;
+
+            // Every scope begun while checking a top-level statement should
+            // have been ended, even should checking have failed part way
+            // through a block:
+            if !self.scopes.is_empty() {
+                failures.push(super::Failure::UnbalancedScopes(self.scopes.len()));
+                self.scopes.clear();
+                self.uninitialised.borrow_mut().clear();
+            }
+        }
+
+        if empty {
+            let entry_point = if self.options.library { None } else { Some(self.options.entry_point.clone()) };
+            return Err(vec![super::Failure::EmptyProgram(entry_point)]);
+        }
+
+        if !self.main_defined && !self.options.library { failures.push(super::Failure::MainUndefined(self.options.entry_point.clone())); }
+
+        if !failures.is_empty() { return Err(failures); }
+
+        // Built-in functions are only included should they be referred to:
+        let builtin_used = |builtin_label: &str| final_ir.iter().any(|instruction| match instruction {
+            super::Instruction::CallExpectingValue(label) | super::Instruction::Push(super::Value::Function(label)) =>
+                label == builtin_label,
+            _ => false
+        });
+        let (approx_eq_used, arg_count_used) = (builtin_used(&approx_eq_label()), builtin_used(&arg_count_label()));
+
+        if approx_eq_used { final_ir.extend(self.approx_eq_instructions()); }
+        if arg_count_used { final_ir.extend(arg_count_instructions()); }
+
+        if let Err(e) = super::verify::verify(&final_ir) {
+            failures.push(super::Failure::InvalidIr(e));
+            return Err(failures);
         }
 
-        assert!(self.scopes.is_empty());
+        let graph = super::callgraph::build(&final_ir);
+        self.check_recursion(&graph);
+        self.check_unused_functions(&graph);
+
+        Ok(final_ir)
+    }
+
+    /// Produce a warning for each group of functions that always call one
+    /// another recursively.
+    fn check_recursion(&mut self, graph: &super::callgraph::CallGraph) {
+        for group in graph.unconditionally_recursive_groups() {
+            // Report the first function of the group to be defined:
+            let label = &graph.functions[group[0]];
+
+            if let Some(def) = self.functions.iter().find(|def| def.label == *label) {
+                log::trace!("Function '{}' is unconditionally recursive", def.identifier);
+                self.warnings.borrow_mut().push(super::Warning::PossibleInfiniteRecursion(def.pos.clone(), def.identifier.clone()));
+            }
+        }
+    }
+
+    /// Produce a warning for each function that can never be executed when
+    /// starting from the entry point.
+    fn check_unused_functions(&mut self, graph: &super::callgraph::CallGraph) {
+        if !self.main_defined { return; }
 
-        if self.main_defined { Ok(final_ir) }
-        else { Err(super::Failure::MainUndefined) }
+        let reachable = graph.reachable_from(&self.options.entry_point);
+
+        for def in self.functions.iter().filter(|def| def.label != approx_eq_label() && def.label != arg_count_label()) {
+            let used = graph.functions.iter().position(|label| *label == def.label).is_some_and(|index| reachable[index]);
+
+            if !used {
+                log::trace!("Function '{}' is never called", def.identifier);
+                self.warnings.borrow_mut().push(super::Warning::UnusedFunction(def.pos.clone(), def.identifier.clone()));
+            }
+        }
+    }
+
+    /// Instructions of the built-in function `approx_eq(Num a, Num b, Num epsilon) -> Bool`
+    /// which gives whether the absolute difference between `a` and `b` is less
+    /// than `epsilon`.
+    fn approx_eq_instructions(&mut self) -> Vec<super::Instruction> {
+        use super::{ Instruction, Value };
+
+        let (a, b, epsilon, difference) = (self.new_id(), self.new_id(), self.new_id(), self.new_id());
+        let positive_id = self.new_id();
+
+        vec![
+            Instruction::Function { label: approx_eq_label(), local_variable_count: 1 },
+            Instruction::Parameter(epsilon), Instruction::Parameter(b), Instruction::Parameter(a),
+            Instruction::Local(difference),
+            Instruction::Push(Value::Variable(a)), Instruction::Push(Value::Variable(b)), Instruction::Subtract,
+            Instruction::Store(difference),
+            // Negate the difference should it be negative:
+            Instruction::Push(Value::Variable(difference)), Instruction::Push(Value::Num(0.0)), Instruction::LessThan,
+            Instruction::JumpIfFalse(positive_id),
+            Instruction::Push(Value::Num(0.0)), Instruction::Push(Value::Variable(difference)), Instruction::Subtract,
+            Instruction::Store(difference),
+            Instruction::Label(positive_id),
+            Instruction::Push(Value::Variable(difference)), Instruction::Push(Value::Variable(epsilon)), Instruction::LessThan,
+            Instruction::ReturnValue
+        ]
     }
 
     /// Ensure the validity and evaluate a top-level statement (function
@@ -64,14 +357,26 @@ impl<T: Iterator<Item=parsing::Statement>> Checker<T> {
     fn eval_top_level_stmt(&mut self, stmt: parsing::Statement) -> super::Result<Vec<super::Instruction>> {
         match stmt {
             parsing::Statement::FunctionDefinition { pos, identifier, parameters, return_type, body } => {
-                // Create a label for this function ("main" if the main function,
-                // "func" followed by a new ID otherwise):
+                // Reject reserved identifiers:
+                if super::is_reserved_identifier(&identifier) {
+                    return Err(super::Failure::ReservedIdentifier(pos, identifier));
+                }
+
+                let is_entry_point = identifier == self.options.entry_point && !self.options.library;
+
+                // The entry point is called without any arguments:
+                if is_entry_point && !parameters.is_empty() {
+                    return Err(super::Failure::EntryPointParameters(pos, identifier));
+                }
+
+                // Create a label for this function (its own identifier if the
+                // entry point, "func" followed by a new ID otherwise):
                 let label = {
-                    if identifier == "main" && parameters.is_empty() {
+                    if is_entry_point {
                         self.main_defined = true;
                         identifier.clone()
                     }
-                    else { format!("func{}", self.new_id()) }
+                    else { super::LabelKind::Function.label(self.new_id()) }
                 };
 
                 // Check the declared return type is actually a real type:
@@ -81,16 +386,22 @@ impl<T: Iterator<Item=parsing::Statement>> Checker<T> {
                 for param in parameters.iter() {
                     param_types.push(super::Type::from_identifier(&param.param_type)?);
                 }
-                let checked_parameters = parameters.into_iter().map(|x| x.identifier).zip(param_types.clone().into_iter()).collect();
+                let checked_parameters = parameters.into_iter().map(|x| (x.identifier, x.pos)).zip(param_types.clone()).collect();
 
                 // Check if the function already exists:
-                if self.function_lookup(&identifier, param_types.as_slice(), &pos).is_ok() {
+                if let Ok(existing) = self.function_lookup(&identifier, param_types.as_slice(), &pos) {
+                    if existing.return_type != checked_return_type {
+                        return Err(super::Failure::ConflictingOverload {
+                            pos, identifier, params: param_types,
+                            existing: existing.return_type.clone(), encountered: checked_return_type
+                        });
+                    }
                     return Err(super::Failure::RedefinedExistingFunction(identifier, param_types.to_vec()))
                 }
                 else {
                     // Create the function definition before evaluating the body
                     // so as to allow recursion:
-                    self.add_function_def(identifier.clone(), param_types.clone(), checked_return_type.clone(), label.clone());
+                    self.add_function_def(identifier.clone(), param_types.clone(), checked_return_type.clone(), label.clone(), pos.clone());
                 }
 
                 // Evaluate the function body:
@@ -99,6 +410,15 @@ impl<T: Iterator<Item=parsing::Statement>> Checker<T> {
                 let mut instructions = vec![super::Instruction::Function { label, local_variable_count }];
                 instructions.extend(body_instructions);
 
+                if self.options.propagate_constants {
+                    super::propagation::propagate_constants(&mut instructions);
+                }
+
+                if self.options.hoist_loop_invariants {
+                    let id_counter = &mut self.id_counter;
+                    super::hoisting::hoist_loop_invariants(&mut instructions, || super::take_id(id_counter));
+                }
+
                 // Return type specified in function signature:
                 if let Some(expected_return_type) = checked_return_type {
                     // Function body should return something if a return type
@@ -151,25 +471,71 @@ impl<T: Iterator<Item=parsing::Statement>> Checker<T> {
     /// item in the tuple will be a return type and stream position should
     /// the statement be a return statement or an if or while statement with a
     /// block containing a return statement.
-    fn eval_inner_stmt(&mut self, stmt: parsing::Statement) -> super::Result<(Vec<super::Instruction>, usize, Option<(super::Type, stream::Position)>)> {
+    fn eval_inner_stmt(&mut self, stmt: parsing::Statement) -> super::Result<InnerStmtEvaluation> {
         match stmt {
-            parsing::Statement::Return(Some(expr)) => {
+            parsing::Statement::Return { value: Some(expr), .. } => {
                 let (mut instructions, ret_type, pos) = self.eval_expr(expr)?;
                 instructions.push(super::Instruction::ReturnValue);
                 Ok((instructions, 0, Some((ret_type, pos))))
             }
-            parsing::Statement::Return(None) =>
+            parsing::Statement::Return { value: None, .. } =>
                 Ok((vec![super::Instruction::ReturnVoid], 0, None)),
 
             parsing::Statement::Display(expr) => {
                 let (mut instructions, value_type, pos) = self.eval_expr(expr)?;
+                if let super::Type::Function(_) = value_type { return Err(super::Failure::FunctionDisplayed(pos)); }
+
                 instructions.push(super::Instruction::Display {
                     value_type, line_number: pos.line_number
                 });
                 Ok((instructions, 0, None))
             }
 
+            parsing::Statement::Print { args, newline, .. } => {
+                let mut instructions = Vec::new();
+
+                // Each value is printed according to its own type, seperated by
+                // a space character:
+                for (i, arg) in args.into_iter().enumerate() {
+                    if i > 0 {
+                        instructions.extend(vec![
+                            super::Instruction::Push(super::Value::Char(' ')),
+                            super::Instruction::Print(super::Type::Char)
+                        ]);
+                    }
+
+                    let (arg_instructions, arg_type, pos) = self.eval_expr(arg)?;
+                    if let super::Type::Function(_) = arg_type { return Err(super::Failure::FunctionDisplayed(pos)); }
+
+                    instructions.extend(arg_instructions);
+                    instructions.push(super::Instruction::Print(arg_type));
+                }
+
+                if newline {
+                    instructions.extend(vec![
+                        super::Instruction::Push(super::Value::Char('\n')),
+                        super::Instruction::Print(super::Type::Char)
+                    ]);
+                }
+
+                Ok((instructions, 0, None))
+            }
+
+            parsing::Statement::Assert { pos, condition } => {
+                let (instructions, _) = self.expect_expr_type(condition, super::Type::Bool)?;
+                Ok((self.assertion_instructions(instructions, pos.line_number), 0, None))
+            }
+
+            parsing::Statement::AssertEq { pos, left, right } => {
+                // Both values must be of the same type as with the == operator:
+                let (instructions, _, _) = self.eval_expr(parsing::Expression::Equal(Box::new(left), Box::new(right)))?;
+                Ok((self.assertion_instructions(instructions, pos.line_number), 0, None))
+            }
+
             parsing::Statement::While { condition, block } => {
+                let constant_condition = self.check_constant_condition(&condition);
+                self.check_loop_condition_changes(&condition, &block);
+
                 let block_end_id = self.new_id();
                 let start_id = self.new_id();
                 
@@ -178,13 +544,28 @@ impl<T: Iterator<Item=parsing::Statement>> Checker<T> {
                     super::Instruction::Label(start_id)
                 ];
 
+                // Loop body may never be executed so any variables assigned
+                // within it may still be uninitialised after the loop (and
+                // when the condition is first evaluated):
+                let uninitialised = self.uninitialised.borrow().clone();
                 let (block_instructions, block_locals_count, block_ret_type) = self.eval_block(block, vec![])?;
+                self.uninitialised.replace(uninitialised);
+
                 instructions.extend(block_instructions);
                 instructions.push(super::Instruction::Label(block_end_id));
 
                 let (condition_instructions, pos) = self.expect_expr_type(condition, super::Type::Bool)?;
-                instructions.extend(condition_instructions);
-                instructions.push(super::Instruction::JumpIfTrue(start_id));
+
+                match constant_condition {
+                    // Loop body never executed so remove the loop entirely:
+                    Some(false) => instructions.clear(),
+                    // Always jump back to the start of the loop body:
+                    Some(true) => instructions.push(super::Instruction::Jump(start_id)),
+                    None => {
+                        instructions.extend(condition_instructions);
+                        instructions.push(super::Instruction::JumpIfTrue(start_id));
+                    }
+                }
 
                 Ok((
                     instructions, block_locals_count,
@@ -193,15 +574,27 @@ impl<T: Iterator<Item=parsing::Statement>> Checker<T> {
             }
 
             parsing::Statement::If { condition, block } => {
+                let constant_condition = self.check_constant_condition(&condition);
+
                 let skip_block_id = self.new_id();
 
                 let (mut instructions, pos) = self.expect_expr_type(condition, super::Type::Bool)?;
                 instructions.push(super::Instruction::JumpIfFalse(skip_block_id));
 
+                let uninitialised = self.uninitialised.borrow().clone();
                 let (block_instructions, block_locals_count, block_ret_type) = self.eval_block(block, vec![])?;
-                instructions.extend(block_instructions);
-
-                instructions.push(super::Instruction::Label(skip_block_id));
+                self.uninitialised.replace(uninitialised);
+
+                match constant_condition {
+                    // Block never executed so remove it entirely:
+                    Some(false) => instructions.clear(),
+                    // Block always executed so no need for the condition:
+                    Some(true) => instructions = block_instructions,
+                    None => {
+                        instructions.extend(block_instructions);
+                        instructions.push(super::Instruction::Label(skip_block_id));
+                    }
+                }
 
                 Ok(
                     if let Some(ret_type) = block_ret_type { (instructions, block_locals_count, Some((ret_type, pos))) }
@@ -209,8 +602,9 @@ impl<T: Iterator<Item=parsing::Statement>> Checker<T> {
                 )
             }
 
-            parsing::Statement::VariableDeclaration { var_type, identifier, value } => {
+            parsing::Statement::VariableDeclaration { pos, var_type, identifier, value } => {
                 let checked_type = super::Type::from_identifier(&var_type)?;
+                self.ensure_not_function_identifier(&identifier, &pos)?;
                 let mut local_variable_count = 0;
                 let mut instructions = Vec::new();
 
@@ -235,11 +629,14 @@ impl<T: Iterator<Item=parsing::Statement>> Checker<T> {
                     else {
                         log::trace!("Introducing variable '{}' to current scope", identifier);
 
-                        let id = self.add_variable_def_to_inner_scope(identifier, checked_type.clone());
+                        self.check_parameter_shadowed(&identifier, &pos);
+                        let id = self.add_variable_def_to_inner_scope(identifier, checked_type.clone(), pos, super::SymbolKind::Variable);
                         
                         instructions.push(super::Instruction::Local(id));
                         local_variable_count = 1;
 
+                        if value.is_none() { self.uninitialised.borrow_mut().push(id); }
+
                         id
                     }
                 };
@@ -251,19 +648,21 @@ impl<T: Iterator<Item=parsing::Statement>> Checker<T> {
 
                     // Store the initial value in the variable:
                     instructions.push(super::Instruction::Store(var_id));
+                    self.uninitialised.borrow_mut().retain(|id| *id != var_id);
                 }
 
                 Ok((instructions, local_variable_count, None))
             }
 
-            parsing::Statement::VariableAssignment { identifier, assign_to } => {
+            parsing::Statement::VariableAssignment { pos, identifier, assign_to } => {
                 let mut instructions = Vec::new();
 
                 let var_id = {
                     let (expr_instructions, assign_to_type, strm_pos) = self.eval_expr(assign_to)?;
                     instructions.extend(expr_instructions);
                     
-                    let var_def = self.variable_lookup(&identifier, &strm_pos)?;
+                    let var_def = self.variable_lookup(&identifier, &pos)?;
+                    self.add_reference(&pos, &var_def.pos);
 
                     if var_def.var_type != assign_to_type {
                         return Err(super::Failure::UnexpectedType {
@@ -277,6 +676,7 @@ impl<T: Iterator<Item=parsing::Statement>> Checker<T> {
                 };
 
                 instructions.push(super::Instruction::Store(var_id));
+                self.uninitialised.borrow_mut().retain(|id| *id != var_id);
 
                 // A variable assignment modifies a previously declared local
                 // variable so does not increase the local variable count:
@@ -294,13 +694,38 @@ impl<T: Iterator<Item=parsing::Statement>> Checker<T> {
     /// statements then it will be ensured that they are all returning the same
     /// type. Also returns the number of local variables created (excluding
     /// parameters) within the block as the second part of the returned tuple.
-    fn eval_block(&mut self, block: parsing::Block, params: Vec<(String, super::Type)>) -> super::Result<(Vec<super::Instruction>, usize, Option<super::Type>)> {
-        let mut instructions = Vec::new();
+    fn eval_block(&mut self, block: parsing::Block, params: Vec<((String, stream::Position), super::Type)>) -> super::Result<(Vec<super::Instruction>, usize, Option<super::Type>)> {
+        // Scope begins at the line of any parameters, otherwise the first
+        // statement:
+        let first_line = params.first().map(|((_, pos), _)| pos.line_number)
+            .or_else(|| block.first().map(|stmt| stmt.pos().line_number))
+            .unwrap_or(self.last_line);
 
         self.begin_new_scope();
+        let result = self.eval_block_in_scope(block, params);
+
+        // The scope is ended regardless of whether checking the block failed so
+        // that the scope stack remains balanced:
+        if result.is_ok() {
+            self.analysis.scopes.push(super::ScopeSpan {
+                depth: self.scopes.len(), first_line, last_line: self.last_line.max(first_line)
+            });
+        }
+        self.end_scope();
+
+        result
+    }
+
+    /// Check the parameters and statements of a block as with `eval_block`
+    /// once the scope of that block has been begun.
+    fn eval_block_in_scope(&mut self, block: parsing::Block, params: Vec<((String, stream::Position), super::Type)>) -> super::Result<(Vec<super::Instruction>, usize, Option<super::Type>)> {
+        let mut instructions = Vec::new();
 
-        for (identifier, param_type) in params.into_iter().rev() {
-            let var_id = self.add_variable_def_to_inner_scope(identifier, param_type);
+        // Last argument is nearest the top of the stack so introduce parameters
+        // in reverse order (see `Instruction::Parameter`):
+        for ((identifier, pos), param_type) in params.into_iter().rev() {
+            self.ensure_not_function_identifier(&identifier, &pos)?;
+            let var_id = self.add_variable_def_to_inner_scope(identifier, param_type, pos, super::SymbolKind::Parameter);
             instructions.push(super::Instruction::Parameter(var_id));
         }
 
@@ -308,6 +733,10 @@ impl<T: Iterator<Item=parsing::Statement>> Checker<T> {
         let mut local_variable_count = 0;
 
         for stmt in block {
+            let line_number = stmt.pos().line_number;
+            if self.options.mark_lines { instructions.push(super::Instruction::MarkLine(line_number)); }
+            self.last_line = self.last_line.max(line_number);
+
             let (inner_instructions, inner_locals_count, optional_ret_info) = self.eval_inner_stmt(stmt)?;
             instructions.extend(inner_instructions);
             local_variable_count += inner_locals_count;
@@ -326,11 +755,40 @@ impl<T: Iterator<Item=parsing::Statement>> Checker<T> {
             }
         }
 
-        self.end_scope();
-
         Ok((instructions, local_variable_count, ret_type))
     }
 
+    /// Produce a warning should the given condition of an if or while statement
+    /// always evaluate to the same value. Returns that value if dead branches
+    /// are to be removed so that the caller can simplify the statement.
+    fn check_constant_condition(&mut self, condition: &parsing::Expression) -> Option<bool> {
+        if let Some(super::Value::Bool(value)) = const_eval(condition) {
+            log::trace!("Condition at {} always evaluates to {}", condition.pos(), value);
+
+            self.warnings.borrow_mut().push(super::Warning::ConstantCondition(condition.pos().clone(), value));
+
+            if self.options.remove_dead_branches { return Some(value); }
+        }
+        None
+    }
+
+    /// Produce a warning should the given condition of a while statement only
+    /// refer to variables that are never assigned to in the loop body. To avoid
+    /// false positives, no warning is produced for conditions containing
+    /// function calls or for loop bodies containing return statements.
+    fn check_loop_condition_changes(&mut self, condition: &parsing::Expression, block: &parsing::Block) {
+        let mut condition_variables = Vec::new();
+        if !variables_in_expr(condition, &mut condition_variables) || condition_variables.is_empty() { return; }
+
+        let mut assigned = Vec::new();
+        if !assigned_variables(block, &mut assigned) { return; }
+
+        if !condition_variables.iter().any(|ident| assigned.contains(ident)) {
+            log::trace!("Condition of while loop at {} never changes", condition.pos());
+            self.warnings.borrow_mut().push(super::Warning::LoopConditionNeverChanges(condition.pos().clone()));
+        }
+    }
+
     /// Introduce a new, inner-most scope which is added to the end of the scope
     /// stack.
     fn begin_new_scope(&mut self) {
@@ -342,6 +800,7 @@ impl<T: Iterator<Item=parsing::Statement>> Checker<T> {
     fn end_scope(&mut self) {
         if let Some(previous_scope) = self.scopes.pop() {
             for def in previous_scope.variables {
+                self.uninitialised.borrow_mut().retain(|id| *id != def.id);
                 self.available_local_variable_ids.push(def.id);
             }
         }
@@ -364,12 +823,26 @@ impl<T: Iterator<Item=parsing::Statement>> Checker<T> {
         Err(super::Failure::FunctionUndefined(strm_pos.clone(), ident.to_string(), params.to_vec()))
     }
 
-    fn add_function_def(&mut self, identifier: String, parameter_types: Vec<super::Type>, return_type: Option<super::Type>, label: String) {
+    fn add_function_def(&mut self, identifier: String, parameter_types: Vec<super::Type>, return_type: Option<super::Type>, label: String, pos: stream::Position) {
+        for param_type in &parameter_types { self.record_type(param_type); }
+        self.add_symbol(identifier.clone(), super::SymbolKind::Function, return_type.clone(), pos.clone());
+
         self.functions.push(super::FunctionDef {
-            identifier, parameter_types, return_type, label
+            identifier, parameter_types, return_type, label, pos
         });
     }
 
+    /// Ensure that no function has been defined with the given identifier so
+    /// that a variable or parameter may be introduced with that identifier. A
+    /// variable and a function cannot share an identifier as the identifier of
+    /// a function may also be used to refer to that function as a value.
+    fn ensure_not_function_identifier(&self, ident: &str, strm_pos: &stream::Position) -> super::Result<()> {
+        if self.functions.iter().any(|def| def.identifier == ident) {
+            Err(super::Failure::NameClash(strm_pos.clone(), ident.to_string()))
+        }
+        else { Ok(()) }
+    }
+
     /// Search the current accessible scopes for the variable definition with
     /// the given identifier.
     fn variable_lookup(&self, ident: &str, strm_pos: &stream::Position) -> super::Result<&super::VariableDef> {
@@ -383,29 +856,133 @@ impl<T: Iterator<Item=parsing::Statement>> Checker<T> {
         Err(super::Failure::VariableNotInScope(strm_pos.clone(), ident.to_string()))
     }
 
-    fn add_variable_def_to_inner_scope(&mut self, identifier: String, var_type: super::Type) -> super::Id {
+    /// Check whether a variable with the given identifier would collide with an
+    /// existing definition should it be introduced to the scope at the given
+    /// index of the scope stack (0 being the outer-most scope). Such a variable
+    /// would collide if it either clashes with a variable in that same scope or
+    /// shadows one in an enclosing scope. Intended for tooling to check that a
+    /// rename is safe.
+    pub fn would_collide(&self, new_name: &str, scope: usize) -> bool {
+        self.scopes.iter().take(scope + 1).any(|s| s.find_variable_def(new_name).is_some())
+    }
+
+    fn add_variable_def_to_inner_scope(&mut self, identifier: String, var_type: super::Type, pos: stream::Position, kind: super::SymbolKind) -> super::Id {
         let id = {
             if let Some(available_id) = self.available_local_variable_ids.pop() { available_id }
             else { self.new_id() }
         };
+
+        self.add_symbol(identifier.clone(), kind, Some(var_type.clone()), pos.clone());
         
         self.get_inner_scope().variables.push(super::VariableDef {
-            identifier, var_type, id
+            identifier, var_type, id, pos, kind
         });
         
         id
     }
 
+    /// Record a definition for tooling purposes.
+    fn add_symbol(&mut self, name: String, kind: super::SymbolKind, symbol_type: Option<super::Type>, pos: stream::Position) {
+        let scope_depth = self.scopes.len();
+        if let Some(t) = &symbol_type { self.record_type(t); }
+        self.analysis.symbols.push(super::Symbol { name, kind, symbol_type, pos, scope_depth });
+    }
+
+    /// Record that the given type (along with the parameter and return types
+    /// should it be a function type) is used by the program.
+    fn record_type(&self, t: &super::Type) {
+        if let super::Type::Function(func) = t {
+            for param_type in &func.params { self.record_type(param_type); }
+            if let Some(ret) = &func.ret { self.record_type(ret); }
+        }
+
+        let mut types = self.types.borrow_mut();
+        if !types.contains(t) { types.push(t.clone()); }
+    }
+
+    /// Record a reference made at a given position to the definition at another
+    /// position for tooling purposes.
+    fn add_reference(&self, pos: &stream::Position, definition: &stream::Position) {
+        self.references.borrow_mut().push(super::Reference { pos: pos.clone(), definition: definition.clone() });
+    }
+
+    /// Report a warning should a variable with the given identifier be about to
+    /// be introduced to the inner scope while a parameter of the same name is
+    /// accessible from an enclosing scope.
+    fn check_parameter_shadowed(&self, identifier: &str, pos: &stream::Position) {
+        if let Ok(super::VariableDef { kind: super::SymbolKind::Parameter, .. }) = self.variable_lookup(identifier, pos) {
+            log::trace!("Variable '{}' declared at {} shadows a parameter", identifier, pos);
+            self.warnings.borrow_mut().push(super::Warning::ParameterShadowed(pos.clone(), identifier.to_string()));
+        }
+    }
+
+    /// Produce a warning should the given variable, referred to at the given
+    /// position, possibly not have been given a value yet.
+    fn check_initialised(&self, pos: &stream::Position, def: &super::VariableDef) {
+        if self.uninitialised.borrow().contains(&def.id) {
+            log::trace!("Variable '{}' at {} may be used before it is given a value", def.identifier, pos);
+            self.warnings.borrow_mut().push(super::Warning::UseBeforeInit(pos.clone(), def.identifier.clone()));
+        }
+    }
+
+    /// Determine the type of a given expression should it be evaluated in the
+    /// current scope. Allows for tooling to query the type of any expression
+    /// without modifying the state of the checker.
+    pub fn infer_type(&self, expr: &parsing::Expression) -> super::Result<super::Type> {
+        // Discard any references, warnings, and types (of the expression as a
+        // whole and of each subexpression) recorded while evaluating the
+        // expression, and restore the variables considered uninitialised (as
+        // an assignment expression is never actually performed):
+        let uninitialised = self.uninitialised.borrow().clone();
+        let reference_count = self.references.borrow().len();
+        let warning_count = self.warnings.borrow().len();
+        let type_count = self.types.borrow().len();
+        let expression_type_count = self.expression_types.borrow().len();
+        let result = self.eval_expr(expr.clone()).map(|(_, expr_type, _)| expr_type);
+        self.references.borrow_mut().truncate(reference_count);
+        self.warnings.borrow_mut().truncate(warning_count);
+        self.types.borrow_mut().truncate(type_count);
+        self.expression_types.borrow_mut().truncate(expression_type_count);
+        self.uninitialised.replace(uninitialised);
+
+        result
+    }
+
     /// Check the validity of a given expression as well as return the appropriate
     /// instructions to be inserted into the final IR.
     fn eval_expr(&self, expr: parsing::Expression) -> super::Result<(Vec<super::Instruction>, super::Type, stream::Position)> {
+        let depth = self.expression_depth.get();
+
+        if depth >= self.options.max_expression_depth {
+            return Err(super::Failure::ExpressionTooDeep(expr.pos().clone()));
+        }
+
+        let pos = expr.pos().clone();
+
+        self.expression_depth.set(depth + 1);
+        let result = self.eval_expr_unguarded(expr);
+        self.expression_depth.set(depth);
+
+        if let Ok((_, expr_type, _)) = &result {
+            self.record_type(expr_type);
+            self.expression_types.borrow_mut().push((pos, expr_type.clone()));
+        }
+        result
+    }
+
+    fn eval_expr_unguarded(&self, expr: parsing::Expression) -> super::Result<(Vec<super::Instruction>, super::Type, stream::Position)> {
         match expr {
             parsing::Expression::Variable { pos, identifier } => {
                 log::trace!("Searching scope for the type of referenced variable with identifier '{}'", identifier);
 
-                let (var_type, id) = { // TODO: Check if variable is initialised before use!
-                    let def = self.variable_lookup(&identifier, &pos)?;
-                    (def.var_type.clone(), def.id)
+                let (var_type, id) = match self.variable_lookup(&identifier, &pos) {
+                    Ok(def) => {
+                        self.add_reference(&pos, &def.pos);
+                        self.check_initialised(&pos, def);
+                        (def.var_type.clone(), def.id)
+                    }
+                    // Identifier may instead refer to a function:
+                    Err(failure) => return self.eval_function_reference(pos, identifier, failure)
                 };
 
                 Ok((
@@ -414,33 +991,30 @@ impl<T: Iterator<Item=parsing::Statement>> Checker<T> {
                 ))
             }
 
-            parsing::Expression::FunctionCall {pos, identifier, args } => {
-                log::trace!("Searching scope for the return type of referenced function '{}' given arguments {:?}", identifier, args);
+            parsing::Expression::FunctionCall { pos, identifier, args } => self.eval_function_call(pos, identifier, args),
 
-                let mut instructions = Vec::new();
+            parsing::Expression::Cast { pos, expr, target_type } => self.eval_cast(pos, *expr, &target_type),
 
-                let mut arg_types = Vec::new();
-                for arg in args {
-                    let (arg_instructions, arg_type, _) = self.eval_expr(arg)?;
+            parsing::Expression::Assignment { pos, identifier, value } => {
+                let (mut instructions, value_type, value_pos) = self.eval_expr(*value)?;
 
-                    instructions.extend(arg_instructions);
-                    arg_types.push(arg_type); 
-                }
+                let var_def = self.variable_lookup(&identifier, &pos)?;
+                self.add_reference(&pos, &var_def.pos);
 
-                let (ident, option_ret_type, label) = {
-                    let def = self.function_lookup(&identifier, arg_types.as_slice(), &pos)?;
-                    (def.identifier.clone(), def.return_type.clone(), def.label.clone())
-                };
+                if var_def.var_type != value_type {
+                    return Err(super::Failure::UnexpectedType {
+                        pos: value_pos,
+                        encountered: value_type,
+                        expected: var_def.var_type.clone()
+                    });
+                }
 
-                instructions.push(
-                    if option_ret_type.is_some() { super::Instruction::CallExpectingValue(label) }
-                    else { super::Instruction::CallExpectingVoid(label) }
-                );
+                // The value assigned remains on the stack as the value of the
+                // assignment itself:
+                instructions.push(super::Instruction::StoreAndKeep(var_def.id));
+                self.uninitialised.borrow_mut().retain(|id| *id != var_def.id);
 
-                match option_ret_type {
-                    Some(ret_type) => Ok((instructions, ret_type, pos)),
-                    None => Err(super::Failure::VoidFunctionInExpr(pos, ident, arg_types))
-                }
+                Ok((instructions, value_type, pos))
             }
 
             parsing::Expression::Add(l, r) => {
@@ -463,12 +1037,19 @@ impl<T: Iterator<Item=parsing::Statement>> Checker<T> {
                 Ok((instructions, super::Type::Num, pos))
             }
 
+            parsing::Expression::Modulo(l, r) => {
+                let (instructions, pos) = self.eval_arithmetic_expr(*l, *r, super::Instruction::Modulo, "modulo")?;
+                Ok((instructions, super::Type::Num, pos))
+            }
+
             parsing::Expression::GreaterThan(l, r) => {
+                self.ensure_not_chained_comparison(&l, &r)?;
                 let (instructions, pos) = self.eval_arithmetic_expr(*l, *r, super::Instruction::GreaterThan, "greater than")?;
                 Ok((instructions, super::Type::Bool, pos))
             }
 
             parsing::Expression::LessThan(l, r) => {
+                self.ensure_not_chained_comparison(&l, &r)?;
                 let (instructions, pos) = self.eval_arithmetic_expr(*l, *r, super::Instruction::LessThan, "less than")?;
                 Ok((instructions, super::Type::Bool, pos))
             }
@@ -476,14 +1057,11 @@ impl<T: Iterator<Item=parsing::Statement>> Checker<T> {
             parsing::Expression::Equal(left, right) => {
                 log::trace!("Verifying types of equality expression - types on both sides of the operator should be the same");
 
-                let (mut instructions, left_type, strm_pos) = self.eval_expr(*left)?;
+                let (instructions, left_type, strm_pos) = self.eval_expr(*left)?;
                 let (right_instructions, right_type, _) = self.eval_expr(*right)?;
 
                 if left_type == right_type {
-                    instructions.extend(right_instructions);
-                    instructions.push(super::Instruction::Equals);
-
-                    Ok((instructions, super::Type::Bool, strm_pos))
+                    Ok((self.equality_instructions(instructions, right_instructions), super::Type::Bool, strm_pos))
                 }
                 else {
                     Err(super::Failure::UnexpectedType {
@@ -494,11 +1072,19 @@ impl<T: Iterator<Item=parsing::Statement>> Checker<T> {
                 }
             }
 
+            parsing::Expression::Xor(l, r) => self.eval_xor(*l, *r),
+
             parsing::Expression::BooleanNot(expr) => {
                 log::trace!("Verifying type of expression to which boolean NOT operator is being applied - expecting Bool expression to right of operator");
 
                 let (mut instructions, strm_pos) = self.expect_expr_type(*expr, super::Type::Bool)?;
-                instructions.push(super::Instruction::Not);
+
+                // Two consecutive NOT operations cancel one another out:
+                if self.options.fold_boolean_identities && instructions.last() == Some(&super::Instruction::Not) {
+                    log::trace!("Folding double boolean NOT at {}", strm_pos);
+                    instructions.pop();
+                }
+                else { instructions.push(super::Instruction::Not); }
 
                 Ok((instructions, super::Type::Bool, strm_pos))
             }
@@ -508,7 +1094,7 @@ impl<T: Iterator<Item=parsing::Statement>> Checker<T> {
 
                 let mut instructions = vec![super::Instruction::Push(super::Value::Num(0.0))];
                 
-                let (contained_instructions, strm_pos) = self.expect_expr_type(*expr, super::Type::Num)?;
+                let (contained_instructions, strm_pos) = self.expect_num_operand(*expr)?;
                 instructions.extend(contained_instructions);
                 
                 instructions.push(super::Instruction::Subtract);
@@ -527,13 +1113,101 @@ impl<T: Iterator<Item=parsing::Statement>> Checker<T> {
         }
     }
 
+    /// Evaluate a reference to a function as a value (e.g. `double` rather than
+    /// the call `double(x)`). The function must not be overloaded so that the
+    /// type of the value is known. The given failure is returned should there
+    /// be no function with the given identifier.
+    fn eval_function_reference(&self, pos: stream::Position, identifier: String, not_found: super::Failure) -> super::Result<(Vec<super::Instruction>, super::Type, stream::Position)> {
+        let mut defs = self.functions.iter().filter(|def| def.identifier == identifier);
+
+        match (defs.next(), defs.next()) {
+            (Some(def), None) => {
+                self.add_reference(&pos, &def.pos);
+                Ok((
+                    vec![super::Instruction::Push(super::Value::Function(def.label.clone()))],
+                    def.function_type(), pos
+                ))
+            }
+            (Some(_), Some(_)) => Err(super::Failure::AmbiguousFunctionReference(pos, identifier)),
+            (None, _) => Err(not_found)
+        }
+    }
+
+    /// Check the validity of a call to the function with the given identifier.
+    /// Should there be a variable of a function type with that identifier then
+    /// the function held by that variable is called instead.
+    fn eval_function_call(&self, pos: stream::Position, identifier: String, args: Vec<parsing::Expression>) -> super::Result<(Vec<super::Instruction>, super::Type, stream::Position)> {
+        log::trace!("Searching scope for the return type of referenced function '{}' given arguments {:?}", identifier, args);
+
+        let mut instructions = Vec::new();
+
+        let mut arg_types = Vec::new();
+        for arg in args {
+            let (arg_instructions, arg_type, _) = self.eval_expr(arg)?;
+
+            instructions.extend(arg_instructions);
+            arg_types.push(arg_type); 
+        }
+
+        let (option_ret_type, call_instruction) = match self.variable_lookup(&identifier, &pos) {
+            Ok(def @ super::VariableDef { var_type: super::Type::Function(function_type), .. }) => {
+                self.add_reference(&pos, &def.pos);
+
+                if function_type.params != arg_types {
+                    return Err(super::Failure::UnexpectedType {
+                        pos, expected: def.var_type.clone(),
+                        encountered: super::Type::Function(Box::new(super::FunctionType {
+                            params: arg_types, ret: function_type.ret.clone()
+                        }))
+                    });
+                }
+
+                // Function to call is placed on the stack after the arguments:
+                instructions.push(super::Instruction::Push(super::Value::Variable(def.id)));
+                (function_type.ret.clone(), super::Instruction::CallIndirect(arg_types.len()))
+            }
+
+            _ => {
+                let def = self.function_lookup(&identifier, arg_types.as_slice(), &pos)?;
+                self.add_reference(&pos, &def.pos);
+
+                let label = def.label.clone();
+                (
+                    def.return_type.clone(),
+                    if def.return_type.is_some() { super::Instruction::CallExpectingValue(label) }
+                    else { super::Instruction::CallExpectingVoid(label) }
+                )
+            }
+        };
+
+        instructions.push(call_instruction);
+
+        match option_ret_type {
+            Some(ret_type) => Ok((instructions, ret_type, pos)),
+            None => Err(super::Failure::VoidFunctionInExpr(pos, identifier, arg_types))
+        }
+    }
+
+    /// Ensure that neither operand of a greater than or less than comparison is
+    /// itself such a comparison (e.g. `a < b < c`, which would otherwise only
+    /// be reported as an unexpected Bool operand).
+    fn ensure_not_chained_comparison(&self, left: &parsing::Expression, right: &parsing::Expression) -> super::Result<()> {
+        for operand in &[left, right] {
+            if let parsing::Expression::GreaterThan(_, _) | parsing::Expression::LessThan(_, _) = operand {
+                return Err(super::Failure::ChainedComparison(operand.pos().clone()));
+            }
+        }
+        Ok(())
+    }
+
     /// Ensure the two sub-expressions of an arithmetic expression are both of
-    /// Num type. Insert the relevant final IR instruction also.
+    /// Num type (or Char when not using strict types). Insert the relevant final
+    /// IR instruction also.
     fn eval_arithmetic_expr(&self, left: parsing::Expression, right: parsing::Expression, operation_instruction: super::Instruction, expr_type: &str) -> super::Result<(Vec<super::Instruction>, stream::Position)> {
         log::trace!("Verifying types of {} expression - Num type on both sides of operator expected", expr_type);
 
-        let (mut instructions, strm_pos) = self.expect_expr_type(left, super::Type::Num)?;
-        let (right_instructions, _) = self.expect_expr_type(right, super::Type::Num)?;
+        let (mut instructions, strm_pos) = self.expect_num_operand(left)?;
+        let (right_instructions, _) = self.expect_num_operand(right)?;
 
         instructions.extend(right_instructions);
         instructions.push(operation_instruction);
@@ -541,6 +1215,94 @@ impl<T: Iterator<Item=parsing::Statement>> Checker<T> {
         Ok((instructions, strm_pos))
     }
 
+    /// Follow the instructions of the condition of an assertion with those that
+    /// skip past the failure of the assertion should that condition be true.
+    fn assertion_instructions(&mut self, mut instructions: Vec<super::Instruction>, line_number: u64) -> Vec<super::Instruction> {
+        let passed_id = self.new_id();
+
+        instructions.extend(vec![
+            super::Instruction::JumpIfTrue(passed_id),
+            super::Instruction::AssertionFailed(line_number),
+            super::Instruction::Label(passed_id)
+        ]);
+        instructions
+    }
+
+    /// Combine the instructions of the two (same-typed) operands of an equality
+    /// comparison. Comparing a Bool with the literal true simply gives that
+    /// Bool when folding boolean identities.
+    fn equality_instructions(&self, mut left: Vec<super::Instruction>, right: Vec<super::Instruction>) -> Vec<super::Instruction> {
+        let literal_true = [super::Instruction::Push(super::Value::Bool(true))];
+
+        if self.options.fold_boolean_identities {
+            if right == literal_true { return left; }
+            if left == literal_true { return right; }
+        }
+
+        left.extend(right);
+        left.push(super::Instruction::Equals);
+        left
+    }
+
+    /// Ensure that both operands of an exclusive or are Bool values. As exactly
+    /// one operand must be true for the result to be true, this is equivalent
+    /// to the operands being unequal (so no new instruction is required).
+    fn eval_xor(&self, left: parsing::Expression, right: parsing::Expression) -> super::Result<(Vec<super::Instruction>, super::Type, stream::Position)> {
+        let (mut instructions, strm_pos) = self.expect_expr_type(left, super::Type::Bool)?;
+        let (right_instructions, _) = self.expect_expr_type(right, super::Type::Bool)?;
+
+        instructions.extend(right_instructions);
+        instructions.extend(vec![super::Instruction::Equals, super::Instruction::Not]);
+
+        Ok((instructions, super::Type::Bool, strm_pos))
+    }
+
+    /// Ensure that the value of the given expression can be converted to the
+    /// given type and insert the instruction performing that conversion. Only
+    /// Char and Bool values may be cast to Num and only Num values may be cast
+    /// to Char (casting a value to its own type has no effect).
+    fn eval_cast(&self, pos: stream::Position, expr: parsing::Expression, target_type: &str) -> super::Result<(Vec<super::Instruction>, super::Type, stream::Position)> {
+        let to = super::Type::from_identifier(target_type)?;
+        let (mut instructions, from, _) = self.eval_expr(expr)?;
+
+        log::trace!("Verifying cast at {} from {:?} to {:?}", pos, from, to);
+
+        match (&from, &to) {
+            _ if from == to => {}
+
+            (super::Type::Char, super::Type::Num) | (super::Type::Bool, super::Type::Num) |
+            (super::Type::Num, super::Type::Char) => {
+                instructions.push(super::Instruction::Convert { from, to: to.clone() });
+            }
+
+            _ => return Err(super::Failure::InvalidCast { pos, from, to })
+        }
+
+        Ok((instructions, to, pos))
+    }
+
+    /// Ensure the given operand of an arithmetic operator is of Num type. When
+    /// strict types are disabled, a Char operand is also accepted and converted
+    /// to Num.
+    fn expect_num_operand(&self, expr: parsing::Expression) -> super::Result<(Vec<super::Instruction>, stream::Position)> {
+        let (mut instructions, expr_type, strm_pos) = self.eval_expr(expr)?;
+
+        match expr_type {
+            super::Type::Num => Ok((instructions, strm_pos)),
+
+            super::Type::Char if !self.options.strict_types => {
+                log::trace!("Implicitly converting Char operand at {} to Num", strm_pos);
+
+                instructions.push(super::Instruction::Convert { from: super::Type::Char, to: super::Type::Num });
+                Ok((instructions, strm_pos))
+            }
+
+            _ => Err(super::Failure::UnexpectedType {
+                pos: strm_pos, expected: super::Type::Num, encountered: expr_type
+            })
+        }
+    }
+
     fn expect_expr_type(&self, expr: parsing::Expression, expected: super::Type) -> super::Result<(Vec<super::Instruction>, stream::Position)> {
         let (instructions, expr_type, strm_pos) = self.eval_expr(expr)?;
         
@@ -553,41 +1315,205 @@ impl<T: Iterator<Item=parsing::Statement>> Checker<T> {
     }
 
     fn new_id(&mut self) -> super::Id {
-        let id = self.id_counter;
-        self.id_counter += 1;
-        id
+        super::take_id(&mut self.id_counter)
     }
 }
 
+/// Label of the built-in function `approx_eq`.
+fn approx_eq_label() -> String { super::LabelKind::named("builtin_approx_eq") }
+
+/// Definition of the built-in function `approx_eq` which is available in every
+/// till program. Floating-point arithmetic is inexact so comparing Num values
+/// using `==` often does not give the expected result (e.g. `0.1 + 0.2 == 0.3`
+/// is false) - `approx_eq(0.1 + 0.2, 0.3, 0.000001)` can be used instead.
+fn approx_eq_def() -> super::FunctionDef {
+    super::FunctionDef {
+        identifier: "approx_eq".to_string(),
+        parameter_types: vec![super::Type::Num, super::Type::Num, super::Type::Num],
+        return_type: Some(super::Type::Bool),
+        label: approx_eq_label(),
+        pos: stream::Position::new()
+    }
+}
 
+/// Label of the built-in function `arg_count`.
+fn arg_count_label() -> String { super::LabelKind::named("builtin_arg_count") }
+
+/// Definition of the built-in function `arg_count` which gives the number of
+/// command-line arguments the program was run with (including the name of the
+/// program itself, as with `argc` in C).
+fn arg_count_def() -> super::FunctionDef {
+    super::FunctionDef {
+        identifier: "arg_count".to_string(),
+        parameter_types: vec![],
+        return_type: Some(super::Type::Num),
+        label: arg_count_label(),
+        pos: stream::Position::new()
+    }
+}
 
-#[cfg(test)]
-mod tests {
-    use std::iter;
-    use crate::{ assert_pattern, parsing, checking, stream::Position };
+/// Instructions of the built-in function `arg_count() -> Num`.
+fn arg_count_instructions() -> Vec<super::Instruction> {
+    vec![
+        super::Instruction::Function { label: arg_count_label(), local_variable_count: 0 },
+        super::Instruction::ArgCount,
+        super::Instruction::ReturnValue
+    ]
+}
 
-    fn new_empty_checker() -> super::Checker<iter::Empty<parsing::Statement>> {
-        let mut chkr = super::Checker::new(iter::empty());
-        chkr.begin_new_scope();
-        chkr
+/// Evaluate an expression at compile time should it be made up of only literal
+/// values (i.e. perform constant folding). Returns `None` if the expression
+/// refers to any variables or functions, or if it is not well-typed. Used when
+/// checking for conditions that always evaluate to the same value.
+pub fn const_eval(expr: &parsing::Expression) -> Option<super::Value> {
+    use super::Value;
+
+    let fold_nums = |l: &parsing::Expression, r: &parsing::Expression| match (const_eval(l)?, const_eval(r)?) {
+        (Value::Num(x), Value::Num(y)) => Some((x, y)),
+        _ => None
+    };
+
+    match expr {
+        parsing::Expression::NumberLiteral { value, .. } => Some(Value::Num(*value)),
+        parsing::Expression::BooleanLiteral { value, .. } => Some(Value::Bool(*value)),
+        parsing::Expression::CharLiteral { value, .. } => Some(Value::Char(*value)),
+
+        parsing::Expression::Add(l, r) => fold_nums(l, r).map(|(x, y)| Value::Num(x + y)),
+        parsing::Expression::Subtract(l, r) => fold_nums(l, r).map(|(x, y)| Value::Num(x - y)),
+        parsing::Expression::Multiply(l, r) => fold_nums(l, r).map(|(x, y)| Value::Num(x * y)),
+        parsing::Expression::Divide(l, r) => fold_nums(l, r).map(|(x, y)| Value::Num(x / y)),
+        parsing::Expression::Modulo(l, r) => fold_nums(l, r).map(|(x, y)| Value::Num(x % y)),
+        parsing::Expression::GreaterThan(l, r) => fold_nums(l, r).map(|(x, y)| Value::Bool(x > y)),
+        parsing::Expression::LessThan(l, r) => fold_nums(l, r).map(|(x, y)| Value::Bool(x < y)),
+
+        // Numbers are compared by bit pattern as at run time (so 0 and -0
+        // differ yet NaN equals itself):
+        parsing::Expression::Equal(l, r) => match (const_eval(l)?, const_eval(r)?) {
+            (Value::Num(x), Value::Num(y)) => Some(Value::Bool(x.to_bits() == y.to_bits())),
+            (Value::Char(x), Value::Char(y)) => Some(Value::Bool(x == y)),
+            (Value::Bool(x), Value::Bool(y)) => Some(Value::Bool(x == y)),
+            _ => None
+        },
+
+        parsing::Expression::Xor(l, r) => match (const_eval(l)?, const_eval(r)?) {
+            (Value::Bool(x), Value::Bool(y)) => Some(Value::Bool(x != y)),
+            _ => None
+        },
+
+        parsing::Expression::BooleanNot(x) => match const_eval(x)? {
+            Value::Bool(value) => Some(Value::Bool(!value)),
+            _ => None
+        },
+
+        // Calculated by subtraction from 0 as at run time (so ~0 is 0 rather
+        // than -0):
+        parsing::Expression::UnaryMinus(x) => match const_eval(x)? {
+            Value::Num(value) => Some(Value::Num(0.0 - value)),
+            _ => None
+        },
+
+        parsing::Expression::Variable { .. } | parsing::Expression::FunctionCall { .. } |
+        parsing::Expression::Cast { .. } | parsing::Expression::Assignment { .. } => None
     }
+}
 
-    #[test]
-    fn scoping() {
-        let mut chkr = new_empty_checker();
+/// Collect the identifiers of the variables that the given expression refers
+/// to. Returns false should the expression contain a function call (in which
+/// case its value may change regardless of those variables).
+fn variables_in_expr<'a>(expr: &'a parsing::Expression, variables: &mut Vec<&'a str>) -> bool {
+    match expr {
+        parsing::Expression::Equal(l, r) | parsing::Expression::Xor(l, r) |
+        parsing::Expression::GreaterThan(l, r) | parsing::Expression::LessThan(l, r) |
+        parsing::Expression::Add(l, r) | parsing::Expression::Subtract(l, r) |
+        parsing::Expression::Multiply(l, r) | parsing::Expression::Divide(l, r) | parsing::Expression::Modulo(l, r) =>
+            variables_in_expr(l, variables) && variables_in_expr(r, variables),
+
+        parsing::Expression::BooleanNot(x) | parsing::Expression::UnaryMinus(x) |
+        parsing::Expression::Cast { expr: x, .. } => variables_in_expr(x, variables),
+
+        parsing::Expression::Variable { identifier, .. } => {
+            variables.push(identifier);
+            true
+        }
 
-        let pos = Position::new();
+        // Function calls and assignments may change the value of the
+        // expression regardless of the variables otherwise referred to:
+        parsing::Expression::FunctionCall { .. } | parsing::Expression::Assignment { .. } => false,
 
-        chkr.add_variable_def_to_inner_scope("outer".to_string(), checking::Type::Num);
+        parsing::Expression::NumberLiteral { .. } | parsing::Expression::CharLiteral { .. } |
+        parsing::Expression::BooleanLiteral { .. } => true
+    }
+}
+
+/// Collect the identifiers of the variables assigned to anywhere within the
+/// given block (including nested blocks). Returns false should the block
+/// contain a return statement.
+fn assigned_variables<'a>(block: &'a parsing::Block, assigned: &mut Vec<&'a str>) -> bool {
+    block.iter().all(|stmt| match stmt {
+        parsing::Statement::VariableAssignment { identifier, assign_to, .. } => {
+            assigned.push(identifier);
+            chained_assignments(assign_to, assigned);
+            true
+        }
+
+        parsing::Statement::VariableDeclaration { value: Some(value), .. } => {
+            chained_assignments(value, assigned);
+            true
+        }
+
+        parsing::Statement::If { block, .. } | parsing::Statement::While { block, .. } => assigned_variables(block, assigned),
+
+        parsing::Statement::Return { .. } => false,
+
+        _ => true
+    })
+}
+
+/// Collect the identifiers of the variables assigned to by the given value of
+/// a declaration or assignment (e.g. `y` in `x = y = 5`).
+fn chained_assignments<'a>(value: &'a parsing::Expression, assigned: &mut Vec<&'a str>) {
+    if let parsing::Expression::Assignment { identifier, value, .. } = value {
+        assigned.push(identifier);
+        chained_assignments(value, assigned);
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::approx_constant)]
+mod tests {
+    use std::iter;
+    use crate::{ parsing, checking, lexing::lexer, stream::{ Stream, Position } };
+
+    fn quick_parse(inp: &str) -> Vec<parsing::Statement> {
+        let final_inp = inp.trim().replace("    ", "\t");
+        let tokens = lexer::input(Stream::from_str(&final_inp)).map(Result::unwrap);
+        parsing::parser::input(tokens).map(Result::unwrap).collect()
+    }
+
+    fn new_empty_checker() -> super::Checker<iter::Empty<parsing::Statement>> {
+        let mut chkr = super::Checker::new(iter::empty(), checking::Options::default());
+        chkr.begin_new_scope();
+        chkr
+    }
+
+    #[test]
+    fn scoping() {
+        let mut chkr = new_empty_checker();
+
+        let pos = Position::new();
+
+        chkr.add_variable_def_to_inner_scope("outer".to_string(), checking::Type::Num, Position::new(), checking::SymbolKind::Variable);
         assert_eq!(chkr.variable_lookup("outer", &pos), Ok(&checking::VariableDef {
             identifier: "outer".to_string(),
             var_type: checking::Type::Num,
-            id: 0
+            id: 0,
+            pos: Position::new(),
+            kind: checking::SymbolKind::Variable
         }));
 
         chkr.begin_new_scope();
 
-        chkr.add_variable_def_to_inner_scope("inner".to_string(), checking::Type::Bool);
+        chkr.add_variable_def_to_inner_scope("inner".to_string(), checking::Type::Bool, Position::new(), checking::SymbolKind::Variable);
 
         assert!(chkr.variable_lookup("inner", &pos).is_ok());
         assert!(chkr.variable_lookup("outer", &pos).is_ok());
@@ -599,6 +1525,43 @@ mod tests {
         assert!(chkr.variable_lookup("undefined", &pos).is_err());
     }
 
+    #[test]
+    fn scopes_balanced_after_failure() {
+        let src = "main()\n    Num x = 1\n    if x > 0\n        while x > 0\n            display y\n    display x";
+        let body = match quick_parse(src).remove(0) {
+            parsing::Statement::FunctionDefinition { body, .. } => body,
+            other => panic!("{:?}", other)
+        };
+
+        // Failing within nested blocks still ends the scope of every block:
+        let mut chkr = super::Checker::new(iter::empty(), checking::Options::default());
+        let result = chkr.eval_block(body, Vec::new());
+        assert_pattern!(result, Err(checking::Failure::VariableNotInScope(_, _)));
+        assert!(chkr.scopes.is_empty());
+        assert!(chkr.uninitialised.borrow().is_empty());
+
+        // And so checking recovers at the next top-level statement without any
+        // further failure:
+        let src = format!("{}\nother() -> Num\n    Num z = 2\n    return z", src);
+        let failures = super::input_with_all_failures(quick_parse(&src).into_iter(), Default::default()).unwrap_err();
+        assert_pattern!(failures.as_slice(), [checking::Failure::VariableNotInScope(_, _)]);
+    }
+
+    #[test]
+    fn collisions() {
+        let mut chkr = new_empty_checker();
+
+        chkr.add_variable_def_to_inner_scope("outer".to_string(), checking::Type::Num, Position::new(), checking::SymbolKind::Variable);
+        chkr.begin_new_scope();
+        chkr.add_variable_def_to_inner_scope("inner".to_string(), checking::Type::Char, Position::new(), checking::SymbolKind::Variable);
+
+        assert!(chkr.would_collide("inner", 1));
+        assert!(chkr.would_collide("outer", 1));
+        assert!(chkr.would_collide("outer", 0));
+        assert!(!chkr.would_collide("inner", 0));
+        assert!(!chkr.would_collide("fresh", 1));
+    }
+
     #[test]
     fn eval_exprs() {
         let mut chkr = new_empty_checker();
@@ -713,7 +1676,7 @@ mod tests {
             Err(checking::Failure::VariableNotInScope(_, _))
         );
 
-        let var_id = chkr.add_variable_def_to_inner_scope("var".to_string(), checking::Type::Num);
+        let var_id = chkr.add_variable_def_to_inner_scope("var".to_string(), checking::Type::Num, Position::new(), checking::SymbolKind::Variable);
 
         chkr.begin_new_scope();
         assert_eq!(
@@ -728,7 +1691,7 @@ mod tests {
         );
         chkr.end_scope();
 
-        chkr.add_function_def("func".to_string(), vec![checking::Type::Char], Some(checking::Type::Num), "func0".to_string());
+        chkr.add_function_def("func".to_string(), vec![checking::Type::Char], Some(checking::Type::Num), "func0".to_string(), Position::new());
 
         assert_eq!(
             chkr.eval_expr(parsing::Expression::FunctionCall {
@@ -759,7 +1722,7 @@ mod tests {
             _ => panic!()
         }
 
-        chkr.add_function_def("abc".to_string(), vec![checking::Type::Char], None, "func1".to_string());
+        chkr.add_function_def("abc".to_string(), vec![checking::Type::Char], None, "func1".to_string(), Position::new());
 
         assert_pattern!(
             chkr.eval_expr(parsing::Expression::FunctionCall {
@@ -773,22 +1736,485 @@ mod tests {
         );
     }
 
+    #[test]
+    fn expression_depth() {
+        let pos = Position { position: 20, line_number: 2, line_position: 10 };
+
+        let nested = |depth: usize| (0..depth).fold(
+            parsing::Expression::BooleanLiteral { pos: pos.clone(), value: true },
+            |expr, _| parsing::Expression::BooleanNot(Box::new(expr))
+        );
+
+        let chkr = new_empty_checker();
+        assert!(chkr.eval_expr(nested(50)).is_ok());
+        assert_eq!(chkr.eval_expr(nested(5000)), Err(checking::Failure::ExpressionTooDeep(pos.clone())));
+
+        let mut limited_chkr = super::Checker::new(iter::empty(), checking::Options { max_expression_depth: 10, ..Default::default() });
+        limited_chkr.begin_new_scope();
+        assert!(limited_chkr.eval_expr(nested(9)).is_ok());
+        assert_eq!(limited_chkr.eval_expr(nested(10)), Err(checking::Failure::ExpressionTooDeep(pos)));
+    }
+
+    #[test]
+    fn void_function_results() {
+        let check = |src: &str| super::input(quick_parse(src).into_iter());
+
+        assert_eq!(
+            check("
+nothing()
+    return
+
+main()
+    Num x = nothing()"),
+            Err(checking::Failure::VoidFunctionInExpr(Position { position: 42, line_number: 5, line_position: 16 }, "nothing".to_string(), vec![]))
+        );
+
+        assert_pattern!(
+            check("
+nothing(Num x)
+    display x
+
+main()
+    Num y = 1
+    y = nothing(y)"),
+            Err(checking::Failure::VoidFunctionInExpr(_, _, _))
+        );
+    }
+
+    #[test]
+    fn function_values() {
+        let check = |src: &str| super::input(quick_parse(src).into_iter());
+
+        let instructions = check("
+double(Num x) -> Num
+    return x * 2
+
+main()
+    Fn(Num) -> Num f = double
+    display f(3)").unwrap();
+
+        assert!(instructions.contains(&checking::Instruction::Push(checking::Value::Function("till.func0".to_string()))));
+        assert!(instructions.contains(&checking::Instruction::CallIndirect(1)));
+        assert!(!instructions.contains(&checking::Instruction::CallExpectingValue("till.func0".to_string())));
+
+        assert_eq!(
+            checking::Type::from_identifier("Fn(Num, Fn(Char) -> Bool)"),
+            Ok(checking::Type::Function(Box::new(checking::FunctionType {
+                params: vec![
+                    checking::Type::Num,
+                    checking::Type::Function(Box::new(checking::FunctionType { params: vec![checking::Type::Char], ret: Some(checking::Type::Bool) }))
+                ],
+                ret: None
+            })))
+        );
+
+        assert_pattern!(
+            check("
+double(Num x) -> Num
+    return x * 2
+
+main()
+    Fn(Char) -> Num f = double"),
+            Err(checking::Failure::UnexpectedType { .. })
+        );
+
+        assert_pattern!(
+            check("
+apply(Fn(Num) -> Num f, Num x) -> Num
+    return f(x, x)
+
+main()
+    display 1"),
+            Err(checking::Failure::UnexpectedType { .. })
+        );
+
+        assert_pattern!(
+            check("
+double(Num x) -> Num
+    return x * 2
+
+double(Char x) -> Num
+    return 2
+
+main()
+    Fn(Num) -> Num f = double"),
+            Err(checking::Failure::AmbiguousFunctionReference(_, _))
+        );
+
+        assert_pattern!(
+            check("
+double(Num x) -> Num
+    return x * 2
+
+main()
+    display double"),
+            Err(checking::Failure::FunctionDisplayed(_))
+        );
+    }
+
+    #[test]
+    fn strict_types() {
+        let char_plus_num = parsing::Expression::Add(
+            Box::new(parsing::Expression::CharLiteral { pos: Position::new(), value: 'a' }),
+            Box::new(parsing::Expression::NumberLiteral { pos: Position::new(), value: 1.0 })
+        );
+
+        let strict_chkr = new_empty_checker();
+        assert!(strict_chkr.options.strict_types);
+        assert_pattern!(
+            strict_chkr.eval_expr(char_plus_num.clone()),
+            Err(checking::Failure::UnexpectedType {
+                expected: checking::Type::Num,
+                encountered: checking::Type::Char, pos: _
+            })
+        );
+
+        let mut lenient_chkr = super::Checker::new(iter::empty(), checking::Options { strict_types: false, ..Default::default() });
+        lenient_chkr.begin_new_scope();
+        assert_eq!(
+            lenient_chkr.eval_expr(char_plus_num),
+            Ok((
+                vec![
+                    checking::Instruction::Push(checking::Value::Char('a')),
+                    checking::Instruction::Convert { from: checking::Type::Char, to: checking::Type::Num },
+                    checking::Instruction::Push(checking::Value::Num(1.0)),
+                    checking::Instruction::Add
+                ],
+                checking::Type::Num, Position::new()
+            ))
+        );
+
+        // Implicit conversion only applies to the operands of arithmetic:
+        assert_pattern!(
+            lenient_chkr.eval_expr(parsing::Expression::Equal(
+                Box::new(parsing::Expression::CharLiteral { pos: Position::new(), value: 'a' }),
+                Box::new(parsing::Expression::NumberLiteral { pos: Position::new(), value: 97.0 })
+            )),
+            Err(checking::Failure::UnexpectedType { .. })
+        );
+    }
+
+    #[test]
+    fn ordering_bools() {
+        let check = |src: &str| super::input(quick_parse(src).into_iter());
+
+        assert_eq!(
+            check("main()\n    display true < false"),
+            Err(checking::Failure::UnexpectedType {
+                pos: Position { position: 20, line_number: 2, line_position: 13 },
+                expected: checking::Type::Num, encountered: checking::Type::Bool
+            })
+        );
+
+        // Position is that of the offending operand rather than of the entire comparison:
+        assert_eq!(
+            check("main()\n    display 1 > (2 == 2)"),
+            Err(checking::Failure::UnexpectedType {
+                pos: Position { position: 22, line_number: 2, line_position: 15 },
+                expected: checking::Type::Num, encountered: checking::Type::Bool
+            })
+        );
+    }
+
+    #[test]
+    fn const_eval() {
+        let num = |value| Box::new(parsing::Expression::NumberLiteral { pos: Position::new(), value });
+
+        let expr = parsing::Expression::Multiply(num(2.0), Box::new(parsing::Expression::Add(num(3.0), num(4.0))));
+        assert_eq!(super::const_eval(&expr), Some(checking::Value::Num(14.0)));
+
+        let var = Box::new(parsing::Expression::Variable { pos: Position::new(), identifier: "x".to_string() });
+        assert_eq!(super::const_eval(&parsing::Expression::Add(var, num(1.0))), None);
+
+        let call = parsing::Expression::FunctionCall { pos: Position::new(), identifier: "f".to_string(), args: vec![] };
+        assert_eq!(super::const_eval(&parsing::Expression::GreaterThan(Box::new(call), num(1.0))), None);
+
+        // Not well-typed:
+        let boolean = Box::new(parsing::Expression::BooleanLiteral { pos: Position::new(), value: true });
+        assert_eq!(super::const_eval(&parsing::Expression::Add(boolean, num(1.0))), None);
+
+        // Numbers compared by bit pattern:
+        let negative_zero = Box::new(parsing::Expression::Multiply(Box::new(parsing::Expression::UnaryMinus(num(1.0))), num(0.0)));
+        assert_eq!(super::const_eval(&parsing::Expression::Equal(num(0.0), negative_zero)), Some(checking::Value::Bool(false)));
+        assert_eq!(super::const_eval(&parsing::Expression::Equal(num(0.0), Box::new(parsing::Expression::UnaryMinus(num(0.0))))), Some(checking::Value::Bool(true)));
+        let nan = || Box::new(parsing::Expression::Divide(num(0.0), num(0.0)));
+        assert_eq!(super::const_eval(&parsing::Expression::Equal(nan(), nan())), Some(checking::Value::Bool(true)));
+    }
+
+    #[test]
+    fn approx_eq() {
+        let chkr = new_empty_checker();
+        let num = |value| parsing::Expression::NumberLiteral { pos: Position::new(), value };
+        let call = |args| parsing::Expression::FunctionCall { pos: Position::new(), identifier: "approx_eq".to_string(), args };
+
+        assert_eq!(chkr.infer_type(&call(vec![num(1.0), num(2.0), num(0.5)])), Ok(checking::Type::Bool));
+        assert_pattern!(chkr.infer_type(&call(vec![num(1.0), num(2.0)])), Err(checking::Failure::FunctionUndefined(_, _, _)));
+
+        let check = |src: &str| super::input_with_warnings(quick_parse(src).into_iter(), checking::Options::default());
+
+        // Only included in the final IR should it be used (and never reported as unused):
+        let (instructions, warnings) = check("main()\n    display 1").unwrap();
+        assert_eq!(instructions.iter().filter(|x| matches!(x, checking::Instruction::Function { .. })).count(), 1);
+        assert!(warnings.is_empty());
+
+        let (instructions, _) = check("main()\n    display approx_eq(1, 1.5, 1)").unwrap();
+        assert!(instructions.contains(&checking::Instruction::Function { label: super::approx_eq_label(), local_variable_count: 1 }));
+
+        assert_pattern!(
+            check("approx_eq(Num a, Num b, Num epsilon) -> Bool\n    return true\n\nmain()\n    display 1"),
+            Err(checking::Failure::RedefinedExistingFunction(_, _))
+        );
+    }
+
+    #[test]
+    fn arg_count() {
+        let check = |src: &str| super::input_with_warnings(quick_parse(src).into_iter(), checking::Options::default());
+
+        let (instructions, warnings) = check("main()\n    display 1").unwrap();
+        assert!(!instructions.contains(&checking::Instruction::ArgCount));
+        assert!(warnings.is_empty());
+
+        let (instructions, _) = check("main()\n    display arg_count() + 1").unwrap();
+        assert!(instructions.contains(&checking::Instruction::CallExpectingValue(super::arg_count_label())));
+        assert!(instructions.contains(&checking::Instruction::ArgCount));
+
+        assert_pattern!(check("main()\n    display arg_count(1)"), Err(_));
+    }
+
+    #[test]
+    fn conflicting_overloads() {
+        let check = |src: &str| super::input(quick_parse(src).into_iter());
+
+        assert_eq!(
+            check("f(Num x) -> Num\n    return x\n\nf(Num x) -> Bool\n    return true\n\nmain()\n    display f(1)"),
+            Err(checking::Failure::ConflictingOverload {
+                pos: Position { position: 28, line_number: 4, line_position: 1 }, identifier: "f".to_string(),
+                params: vec![checking::Type::Num], existing: Some(checking::Type::Num), encountered: Some(checking::Type::Bool)
+            })
+        );
+
+        assert_pattern!(
+            check("f(Num x) -> Num\n    return x\n\nf(Num x) -> Num\n    return 1\n\nmain()\n    display f(1)"),
+            Err(checking::Failure::RedefinedExistingFunction(_, _))
+        );
+
+        // Overloads differing by parameter types may still differ by return type:
+        assert!(check("f(Num x) -> Num\n    return x\n\nf(Char x) -> Bool\n    return true\n\nmain()\n    display f(1)\n    display f('a')").is_ok());
+    }
+
+    #[test]
+    fn assertions() {
+        let check = |src: &str| super::input(quick_parse(src).into_iter());
+
+        let instructions = check("main()\n    Num x = 1\n    assert(x > 0)\n    assert_eq(x, 1)").unwrap();
+        assert_eq!(instructions.iter().filter(|x| matches!(x, checking::Instruction::JumpIfTrue(_))).count(), 2);
+        assert!(instructions.contains(&checking::Instruction::AssertionFailed(3)));
+        assert!(instructions.contains(&checking::Instruction::AssertionFailed(4)));
+
+        assert_pattern!(
+            check("main()\n    assert(1)"),
+            Err(checking::Failure::UnexpectedType { expected: checking::Type::Bool, encountered: checking::Type::Num, .. })
+        );
+        assert_pattern!(
+            check("main()\n    assert_eq(1, 'a')"),
+            Err(checking::Failure::UnexpectedType { expected: checking::Type::Num, encountered: checking::Type::Char, .. })
+        );
+        assert!(check("main()\n    assert_eq('a', 'b')").is_ok());
+    }
+
+    #[test]
+    fn xor() {
+        let chkr = new_empty_checker();
+
+        let boolean = |value| Box::new(parsing::Expression::BooleanLiteral { pos: Position::new(), value });
+        assert_eq!(chkr.infer_type(&parsing::Expression::Xor(boolean(true), boolean(false))), Ok(checking::Type::Bool));
+
+        assert_pattern!(
+            chkr.infer_type(&parsing::Expression::Xor(boolean(true), Box::new(parsing::Expression::NumberLiteral { pos: Position::new(), value: 1.0 }))),
+            Err(checking::Failure::UnexpectedType { expected: checking::Type::Bool, encountered: checking::Type::Num, .. })
+        );
+
+        assert_eq!(super::const_eval(&parsing::Expression::Xor(boolean(true), boolean(true))), Some(checking::Value::Bool(false)));
+    }
+
+    #[test]
+    fn casts() {
+        let check = |src: &str| super::input(quick_parse(src).into_iter());
+
+        // Bool values are never implicitly treated as numbers:
+        assert_eq!(
+            check("main()\n    display true + 1"),
+            Err(checking::Failure::UnexpectedType {
+                pos: Position { position: 20, line_number: 2, line_position: 13 },
+                expected: checking::Type::Num, encountered: checking::Type::Bool
+            })
+        );
+
+        let instructions = check("main()\n    display cast(true, Num) + 1").unwrap();
+        assert!(instructions.windows(2).any(|pair| pair == [
+            checking::Instruction::Push(checking::Value::Bool(true)),
+            checking::Instruction::Convert { from: checking::Type::Bool, to: checking::Type::Num }
+        ]));
+
+        let instructions = check("main()\n    display cast(cast('a', Num) + 1, Char)").unwrap();
+        assert!(instructions.contains(&checking::Instruction::Convert { from: checking::Type::Char, to: checking::Type::Num }));
+        assert!(instructions.contains(&checking::Instruction::Convert { from: checking::Type::Num, to: checking::Type::Char }));
+
+        // Casting to the same type has no effect:
+        let instructions = check("main()\n    display cast(1, Num)").unwrap();
+        assert!(!instructions.iter().any(|x| matches!(x, checking::Instruction::Convert { .. })));
+
+        assert_pattern!(
+            check("main()\n    display cast(1, Bool)"),
+            Err(checking::Failure::InvalidCast { from: checking::Type::Num, to: checking::Type::Bool, .. })
+        );
+        assert_pattern!(check("cast()\n    display 1\n\nmain()\n    display 2"), Err(checking::Failure::ReservedIdentifier(_, _)));
+    }
+
+    #[test]
+    fn boolean_identities() {
+        let options = checking::Options { fold_boolean_identities: true, ..Default::default() };
+        let check = |body: &str| {
+            let src = format!("is_positive(Num x) -> Bool\n    display x\n    return x > 0\n\nmain()\n    Bool b = true\n    Num n = 1\n    {}", body);
+            let instructions = super::input_with_options(quick_parse(&src).into_iter(), options.clone()).unwrap();
+            let main_start = instructions.iter().position(|x| matches!(x, checking::Instruction::Function { label, .. } if label == "main")).unwrap();
+            instructions[main_start..].iter()
+                .filter(|x| matches!(x, checking::Instruction::Not | checking::Instruction::Equals | checking::Instruction::CallExpectingValue(_)))
+                .count()
+        };
+
+        assert_eq!(check("display !!b"), 0);
+        assert_eq!(check("display !!!b"), 1);
+        assert_eq!(check("display b == true"), 0);
+        assert_eq!(check("display true == b"), 0);
+
+        // The function call is kept when the literal beside it is dropped:
+        assert_eq!(check("display is_positive(n) == true"), 1);
+        assert_eq!(check("display !!is_positive(n)"), 1);
+
+        // Neither operand can be dropped:
+        assert_eq!(check("display b == is_positive(n)"), 2);
+        assert_eq!(check("display b == false"), 1);
+
+        // Only a Bool can be compared with true:
+        assert_pattern!(
+            super::input_with_options(quick_parse("main()\n    display 1 == true").into_iter(), options.clone()),
+            Err(checking::Failure::UnexpectedType { .. })
+        );
+
+        // No folding when disabled:
+        let unfolded = super::input(quick_parse("main()\n    Bool b = true\n    display !!b == true").into_iter()).unwrap();
+        assert_eq!(unfolded.iter().filter(|x| matches!(x, checking::Instruction::Not)).count(), 2);
+        assert!(unfolded.contains(&checking::Instruction::Equals));
+    }
+
+    #[test]
+    fn builder() {
+        let src = "main()\n    display 'a' + 1";
+
+        let builder = super::CheckerBuilder::new().max_expression_depth(50).entry_point("main");
+        assert_eq!(builder.options(), &checking::Options { max_expression_depth: 50, ..Default::default() });
+
+        assert_pattern!(
+            builder.clone().strict_types(true).build(quick_parse(src).into_iter()).check(),
+            Err(checking::Failure::UnexpectedType { encountered: checking::Type::Char, .. })
+        );
+
+        let (instructions, warnings) = builder.strict_types(false).build(quick_parse(src).into_iter()).check().unwrap();
+        assert!(instructions.contains(&checking::Instruction::Convert { from: checking::Type::Char, to: checking::Type::Num }));
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn chained_comparisons() {
+        let mut chkr = new_empty_checker();
+
+        for ident in &["a", "b", "c"] {
+            chkr.add_variable_def_to_inner_scope(ident.to_string(), checking::Type::Num, Position::new(), checking::SymbolKind::Variable);
+        }
+
+        let var = |ident: &str, line_position| Box::new(parsing::Expression::Variable {
+            pos: Position { position: line_position, line_number: 1, line_position },
+            identifier: ident.to_string()
+        });
+
+        // a < b < c
+        assert_eq!(
+            chkr.eval_expr(parsing::Expression::LessThan(
+                Box::new(parsing::Expression::LessThan(var("a", 1), var("b", 5))),
+                var("c", 9)
+            )),
+            Err(checking::Failure::ChainedComparison(Position { position: 1, line_number: 1, line_position: 1 }))
+        );
+
+        // a > (b < c)
+        assert_pattern!(
+            chkr.eval_expr(parsing::Expression::GreaterThan(
+                var("a", 1),
+                Box::new(parsing::Expression::LessThan(var("b", 6), var("c", 10)))
+            )),
+            Err(checking::Failure::ChainedComparison(_))
+        );
+
+        // a < b == b < c
+        assert_pattern!(
+            chkr.eval_expr(parsing::Expression::Equal(
+                Box::new(parsing::Expression::LessThan(var("a", 1), var("b", 5))),
+                Box::new(parsing::Expression::LessThan(var("b", 10), var("c", 14)))
+            )),
+            Ok((_, checking::Type::Bool, _))
+        );
+    }
+
+    #[test]
+    fn infer_types() {
+        let mut chkr = new_empty_checker();
+
+        assert_eq!(
+            chkr.infer_type(&parsing::Expression::Equal(
+                Box::new(parsing::Expression::CharLiteral { pos: Position::new(), value: 'a' }),
+                Box::new(parsing::Expression::CharLiteral { pos: Position::new(), value: 'b' })
+            )),
+            Ok(checking::Type::Bool)
+        );
+
+        chkr.add_variable_def_to_inner_scope("c".to_string(), checking::Type::Char, Position::new(), checking::SymbolKind::Variable);
+
+        let var_ref = parsing::Expression::Variable { pos: Position::new(), identifier: "c".to_string() };
+        assert_eq!(chkr.infer_type(&var_ref), Ok(checking::Type::Char));
+        assert!(chkr.expression_types.borrow().is_empty());
+
+        // Inferring the type of an assignment does not give the variable a
+        // value:
+        let c_id = chkr.variable_lookup("c", &Position::new()).unwrap().id;
+        chkr.uninitialised.borrow_mut().push(c_id);
+        let assignment = parsing::Expression::Assignment {
+            pos: Position::new(), identifier: "c".to_string(),
+            value: Box::new(parsing::Expression::CharLiteral { pos: Position::new(), value: 'd' })
+        };
+        assert_eq!(chkr.infer_type(&assignment), Ok(checking::Type::Char));
+        assert_eq!(*chkr.uninitialised.borrow(), vec![c_id]);
+
+        chkr.end_scope();
+        assert_pattern!(chkr.infer_type(&var_ref), Err(checking::Failure::VariableNotInScope(_, _)));
+    }
+
     #[test]
     fn eval_inner_stmts() {
         let mut chkr = new_empty_checker();
 
         assert_eq!(
-            chkr.eval_inner_stmt(parsing::Statement::Return(None)),
+            chkr.eval_inner_stmt(parsing::Statement::Return { pos: Position::new(), value: None }),
             Ok((vec![checking::Instruction::ReturnVoid], 0, None))
         );
 
         assert_eq!(
-            chkr.eval_inner_stmt(parsing::Statement::Return(Some(
+            chkr.eval_inner_stmt(parsing::Statement::Return { pos: Position::new(), value: Some(
                 parsing::Expression::Add(
                     Box::new(parsing::Expression::NumberLiteral { pos: Position::new(), value: 1.2 }),
                     Box::new(parsing::Expression::NumberLiteral { pos: Position::new(), value: 2.8 })
                 )
-            ))),
+            ) }),
             Ok((
                 vec![
                     checking::Instruction::Push(checking::Value::Num(1.2)),
@@ -804,7 +2230,7 @@ mod tests {
             chkr.eval_inner_stmt(parsing::Statement::If {
                 condition: parsing::Expression::BooleanLiteral { pos: Position::new(), value: true },
                 block: vec![
-                    parsing::Statement::Return(Some(parsing::Expression::CharLiteral { pos: Position::new(), value: 'x' }))
+                    parsing::Statement::Return { pos: Position::new(), value: Some(parsing::Expression::CharLiteral { pos: Position::new(), value: 'x' }) }
                 ]
             }),
             Ok((
@@ -821,6 +2247,7 @@ mod tests {
 
         assert_eq!(
             chkr.eval_inner_stmt(parsing::Statement::VariableDeclaration {
+                pos: Position::new(),
                 identifier: "pi".to_string(),
                 var_type: "Num".to_string(),
                 value: Some(parsing::Expression::NumberLiteral { pos: Position::new(), value: 3.14 })
@@ -838,6 +2265,7 @@ mod tests {
 
         assert_eq!(
             chkr.eval_inner_stmt(parsing::Statement::VariableDeclaration {
+                pos: Position::new(),
                 identifier: "xyz".to_string(),
                 var_type: "Oops".to_string(),
                 value: None
@@ -847,6 +2275,7 @@ mod tests {
 
         assert_pattern!(
             chkr.eval_inner_stmt(parsing::Statement::VariableAssignment {
+                pos: Position::new(),
                 identifier: "pi".to_string(),
                 assign_to: parsing::Expression::NumberLiteral { pos: Position::new(), value: 3.1 }
             }),
@@ -855,6 +2284,7 @@ mod tests {
 
         assert_pattern!(
             chkr.eval_inner_stmt(parsing::Statement::VariableAssignment {
+                pos: Position::new(),
                 identifier: "pi".to_string(),
                 assign_to: parsing::Expression::BooleanLiteral { pos: Position::new(), value: true }
             }),
@@ -876,6 +2306,409 @@ mod tests {
         );
     }
 
+    #[test]
+    fn print_mixed_types() {
+        let mut chkr = new_empty_checker();
+
+        let mixed_args = vec![
+            parsing::Expression::CharLiteral { pos: Position::new(), value: 'a' },
+            parsing::Expression::NumberLiteral { pos: Position::new(), value: 1.0 },
+            parsing::Expression::BooleanLiteral { pos: Position::new(), value: true }
+        ];
+
+        assert_eq!(
+            chkr.eval_inner_stmt(parsing::Statement::Print { pos: Position::new(), args: mixed_args.clone(), newline: false }),
+            Ok((
+                vec![
+                    checking::Instruction::Push(checking::Value::Char('a')),
+                    checking::Instruction::Print(checking::Type::Char),
+                    checking::Instruction::Push(checking::Value::Char(' ')),
+                    checking::Instruction::Print(checking::Type::Char),
+                    checking::Instruction::Push(checking::Value::Num(1.0)),
+                    checking::Instruction::Print(checking::Type::Num),
+                    checking::Instruction::Push(checking::Value::Char(' ')),
+                    checking::Instruction::Print(checking::Type::Char),
+                    checking::Instruction::Push(checking::Value::Bool(true)),
+                    checking::Instruction::Print(checking::Type::Bool)
+                ],
+                0, None
+            ))
+        );
+
+        assert_pattern!(
+            chkr.eval_inner_stmt(parsing::Statement::Print {
+                pos: Position::new(),
+                args: vec![parsing::Expression::Variable { pos: Position::new(), identifier: "undefined".to_string() }],
+                newline: false
+            }),
+            Err(checking::Failure::VariableNotInScope(_, _))
+        );
+
+        // Output of println should differ only by a trailing newline:
+        let (mut print_instructions, _, _) = chkr.eval_inner_stmt(parsing::Statement::Print { pos: Position::new(), args: mixed_args.clone(), newline: false }).unwrap();
+        let (println_instructions, _, _) = chkr.eval_inner_stmt(parsing::Statement::Print { pos: Position::new(), args: mixed_args, newline: true }).unwrap();
+
+        print_instructions.extend(vec![
+            checking::Instruction::Push(checking::Value::Char('\n')),
+            checking::Instruction::Print(checking::Type::Char)
+        ]);
+        assert_eq!(println_instructions, print_instructions);
+    }
+
+    #[test]
+    fn mark_lines() -> checking::Result<()> {
+        let stmts = quick_parse("
+main()
+    Num x = 5
+
+    display x
+    print()
+    return");
+
+        let instructions = super::input_with_options(stmts.into_iter(), checking::Options { mark_lines: true, ..Default::default() })?;
+
+        let marked_lines: Vec<u64> = instructions.iter().filter_map(|instruction| match instruction {
+            checking::Instruction::MarkLine(line_number) => Some(*line_number),
+            _ => None
+        }).collect();
+        // Including statements without any values:
+        assert_eq!(marked_lines, vec![2, 4, 5, 6]);
+
+        let unmarked = super::input(quick_parse("main()\n    display 1").into_iter())?;
+        assert!(!unmarked.iter().any(|instruction| matches!(instruction, checking::Instruction::MarkLine(_))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn name_clashes() {
+        let check = |src: &str| super::input(quick_parse(src).into_iter());
+
+        assert_pattern!(
+            check("
+total() -> Num
+    return 1
+
+main()
+    Num total = total()"),
+            Err(checking::Failure::NameClash(_, _))
+        );
+
+        assert_pattern!(
+            check("
+scale(Num scale) -> Num
+    return scale * 2
+
+main()
+    display scale(1)"),
+            Err(checking::Failure::NameClash(_, _))
+        );
+
+        // A variable may share an identifier with a function defined after it:
+        assert!(check("
+main()
+    Num later = 1
+    display later
+
+later() -> Num
+    return 2").is_ok());
+    }
+
+    #[test]
+    fn failure_recovery() {
+        let src = "
+first() -> Num
+    Num x = true
+    return x
+
+second() -> Num
+    if 1
+        return 1
+    return 2
+
+third() -> Num
+    return 3
+
+main()
+    display nothing(1)
+    display third()";
+
+        let mut failures = super::input_with_all_failures(quick_parse(src).into_iter(), checking::Options::default()).unwrap_err();
+        assert_eq!(failures.len(), 3);
+        assert_pattern!(failures[0], checking::Failure::UnexpectedType { encountered: checking::Type::Bool, .. });
+        assert_pattern!(failures[1], checking::Failure::UnexpectedType { encountered: checking::Type::Num, .. });
+        assert_pattern!(failures[2], checking::Failure::FunctionUndefined(_, _, _));
+
+        // Only the first failure is returned otherwise:
+        assert_eq!(super::input(quick_parse(src).into_iter()), Err(failures.remove(0)));
+
+        // Entry point not being defined is reported alongside other failures:
+        let failures = super::input_with_all_failures(quick_parse("start()\n    Num x = 'a'").into_iter(), checking::Options::default()).unwrap_err();
+        assert_pattern!(failures.as_slice(), [checking::Failure::UnexpectedType { .. }, checking::Failure::MainUndefined(_)]);
+
+        assert!(super::input_with_all_failures(quick_parse("main()\n    display 1").into_iter(), checking::Options::default()).is_ok());
+    }
+
+    #[test]
+    fn empty_program() {
+        assert_eq!(super::input(iter::empty()), Err(checking::Failure::EmptyProgram(Some("main".to_string()))));
+
+        for src in ["", "\n\n", "\n   \n"] {
+            let tokens = lexer::input(Stream::from_str(src)).map(Result::unwrap);
+            assert_eq!(
+                super::input(parsing::parser::input(tokens).map(Result::unwrap)),
+                Err(checking::Failure::EmptyProgram(Some("main".to_string())))
+            );
+        }
+
+        // No entry point is required of a library:
+        let library = super::input_with_options(iter::empty(), checking::Options { library: true, ..Default::default() });
+        assert_eq!(library, Err(checking::Failure::EmptyProgram(None)));
+        assert!(!library.unwrap_err().to_string().contains("main"));
+    }
+
+    #[test]
+    fn entry_point() {
+        let options = checking::Options { entry_point: "start".to_string(), ..Default::default() };
+
+        assert_eq!(
+            super::input_with_options(quick_parse("main()\n    display 1").into_iter(), options.clone()),
+            Err(checking::Failure::MainUndefined("start".to_string()))
+        );
+
+        let instructions = super::input_with_options(quick_parse("main()\n    display 1\nstart()\n    display 2").into_iter(), options).unwrap();
+        assert!(instructions.contains(&checking::Instruction::Function { label: "start".to_string(), local_variable_count: 0 }));
+        assert!(!instructions.iter().any(|x| matches!(x, checking::Instruction::Function { label, .. } if label == "main")));
+    }
+
+    #[test]
+    fn library() -> checking::Result<()> {
+        let src = "double(Num x) -> Num\n    return x * 2\nmain(Num x) -> Num\n    return double(x)";
+        let options = checking::Options { library: true, ..Default::default() };
+        let (instructions, warnings) = super::input_with_warnings(quick_parse(src).into_iter(), options)?;
+
+        // Neither function is unused and a function named after the entry point
+        // need not be one:
+        assert!(warnings.is_empty());
+        assert_eq!(instructions.iter().filter(|x| matches!(x, checking::Instruction::Function { .. })).count(), 2);
+        assert!(!instructions.iter().any(|x| matches!(x, checking::Instruction::Function { label, .. } if label == "main")));
+
+        assert_pattern!(
+            super::input_with_options(quick_parse(src).into_iter(), Default::default()),
+            Err(checking::Failure::EntryPointParameters(_, _))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn infinite_recursion() -> checking::Result<()> {
+        let (_, warnings) = super::input_with_warnings(quick_parse("
+forever(Num x) -> Num
+    Num y = x + 1
+    return forever(y)
+
+countdown(Num x) -> Num
+    if x > 0
+        return countdown(x - 1)
+    return x
+
+main()
+    display countdown(3)").into_iter(), checking::Options::default())?;
+
+        assert_eq!(warnings, vec![
+            checking::Warning::PossibleInfiniteRecursion(Position { position: 7, line_number: 1, line_position: 7 }, "forever".to_string()),
+            checking::Warning::UnusedFunction(Position { position: 7, line_number: 1, line_position: 7 }, "forever".to_string())
+        ]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn unchanging_loop_conditions() -> checking::Result<()> {
+        let warnings_of = |body: &str| {
+            let src = format!("is_done(Bool x) -> Bool\n    return x\n\nmain()\n    Bool x = true\n    Num n = 1\n    println(is_done(x))\n{}", body);
+            super::input_with_warnings(quick_parse(&src).into_iter(), checking::Options::default()).map(|(_, warnings)| warnings)
+        };
+
+        assert_eq!(warnings_of("    while x\n        print('a')")?, vec![
+            checking::Warning::LoopConditionNeverChanges(Position { position: 97, line_number: 8, line_position: 8 })
+        ]);
+        assert_eq!(warnings_of("    while n > 0 == x\n        if n > 3\n            print('a')")?.len(), 1);
+
+        // Condition may become false:
+        assert!(warnings_of("    while x\n        x = false")?.is_empty());
+        assert!(warnings_of("    while n < 10\n        if n > 3\n            n = n + 2")?.is_empty());
+        assert!(warnings_of("    while is_done(x)\n        print('a')")?.is_empty());
+        assert!(warnings_of("    while x\n        return")?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn parameter_shadowed() -> checking::Result<()> {
+        let warnings_of = |body: &str| {
+            let src = format!("f(Num x) -> Num\n{}\n    return x\n\nmain()\n    display f(1)", body);
+            super::input_with_warnings(quick_parse(&src).into_iter(), checking::Options::default()).map(|(_, warnings)| warnings)
+        };
+
+        assert_eq!(warnings_of("    if x > 1\n        Char x = 'a'\n        display x")?, vec![
+            checking::Warning::ParameterShadowed(Position { position: 34, line_number: 3, line_position: 8 }, "x".to_string())
+        ]);
+        assert_eq!(warnings_of("    while x > 1\n        Num x = 1\n        return x")?.len(), 1);
+
+        // Redeclaring in the same scope is not shadowing (and other variables
+        // may be shadowed freely):
+        assert!(warnings_of("    Num x = 2\n    display x")?.is_empty());
+        assert!(warnings_of("    Num y = 1\n    if x > y\n        Num y = 2\n        display y")?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn use_before_init() -> checking::Result<()> {
+        let warnings_of = |body: &str| {
+            let src = format!("main()\n    Num x\n    Bool b = true\n{}", body);
+            super::input_with_warnings(quick_parse(&src).into_iter(), checking::Options::default()).map(|(_, warnings)| warnings)
+        };
+
+        assert_eq!(warnings_of("    display x")?, vec![
+            checking::Warning::UseBeforeInit(Position { position: 39, line_number: 4, line_position: 10 }, "x".to_string())
+        ]);
+
+        // Only assigned should the if statement's condition be true:
+        assert_eq!(warnings_of("    if b\n        x = 1\n        display x\n    display x")?, vec![
+            checking::Warning::UseBeforeInit(Position { position: 65, line_number: 7, line_position: 10 }, "x".to_string())
+        ]);
+
+        // Unassigned when first evaluating the loop condition:
+        assert_eq!(warnings_of("    while b\n        b = x > 1\n        x = 1")?.len(), 1);
+        assert_eq!(warnings_of("    while x < 3\n        x = 1")?.len(), 1);
+
+        // Assigned on every path:
+        assert!(warnings_of("    if b\n        display 1\n    x = 2\n    display x")?.is_empty());
+        assert!(warnings_of("    x = 1\n    while b\n        display x\n        b = false")?.is_empty());
+
+        // Parameter given the ID of an unassigned variable of another function:
+        let src = "f() -> Num\n    Num x\n    return 1\n\ng(Num n) -> Num\n    return n\n\nmain()\n    display g(f())";
+        assert!(super::input_with_warnings(quick_parse(src).into_iter(), checking::Options::default())?.1.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn warnings_as_errors() {
+        let src = "
+uncalled(Num x) -> Num
+    return x
+
+main()
+    display 1";
+
+        let check = |warnings_as_errors| super::CheckerBuilder::new()
+            .warnings_as_errors(warnings_as_errors)
+            .build(quick_parse(src).into_iter())
+            .check_all();
+
+        let warning = checking::Warning::UnusedFunction(Position { position: 8, line_number: 1, line_position: 8 }, "uncalled".to_string());
+
+        assert_eq!(check(false).unwrap().1, vec![warning.clone()]);
+        assert_eq!(check(true), Err(vec![checking::Failure::WarningAsError(warning.clone())]));
+
+        assert_eq!(
+            super::CheckerBuilder::new().warnings_as_errors(true).build(quick_parse(src).into_iter()).check(),
+            Err(checking::Failure::WarningAsError(warning.clone()))
+        );
+
+        let options = checking::Options { warnings_as_errors: true, ..Default::default() };
+        assert_eq!(super::input_with_options(quick_parse(src).into_iter(), options), Err(checking::Failure::WarningAsError(warning)));
+    }
+
+    #[test]
+    fn unused_functions() -> checking::Result<()> {
+        let (_, warnings) = super::input_with_warnings(quick_parse("
+called(Num x) -> Num
+    return x * 2
+
+uncalled(Num x) -> Num
+    return called(x)
+
+main()
+    display called(3)").into_iter(), checking::Options::default())?;
+
+        assert_eq!(warnings, vec![
+            checking::Warning::UnusedFunction(Position { position: 44, line_number: 4, line_position: 8 }, "uncalled".to_string())
+        ]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn constant_conditions() -> checking::Result<()> {
+        let src = "
+main()
+    if false
+        display 1
+    if 1 > 2 == false
+        display 2
+    Num x = 3
+    if x > 2
+        display 3";
+
+        let (instructions, warnings) = super::input_with_warnings(quick_parse(src).into_iter(), checking::Options::default())?;
+        assert_eq!(warnings, vec![
+            checking::Warning::ConstantCondition(Position { position: 16, line_number: 2, line_position: 9 }, false),
+            checking::Warning::ConstantCondition(Position { position: 34, line_number: 4, line_position: 5 }, true)
+        ]);
+        assert!(instructions.contains(&checking::Instruction::Push(checking::Value::Num(1.0))));
+
+        // The dead branch is removed as is the always-true condition:
+        let optimised = super::input_with_options(quick_parse(src).into_iter(), checking::Options { remove_dead_branches: true, ..Default::default() })?;
+        assert!(!optimised.contains(&checking::Instruction::Push(checking::Value::Num(1.0))));
+        assert!(!optimised.contains(&checking::Instruction::Push(checking::Value::Bool(false))));
+        assert!(optimised.contains(&checking::Instruction::Push(checking::Value::Num(2.0))));
+        assert_eq!(optimised.iter().filter(|x| matches!(x, checking::Instruction::JumpIfFalse(_))).count(), 1);
+
+        // Agrees with run time as to whether 0 and -0 are equal:
+        let (_, warnings) = super::input_with_warnings(quick_parse("main()\n    if 0 == (~1) * 0\n        display 1").into_iter(), checking::Options::default())?;
+        assert_pattern!(warnings.as_slice(), [checking::Warning::ConstantCondition(_, false)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn non_bool_conditions() {
+        let mut chkr = new_empty_checker();
+
+        let condition_pos = Position { position: 12, line_number: 3, line_position: 4 };
+
+        assert_eq!(
+            chkr.eval_inner_stmt(parsing::Statement::If {
+                condition: parsing::Expression::NumberLiteral { pos: condition_pos.clone(), value: 5.0 },
+                block: vec![]
+            }),
+            Err(checking::Failure::UnexpectedType {
+                pos: condition_pos.clone(),
+                expected: checking::Type::Bool,
+                encountered: checking::Type::Num
+            })
+        );
+
+        assert_eq!(
+            chkr.eval_inner_stmt(parsing::Statement::While {
+                condition: parsing::Expression::Add(
+                    Box::new(parsing::Expression::NumberLiteral { pos: condition_pos.clone(), value: 1.0 }),
+                    Box::new(parsing::Expression::NumberLiteral { pos: Position::new(), value: 2.0 })
+                ),
+                block: vec![]
+            }),
+            Err(checking::Failure::UnexpectedType {
+                pos: condition_pos,
+                expected: checking::Type::Bool,
+                encountered: checking::Type::Num
+            })
+        );
+    }
+
     #[test]
     fn eval_top_level_stmts() -> checking::Result<()> {
         let mut chkr = new_empty_checker();
@@ -888,6 +2721,7 @@ mod tests {
                 return_type: None,
                 body: vec![
                     parsing::Statement::VariableDeclaration {
+                        pos: Position::new(),
                         identifier: "var".to_string(), var_type: "Num".to_string(),
                         value: None
                     }
@@ -895,7 +2729,7 @@ mod tests {
                 pos: Position::new()
             }),
             Ok(vec![
-                checking::Instruction::Function { label: "func0".to_string(), local_variable_count: 1 },
+                checking::Instruction::Function { label: "till.func0".to_string(), local_variable_count: 1 },
                 checking::Instruction::Local(1),
                 checking::Instruction::ReturnVoid
             ])
@@ -908,15 +2742,16 @@ mod tests {
                 parameters: vec![],
                 return_type: Some("Num".to_string()),
                 body: vec![
-                    parsing::Statement::Return(Some(parsing::Expression::NumberLiteral {
+                    parsing::Statement::Return { pos: Position::new(), value: Some(parsing::Expression::NumberLiteral {
                         pos: Position::new(), value: 1.5
-                    }))
+                    }) }
                 ],
                 pos: Position::new()
             }),
-            Err(checking::Failure::RedefinedExistingFunction(
-                "func".to_string(), vec![]
-            ))
+            Err(checking::Failure::ConflictingOverload {
+                pos: Position::new(), identifier: "func".to_string(), params: vec![],
+                existing: None, encountered: Some(checking::Type::Num)
+            })
         );
 
         assert_pattern!(
@@ -944,9 +2779,9 @@ mod tests {
                 parameters: vec![],
                 return_type: None,
                 body: vec![
-                    parsing::Statement::Return(Some(parsing::Expression::BooleanLiteral {
+                    parsing::Statement::Return { pos: Position::new(), value: Some(parsing::Expression::BooleanLiteral {
                         pos: Position::new(), value: true
-                    }))
+                    }) }
                 ],
                 pos: Position::new()
             }),
@@ -968,14 +2803,14 @@ mod tests {
                 ],
                 return_type: Some("Num".to_string()),
                 body: vec![
-                    parsing::Statement::Return(Some(parsing::Expression::Variable {
+                    parsing::Statement::Return { pos: Position::new(), value: Some(parsing::Expression::Variable {
                         pos: Position::new(), identifier: "x".to_string()
-                    }))
+                    }) }
                 ],
                 pos: Position::new()
             }),
             Ok(vec![
-                checking::Instruction::Function { label: "func0".to_string(), local_variable_count: 0 },
+                checking::Instruction::Function { label: "till.func0".to_string(), local_variable_count: 0 },
                 checking::Instruction::Parameter(1),
                 checking::Instruction::Push(checking::Value::Variable(1)),
                 checking::Instruction::ReturnValue
@@ -994,6 +2829,257 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn reserved_identifiers() {
+        let func_def = |identifier: &str, parameters| parsing::Statement::FunctionDefinition {
+            identifier: identifier.to_string(),
+            parameters, return_type: None, body: vec![],
+            pos: Position::new()
+        };
+
+        let mut chkr = new_empty_checker();
+
+        for ident in &["printf", "cast"] {
+            assert_eq!(
+                chkr.eval_top_level_stmt(func_def(ident, vec![])),
+                Err(checking::Failure::ReservedIdentifier(Position::new(), ident.to_string()))
+            );
+        }
+
+        assert_eq!(
+            chkr.eval_top_level_stmt(func_def("main", vec![
+                parsing::Parameter { pos: Position::new(), identifier: "x".to_string(), param_type: "Num".to_string() }
+            ])),
+            Err(checking::Failure::EntryPointParameters(Position::new(), "main".to_string()))
+        );
+
+        // Generated labels have a prefix no identifier can have:
+        for ident in &["func", "labels", "func_1", "printf2", "func0", "label12", "literal3"] {
+            assert!(chkr.eval_top_level_stmt(func_def(ident, vec![])).is_ok());
+        }
+    }
+
+    #[test]
+    fn symbols() -> checking::Result<()> {
+        let analysis = super::analyse(quick_parse("
+two() -> Num
+    return 2
+
+main()
+    Num x = two()").into_iter())?;
+
+        let summary: Vec<(&str, checking::SymbolKind, Option<checking::Type>, u64, usize)> = analysis.symbols.iter()
+            .map(|sym| (sym.name.as_str(), sym.kind, sym.symbol_type.clone(), sym.pos.line_number, sym.scope_depth))
+            .collect();
+
+        assert_eq!(summary, vec![
+            ("two", checking::SymbolKind::Function, Some(checking::Type::Num), 1, 0),
+            ("main", checking::SymbolKind::Function, None, 4, 0),
+            ("x", checking::SymbolKind::Variable, Some(checking::Type::Num), 5, 1)
+        ]);
+
+        let analysis = super::analyse(quick_parse("
+main()
+    Num x = 1
+    if x > 0
+        Bool y = true
+
+func(Char x)
+    return").into_iter())?;
+
+        let summary: Vec<(&str, checking::SymbolKind, usize)> = analysis.symbols.iter()
+            .map(|sym| (sym.name.as_str(), sym.kind, sym.scope_depth))
+            .collect();
+
+        assert_eq!(summary, vec![
+            ("main", checking::SymbolKind::Function, 0),
+            ("x", checking::SymbolKind::Variable, 1),
+            ("y", checking::SymbolKind::Variable, 2),
+            ("func", checking::SymbolKind::Function, 0),
+            ("x", checking::SymbolKind::Parameter, 1)
+        ]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn definitions() -> checking::Result<()> {
+        let mut chkr = new_empty_checker();
+
+        let declared_pos = Position { position: 10, line_number: 2, line_position: 9 };
+
+        chkr.eval_inner_stmt(parsing::Statement::VariableDeclaration {
+            pos: declared_pos.clone(),
+            identifier: "x".to_string(),
+            var_type: "Num".to_string(),
+            value: None
+        })?;
+
+        assert_eq!(chkr.variable_lookup("x", &Position::new())?.pos, declared_pos);
+
+        let analysis = super::analyse(quick_parse("
+double(Num x) -> Num
+    return x * 2
+
+main()
+    Num y = double(4)
+    display y").into_iter())?;
+
+        // Map each reference (identified by its line) to the line of the
+        // definition it refers to:
+        let lines: Vec<(u64, u64)> = analysis.references.iter()
+            .map(|r| (r.pos.line_number, analysis.definition_at(&r.pos).unwrap().line_number))
+            .collect();
+        assert_eq!(lines, vec![(2, 1), (5, 1), (6, 5)]);
+
+        assert!(analysis.definition_at(&Position::new()).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn references() -> checking::Result<()> {
+        let analysis = super::analyse(quick_parse("
+main()
+    Num x = 1
+    Num y = x + x
+    x = y
+    display y").into_iter())?;
+
+        let lines_referencing = |name: &str| {
+            let def = analysis.symbols.iter().find(|sym| sym.name == name).unwrap();
+            analysis.references_of(def).iter().map(|pos| pos.line_number).collect::<Vec<u64>>()
+        };
+
+        assert_eq!(lines_referencing("x"), vec![3, 3, 4]);
+        assert_eq!(lines_referencing("y"), vec![4, 5]);
+        assert!(lines_referencing("main").is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn types() -> checking::Result<()> {
+        let analysis = super::analyse(quick_parse("
+double(Num x) -> Num
+    return x * 2
+
+main()
+    Bool b = double(2) > 3
+    Fn(Num) -> Num f = double
+    display f(1)").into_iter())?;
+
+        let function_type = checking::Type::Function(Box::new(checking::FunctionType {
+            params: vec![checking::Type::Num], ret: Some(checking::Type::Num)
+        }));
+        assert_eq!(analysis.types, vec![checking::Type::Num, checking::Type::Bool, function_type]);
+
+        // Types of expressions are included (no Char definitions are made):
+        let analysis = super::analyse(quick_parse("main()\n    display cast(65, Char)").into_iter())?;
+        assert_eq!(analysis.types, vec![checking::Type::Num, checking::Type::Char]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn expression_types() -> checking::Result<()> {
+        let stmts = quick_parse("main()\n    Num x = 2\n    display 3 * (x + 1) > 4");
+
+        // Find the comparison and the addition within the body of main:
+        let (comparison, addition) = match &stmts[0] {
+            parsing::Statement::FunctionDefinition { body, .. } => match &body[1] {
+                parsing::Statement::Display(comparison @ parsing::Expression::GreaterThan(multiplication, _)) => match multiplication.as_ref() {
+                    parsing::Expression::Multiply(_, addition) => (comparison.pos().clone(), addition.pos().clone()),
+                    other => panic!("{:?}", other)
+                },
+                other => panic!("{:?}", other)
+            },
+            other => panic!("{:?}", other)
+        };
+
+        let analysis = super::analyse(stmts.into_iter())?;
+
+        assert_ne!(comparison, addition);
+        assert_eq!(analysis.type_at(&addition), Some(&checking::Type::Num));
+        assert_eq!(analysis.type_at(&comparison), Some(&checking::Type::Bool));
+        assert_eq!(analysis.type_at(&Position::new()), None);
+
+        // Every sub-expression is included, inner-most first:
+        let at_comparison: Vec<&checking::Type> = analysis.expression_types.iter()
+            .filter(|(pos, _)| *pos == comparison).map(|(_, t)| t).collect();
+        assert_eq!(at_comparison, vec![&checking::Type::Num, &checking::Type::Num, &checking::Type::Bool]);
+        assert_eq!(analysis.expression_types.len(), 8);
+
+        Ok(())
+    }
+
+    #[test]
+    fn symbols_at() -> checking::Result<()> {
+        let src = "
+helper(Num param) -> Num
+    return param
+
+main()
+    Num outer = 1
+    if outer > 0
+        Num inner = 2
+        display inner
+    display outer";
+
+        let analysis = super::analyse(quick_parse(src).into_iter())?;
+
+        // Symbols accessible at the end of the given line:
+        let names_at = |line_number: u64| {
+            let lines: Vec<String> = src.trim().split('\n').map(|line| line.replace("    ", "\t")).collect();
+            let line = &lines[line_number as usize - 1];
+            let line_start: usize = lines.iter().take(line_number as usize - 1).map(|line| line.len() + 1).sum();
+
+            let pos = Position { position: (line_start + line.len()) as u64, line_number, line_position: line.len() as u64 };
+            analysis.symbols_at(&pos).into_iter().map(|sym| sym.name).collect::<Vec<String>>()
+        };
+
+        assert_eq!(names_at(2), vec!["helper", "param"]);
+        assert_eq!(names_at(8), vec!["helper", "main", "outer", "inner"]);
+        assert_eq!(names_at(9), vec!["helper", "main", "outer"]);
+
+        // Definitions after the position are not yet accessible:
+        assert_eq!(
+            analysis.symbols_at(&Position { position: 0, line_number: 1, line_position: 0 }),
+            Vec::new()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn suggestions_at() -> checking::Result<()> {
+        let src = "
+print_twice(Num x) -> Num
+    return x
+
+main()
+    Num price = 1
+    Num pr = 2
+    Num quantity = 3
+    display price";
+
+        let analysis = super::analyse(quick_parse(src).into_iter())?;
+        let end = Position { position: src.trim().replace("    ", "\t").len() as u64, line_number: 8, line_position: 17 };
+
+        assert_eq!(analysis.suggestions_at(&end, "pr"), vec!["pr", "price", "print", "print_twice", "println"]);
+        assert_eq!(analysis.suggestions_at(&end, "pri"), vec!["price", "print", "print_twice", "println"]);
+        assert_eq!(analysis.suggestions_at(&end, "qu"), vec!["quantity"]);
+        assert_eq!(analysis.suggestions_at(&end, "ca"), vec!["cast"]);
+        assert_eq!(analysis.suggestions_at(&end, "as"), vec!["assert", "assert_eq"]);
+        assert!(analysis.suggestions_at(&end, "zz").is_empty());
+
+        // Variables of main are not accessible from the earlier function:
+        let in_print_twice = Position { position: 35, line_number: 2, line_position: 12 };
+        assert_eq!(analysis.suggestions_at(&in_print_twice, "pr"), vec!["print", "print_twice", "println"]);
+
+        Ok(())
+    }
+
     #[test]
     fn variable_shadowing() -> checking::Result<()> {
         let mut chkr = new_empty_checker();
@@ -1001,6 +3087,7 @@ mod tests {
         let pos = Position::new();
 
         chkr.eval_inner_stmt(parsing::Statement::VariableDeclaration {
+            pos: Position::new(),
             identifier: "x".to_string(),
             var_type: "Num".to_string(),
             value: None
@@ -1011,6 +3098,7 @@ mod tests {
         // Shadow variable 'x' by declaring a variable in the inner scope of the
         // same name but a different type:
         chkr.eval_inner_stmt(parsing::Statement::VariableDeclaration {
+            pos: Position::new(),
             identifier: "x".to_string(),
             var_type: "Bool".to_string(),
             value: None
@@ -1024,6 +3112,7 @@ mod tests {
 
         assert_eq!(
             chkr.eval_inner_stmt(parsing::Statement::VariableDeclaration {
+                pos: Position::new(),
                 identifier: "x".to_string(),
                 var_type: "Char".to_string(),
                 value: None
@@ -0,0 +1,213 @@
+//! Contains an optimisation pass over the final IR instructions of a function
+//! that moves calculations whose results do not change between iterations of a
+//! loop (loop-invariant expressions) to before that loop.
+
+use super::{ Instruction, Value, Id };
+
+/// Move loop-invariant expressions out of the while loops of a single function
+/// (the given instructions should begin with a function instruction). Each
+/// hoisted expression is calculated once before the loop and stored in a new
+/// local variable, the ID of which is taken from the given closure. Only pure
+/// expressions (those made up of literals, variables, and operators but not
+/// function calls) are moved, and only should every variable they refer to be
+/// both given a value before the loop and never modified within it. As a loop
+/// may run zero times, divisions are only moved should they divide by a
+/// non-zero literal (and so never fail even with division checks enabled).
+pub fn hoist_loop_invariants(instructions: &mut Vec<Instruction>, mut new_id: impl FnMut() -> Id) {
+    while let Some((preheader, run)) = find_invariant_expression(instructions) {
+        let temp_id = new_id();
+        log::trace!("Hoisting loop-invariant instructions {:?} into variable with ID {}", run, temp_id);
+
+        let hoisted: Vec<Instruction> = instructions.splice(run, vec![Instruction::Push(Value::Variable(temp_id))]).collect();
+
+        let mut before_loop = vec![Instruction::Local(temp_id)];
+        before_loop.extend(hoisted);
+        before_loop.push(Instruction::Store(temp_id));
+        instructions.splice(preheader..preheader, before_loop);
+
+        if let Some(Instruction::Function { local_variable_count, .. }) = instructions.first_mut() {
+            *local_variable_count += 1;
+        }
+    }
+}
+
+/// Find an expression that can be hoisted out of a loop. Returns the index at
+/// which calculation of the expression should be inserted (immediately before
+/// the loop) and the range of instructions that make up the expression.
+fn find_invariant_expression(instructions: &[Instruction]) -> Option<(usize, std::ops::Range<usize>)> {
+    for (start, end) in find_loops(instructions) {
+        let modified = |id: Id| instructions[start..=end].iter().any(|instruction| match instruction {
+            Instruction::Local(x) | Instruction::Store(x) | Instruction::StoreAndKeep(x) | Instruction::Parameter(x) => *x == id,
+            _ => false
+        });
+
+        let assigned_before = |id: Id| instructions[..start].iter().any(|instruction| match instruction {
+            Instruction::Store(x) | Instruction::StoreAndKeep(x) | Instruction::Parameter(x) => *x == id,
+            _ => false
+        });
+
+        let is_invariant = |instruction: &Instruction, previous: Option<&Instruction>| match instruction {
+            Instruction::Push(Value::Variable(id)) => !modified(*id) && assigned_before(*id),
+            other => is_pure(other, previous)
+        };
+
+        for first in start..end {
+            if let Some(last) = expression_end(&instructions[first..end], is_invariant) {
+                // Loop begins with a jump to the condition that precedes the
+                // label marking the start of the loop body:
+                return Some((start - 1, first..first + last + 1));
+            }
+        }
+    }
+    None
+}
+
+/// Find the while loops in the given instructions, returned as the indices of
+/// the label at the start of the loop body and of the conditional jump back to
+/// that label.
+fn find_loops(instructions: &[Instruction]) -> Vec<(usize, usize)> {
+    instructions.iter().enumerate().filter_map(|(end, instruction)| match instruction {
+        Instruction::JumpIfTrue(id) => {
+            let start = instructions[..end].iter().position(|x| *x == Instruction::Label(*id))?;
+
+            if start > 0 && matches!(instructions[start - 1], Instruction::Jump(_)) { Some((start, end)) }
+            else { None }
+        }
+        _ => None
+    }).collect()
+}
+
+/// Find the index of the last instruction of the longest expression (which
+/// includes at least one operator) at the beginning of the given instructions.
+/// Such an expression leaves exactly one value on the stack without taking any
+/// values that it did not itself place on the stack.
+fn expression_end(instructions: &[Instruction], is_invariant: impl Fn(&Instruction, Option<&Instruction>) -> bool) -> Option<usize> {
+    let mut depth = 0;
+    let mut operator_found = false;
+    let mut end = None;
+
+    for (i, instruction) in instructions.iter().enumerate() {
+        if !is_invariant(instruction, i.checked_sub(1).map(|previous| &instructions[previous])) { break; }
+
+        let effect = instruction.stack_effect();
+        if effect.consumed.len() > depth { break; }
+
+        depth = depth - effect.consumed.len() + effect.produced.len();
+        operator_found |= !effect.consumed.is_empty();
+
+        if depth == 1 && operator_found { end = Some(i); }
+    }
+
+    end
+}
+
+/// Check whether the given instruction only manipulates values on the stack
+/// (i.e. has no side effects and does not alter the flow of execution). A
+/// division is only considered pure should the instruction preceding it push
+/// the divisor as a non-zero literal.
+fn is_pure(instruction: &Instruction, previous: Option<&Instruction>) -> bool {
+    match instruction {
+        Instruction::Divide | Instruction::Modulo =>
+            matches!(previous, Some(Instruction::Push(Value::Num(divisor))) if *divisor != 0.0),
+
+        _ => matches!(instruction,
+            Instruction::Push(_) |
+            Instruction::Equals | Instruction::GreaterThan | Instruction::LessThan |
+            Instruction::Add | Instruction::Subtract | Instruction::Multiply |
+            Instruction::Not | Instruction::Convert { .. }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ lexing::lexer, parsing, checking::{ self, Instruction, Value }, interpreting, stream::Stream };
+
+    fn quick_check(inp: &str, hoist: bool) -> Vec<checking::Instruction> {
+        let final_inp = inp.trim().replace("    ", "\t");
+        let tokens = lexer::input(Stream::from_str(&final_inp)).map(Result::unwrap);
+        let stmts = parsing::parser::input(tokens).map(Result::unwrap);
+        checking::checker::input_with_options(stmts, checking::Options { hoist_loop_invariants: hoist, ..Default::default() }).unwrap()
+    }
+
+    #[test]
+    fn hoisting() {
+        let src = "
+f(Num n) -> Num
+    return n
+
+main()
+    Num x = 3
+    Num i = 0
+    while i < 10
+        i = i + x * 2
+        display f(i)";
+
+        let unoptimised = quick_check(src, false);
+        let loop_label = unoptimised.iter().position(|x| matches!(x, Instruction::Label(_))).unwrap();
+        assert!(unoptimised.iter().position(|x| *x == Instruction::Multiply).unwrap() > loop_label);
+
+        let optimised = quick_check(src, true);
+        let main_start = optimised.iter().position(|x| matches!(x, Instruction::Function { label, .. } if label == "main")).unwrap();
+        let loop_label = optimised.iter().position(|x| matches!(x, Instruction::Label(_))).unwrap();
+        let multiply = optimised.iter().position(|x| *x == Instruction::Multiply).unwrap();
+
+        // Multiplication calculated once before the loop and then stored in a
+        // new local variable:
+        assert!(multiply < loop_label);
+        assert_pattern!(optimised[main_start], Instruction::Function { local_variable_count: 3, .. });
+        assert_pattern!(optimised[multiply + 1], Instruction::Store(_));
+
+        // Addition refers to a variable modified within the loop so remains:
+        assert!(optimised.iter().position(|x| *x == Instruction::Add).unwrap() > loop_label);
+
+        // Function calls are never hoisted:
+        assert!(optimised.iter().position(|x| matches!(x, Instruction::CallExpectingValue(_))).unwrap() > loop_label);
+
+        // Values displayed are unchanged:
+        let displayed = |instructions| interpreting::displayed_values(&interpreting::interpreter::trace(instructions).unwrap());
+        assert_eq!(displayed(optimised), displayed(unoptimised));
+    }
+
+    #[test]
+    fn uninitialised_variables_not_hoisted() {
+        let instructions = quick_check("
+main()
+    Num x
+    Num i = 0
+    while i < 10
+        i = i + (x + 1)", true);
+
+        let loop_label = instructions.iter().position(|x| matches!(x, Instruction::Label(_))).unwrap();
+        assert!(!instructions[..loop_label].contains(&Instruction::Push(Value::Num(1.0))));
+    }
+
+    #[test]
+    fn divisions_only_hoisted_by_non_zero_literals() {
+        // Loop body never runs so the division by zero never takes place:
+        let instructions = quick_check("
+main()
+    Num x = 0
+    Num i = 0
+    while i > 0
+        i = i + 1 / x", true);
+
+        let loop_label = instructions.iter().position(|x| matches!(x, Instruction::Label(_))).unwrap();
+        assert!(instructions.iter().position(|x| *x == Instruction::Divide).unwrap() > loop_label);
+
+        let mut interp = interpreting::interpreter::Interpreter::new(instructions).unwrap();
+        interp.enable_division_checks();
+        assert!(interp.run().is_ok());
+
+        let instructions = quick_check("
+main()
+    Num x = 0
+    Num i = 0
+    while i > 0
+        i = i + (x / 2 + x % 0)", true);
+
+        let loop_label = instructions.iter().position(|x| matches!(x, Instruction::Label(_))).unwrap();
+        assert!(instructions.iter().position(|x| *x == Instruction::Divide).unwrap() < loop_label);
+        assert!(instructions.iter().position(|x| *x == Instruction::Modulo).unwrap() > loop_label);
+    }
+}
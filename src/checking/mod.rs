@@ -3,6 +3,11 @@
 //! of a till program. For the actual checking code, see submodule `checker`.
 
 pub mod checker;
+pub mod cfg;
+pub mod callgraph;
+pub mod hoisting;
+pub mod propagation;
+pub mod verify;
 
 use crate::stream;
 use std::fmt;
@@ -27,7 +32,37 @@ pub enum Failure {
     UnexpectedType { pos: stream::Position, expected: Type, encountered: Type },
     InvalidTopLevelStatement,
     NestedFunctions(stream::Position, String),
-    MainUndefined
+    ReservedIdentifier(stream::Position, String),
+    EntryPointParameters(stream::Position, String),
+    ChainedComparison(stream::Position),
+    ExpressionTooDeep(stream::Position),
+    MainUndefined(String),
+    /// The program contains no top-level statements. Gives the identifier of
+    /// the entry point that is required, or `None` when checking a library.
+    EmptyProgram(Option<String>),
+    AmbiguousFunctionReference(stream::Position, String),
+    FunctionDisplayed(stream::Position),
+    InvalidCast { pos: stream::Position, from: Type, to: Type },
+    NameClash(stream::Position, String),
+    /// A function defined at the given position has the same identifier and
+    /// parameter types as an existing function but a different return type (a
+    /// call could not determine which is meant as the return type plays no
+    /// part in choosing between overloaded functions).
+    ConflictingOverload {
+        pos: stream::Position,
+        identifier: String, params: Vec<Type>,
+        existing: Option<Type>, encountered: Option<Type>
+    },
+    /// A warning reported while the checker's `warnings_as_errors` option is
+    /// enabled.
+    WarningAsError(Warning),
+    /// The given number of scopes remained open after checking a top-level
+    /// statement. Indicates a bug in the checker itself rather than a problem
+    /// with the input program.
+    UnbalancedScopes(usize),
+    /// The final IR produced by the checker failed verification. Like
+    /// `UnbalancedScopes`, indicates a bug in the checker itself.
+    InvalidIr(verify::IrError)
 }
 
 impl fmt::Display for Failure {
@@ -79,25 +114,331 @@ impl fmt::Display for Failure {
             Failure::NestedFunctions(pos, ident) =>
                 write!(f, "Function '{}' at {} cannot be defined as it is contained within the body of another function", ident, pos),
 
-            Failure::MainUndefined =>
-                write!(f, "All till programs are required to have a main function yet such a function could not be found")
+            Failure::ReservedIdentifier(pos, ident) =>
+                write!(f, "Function '{}' at {} cannot be defined as that identifier is reserved for use by the compiler", ident, pos),
+
+            Failure::EntryPointParameters(pos, ident) =>
+                write!(f, "The {} function defined at {} cannot take any parameters as it is the entry point of the program", ident, pos),
+
+            Failure::ChainedComparison(pos) =>
+                write!(f, "Comparison at {} cannot be chained with another as the Bool result of one comparison cannot be compared using < or > - compare each pair of values seperately instead", pos),
+
+            Failure::ExpressionTooDeep(pos) =>
+                write!(f, "Expression at {} is nested too deeply to be checked - consider splitting it up using variables", pos),
+
+            Failure::MainUndefined(ident) =>
+                write!(f, "All till programs are required to have a {} function yet such a function could not be found", ident),
+
+            Failure::EmptyProgram(Some(ident)) =>
+                write!(f, "The program is empty - all till programs are required to have at least a {} function", ident),
+
+            Failure::EmptyProgram(None) =>
+                write!(f, "The program is empty - a till library is required to have at least one function"),
+
+            Failure::AmbiguousFunctionReference(pos, ident) =>
+                write!(f, "Reference made at {} to function '{}' is ambiguous as there are multiple functions with that identifier", pos, ident),
+
+            Failure::FunctionDisplayed(pos) =>
+                write!(f, "Expression at {} evaluates to a function which cannot be displayed or printed - did you mean to call it?", pos),
+
+            Failure::InvalidCast { pos, from, to } =>
+                write!(f, "Value of type {:?} at {} cannot be cast to type {:?}", from, pos, to),
+
+            Failure::NameClash(pos, ident) =>
+                write!(f, "Variable '{}' at {} cannot be declared as a function with that identifier has already been defined", ident, pos),
+
+            Failure::ConflictingOverload { pos, identifier, params, existing, encountered } => {
+                let describe = |ret: &Option<Type>| ret.as_ref().map_or("no return value".to_string(), |t| format!("return type {:?}", t));
+                write!(f, "Function '{}' with parameter types {:?} and {} defined at {} conflicts with an existing function of {} - functions cannot be overloaded by return type alone",
+                       identifier, params, describe(encountered), pos, describe(existing))
+            }
+
+            Failure::WarningAsError(warning) =>
+                write!(f, "{} (warnings are treated as errors)", warning),
+
+            Failure::UnbalancedScopes(count) =>
+                write!(f, "Internal compiler error: {} scope(s) were left open after checking a top-level statement", count),
+
+            Failure::InvalidIr(e) =>
+                write!(f, "Internal compiler error: checker produced invalid IR - {}", e)
+        }
+    }
+}
+
+impl Failure {
+    /// Position in the input at which the failure occurred (not known for some
+    /// failures that concern the program as a whole).
+    pub fn pos(&self) -> Option<&stream::Position> {
+        match self {
+            Failure::VariableNotInScope(pos, _) |
+            Failure::FunctionUndefined(pos, _, _) |
+            Failure::VoidFunctionInExpr(pos, _, _) |
+            Failure::VoidFunctionReturnsValue(pos, _, _, _) |
+            Failure::FunctionUnexpectedReturnType { pos, .. } |
+            Failure::UnexpectedType { pos, .. } |
+            Failure::NestedFunctions(pos, _) |
+            Failure::ReservedIdentifier(pos, _) |
+            Failure::EntryPointParameters(pos, _) |
+            Failure::ChainedComparison(pos) |
+            Failure::ExpressionTooDeep(pos) |
+            Failure::AmbiguousFunctionReference(pos, _) |
+            Failure::FunctionDisplayed(pos) |
+            Failure::InvalidCast { pos, .. } |
+            Failure::NameClash(pos, _) |
+            Failure::ConflictingOverload { pos, .. } => Some(pos),
+
+            Failure::WarningAsError(warning) => Some(warning.pos()),
+
+            Failure::NonexistentPrimitiveType(_) |
+            Failure::RedefinedExistingFunction(_, _) |
+            Failure::VariableRedeclaredToDifferentType { .. } |
+            Failure::InvalidTopLevelStatement |
+            Failure::MainUndefined(_) |
+            Failure::EmptyProgram(_) |
+            Failure::UnbalancedScopes(_) |
+            Failure::InvalidIr(_) => None
+        }
+    }
+
+    /// Short name identifying the kind of failure.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Failure::NonexistentPrimitiveType(_) => "NonexistentPrimitiveType",
+            Failure::VariableNotInScope(_, _) => "VariableNotInScope",
+            Failure::FunctionUndefined(_, _, _) => "FunctionUndefined",
+            Failure::VoidFunctionInExpr(_, _, _) => "VoidFunctionInExpr",
+            Failure::RedefinedExistingFunction(_, _) => "RedefinedExistingFunction",
+            Failure::VoidFunctionReturnsValue(_, _, _, _) => "VoidFunctionReturnsValue",
+            Failure::FunctionUnexpectedReturnType { .. } => "FunctionUnexpectedReturnType",
+            Failure::VariableRedeclaredToDifferentType { .. } => "VariableRedeclaredToDifferentType",
+            Failure::UnexpectedType { .. } => "UnexpectedType",
+            Failure::InvalidTopLevelStatement => "InvalidTopLevelStatement",
+            Failure::NestedFunctions(_, _) => "NestedFunctions",
+            Failure::ReservedIdentifier(_, _) => "ReservedIdentifier",
+            Failure::EntryPointParameters(_, _) => "EntryPointParameters",
+            Failure::ChainedComparison(_) => "ChainedComparison",
+            Failure::ExpressionTooDeep(_) => "ExpressionTooDeep",
+            Failure::MainUndefined(_) => "MainUndefined",
+            Failure::EmptyProgram(_) => "EmptyProgram",
+            Failure::AmbiguousFunctionReference(_, _) => "AmbiguousFunctionReference",
+            Failure::FunctionDisplayed(_) => "FunctionDisplayed",
+            Failure::InvalidCast { .. } => "InvalidCast",
+            Failure::NameClash(_, _) => "NameClash",
+            Failure::ConflictingOverload { .. } => "ConflictingOverload",
+            Failure::WarningAsError(warning) => warning.code(),
+            Failure::UnbalancedScopes(_) => "UnbalancedScopes",
+            Failure::InvalidIr(_) => "InvalidIr"
         }
     }
 }
 
 type Result<T> = std::result::Result<T, Failure>;
 
-/// Represents the types available in till: `Char`, `Num`, and `Bool`.
+/// Represents issues with a till program that do not prevent its compilation
+/// but likely indicate a mistake.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Warning {
+    /// The condition of an if or while statement always evaluates to the same
+    /// value.
+    ConstantCondition(stream::Position, bool),
+    /// Every call to the function with the given identifier leads to the
+    /// function being called again (either directly or through other
+    /// functions) as there is no if or while statement that could prevent it.
+    PossibleInfiniteRecursion(stream::Position, String),
+    /// The function with the given identifier is never called (either directly
+    /// or through other functions) from the entry point.
+    UnusedFunction(stream::Position, String),
+    /// The condition of a while statement at the given position refers only to
+    /// variables that are never assigned to within the loop body (which also
+    /// contains no return statement). Once entered, the loop will therefore
+    /// never end.
+    LoopConditionNeverChanges(stream::Position),
+    /// The variable with the given identifier is referred to at the given
+    /// position yet there is a path through the function on which it would
+    /// not have been given a value by that point.
+    UseBeforeInit(stream::Position, String),
+    /// The variable with the given identifier declared at the given position
+    /// shadows a parameter of the same name (making that parameter
+    /// inaccessible for the rest of the block).
+    ParameterShadowed(stream::Position, String)
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::ConstantCondition(pos, value) =>
+                write!(f, "Condition at {} is always {}", pos, value),
+
+            Warning::PossibleInfiniteRecursion(pos, ident) =>
+                write!(f, "Function '{}' defined at {} always calls itself and so will never return - consider adding a base case using an if statement", ident, pos),
+
+            Warning::UnusedFunction(pos, ident) =>
+                write!(f, "Function '{}' defined at {} is never called", ident, pos),
+
+            Warning::LoopConditionNeverChanges(pos) =>
+                write!(f, "Condition of while loop at {} refers only to variables that the loop never modifies and so once true will always be true", pos),
+
+            Warning::UseBeforeInit(pos, ident) =>
+                write!(f, "Variable '{}' at {} may be used before it is given a value", ident, pos),
+
+            Warning::ParameterShadowed(pos, ident) =>
+                write!(f, "Variable '{}' declared at {} shadows the parameter of the same name", ident, pos)
+        }
+    }
+}
+
+impl Warning {
+    /// Position in the input to which the warning relates.
+    pub fn pos(&self) -> &stream::Position {
+        match self {
+            Warning::ConstantCondition(pos, _) |
+            Warning::PossibleInfiniteRecursion(pos, _) |
+            Warning::UnusedFunction(pos, _) |
+            Warning::LoopConditionNeverChanges(pos) |
+            Warning::UseBeforeInit(pos, _) |
+            Warning::ParameterShadowed(pos, _) => pos
+        }
+    }
+
+    /// Short name identifying the kind of warning.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Warning::ConstantCondition(_, _) => "ConstantCondition",
+            Warning::PossibleInfiniteRecursion(_, _) => "PossibleInfiniteRecursion",
+            Warning::UnusedFunction(_, _) => "UnusedFunction",
+            Warning::LoopConditionNeverChanges(_) => "LoopConditionNeverChanges",
+            Warning::UseBeforeInit(_, _) => "UseBeforeInit",
+            Warning::ParameterShadowed(_, _) => "ParameterShadowed"
+        }
+    }
+}
+
+/// Represents the types available in till: `Char`, `Num`, `Bool`, and function
+/// types (e.g. `Fn(Num, Char) -> Bool`).
+#[derive(Clone, Debug, PartialEq)]
+pub enum Type {
+    Char, Num, Bool,
+    Function(Box<FunctionType>)
+}
+
+/// The type of a function taking parameters of the given types and returning a
+/// value of the given type (`None` if it does not return a value).
 #[derive(Clone, Debug, PartialEq)]
-pub enum Type { Char, Num, Bool }
+pub struct FunctionType { pub params: Vec<Type>, pub ret: Option<Type> }
 
 impl Type {
+    /// Convert a type as given by the parser to a `Type`. Function types are
+    /// expected to be of the form produced by the parser (each comma followed
+    /// by a single space and the arrow surrounded by spaces).
     fn from_identifier(ident: &str) -> Result<Type> {
         match ident {
             "Char" => Ok(Type::Char),
             "Num" => Ok(Type::Num),
             "Bool" => Ok(Type::Bool),
-            _ => Err(Failure::NonexistentPrimitiveType(ident.to_string()))
+            _ => {
+                let nonexistent = || Failure::NonexistentPrimitiveType(ident.to_string());
+
+                let (param_idents, after) = ident.strip_prefix("Fn(")
+                    .and_then(split_function_identifier)
+                    .ok_or_else(nonexistent)?;
+
+                let params = param_idents.into_iter().map(Type::from_identifier).collect::<Result<_>>()?;
+
+                let ret = match after {
+                    "" => None,
+                    _ => Some(Type::from_identifier(after.strip_prefix(" -> ").ok_or_else(nonexistent)?)?)
+                };
+
+                Ok(Type::Function(Box::new(FunctionType { params, ret })))
+            }
+        }
+    }
+}
+
+/// Split the part of a function type identifier following `Fn(` into the
+/// identifiers of its parameter types and whatever follows the closing bracket
+/// of the parameter list. Returns `None` should there be no closing bracket.
+fn split_function_identifier(ident: &str) -> Option<(Vec<&str>, &str)> {
+    let mut depth = 0;
+    let mut param_start = 0;
+    let mut params = Vec::new();
+
+    for (i, c) in ident.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth > 0 => depth -= 1,
+            ')' => {
+                if i > param_start { params.push(ident[param_start..i].trim()); }
+                return Some((params, &ident[i + 1..]));
+            }
+            ',' if depth == 0 => {
+                params.push(ident[param_start..i].trim());
+                param_start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Options that alter the rules enforced by the checker.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+    /// When enabled (the default), the operands of arithmetic and comparison
+    /// operators must be of type Num. When disabled, Char operands are also
+    /// accepted and are implicitly converted to a Num of their character code.
+    pub strict_types: bool,
+    /// When enabled, a `MarkLine` instruction is inserted before the
+    /// instructions of each statement within a function body so that the
+    /// execution of each line can be counted (e.g. by coverage tooling).
+    /// Disabled by default.
+    pub mark_lines: bool,
+    /// When enabled, if and while statements with a condition that always
+    /// evaluates to the same value are simplified: a block that can never be
+    /// executed is removed along with the condition itself. Disabled by
+    /// default.
+    pub remove_dead_branches: bool,
+    /// Identifier of the function at which execution of the program begins
+    /// (`main` by default). The program must define such a function and it
+    /// cannot take any parameters.
+    pub entry_point: String,
+    /// Maximum depth to which the expressions of a syntax tree may be nested
+    /// (e.g. through brackets or chains of operators) for the checker to accept
+    /// them. Syntax trees built from source code are already limited in depth
+    /// by the parser (see `parser::MAX_EXPRESSION_DEPTH`), so this mainly
+    /// guards trees constructed by other means. 100 by default.
+    pub max_expression_depth: usize,
+    /// When enabled, expressions within a while loop whose values do not change
+    /// between iterations are calculated once before the loop instead (see
+    /// submodule `hoisting`). Disabled by default.
+    pub hoist_loop_invariants: bool,
+    /// When enabled, Boolean expressions with redundant operations are
+    /// simplified: `!!x` becomes `x` and both `x == true` and `true == x`
+    /// become `x`. Only literal operands are ever removed so any function
+    /// calls are still made. Disabled by default.
+    pub fold_boolean_identities: bool,
+    /// When enabled, references to variables that are known to hold a literal
+    /// value are replaced by that literal, and operations performed only on
+    /// literals are then replaced by their results (see submodule
+    /// `propagation`). Disabled by default.
+    pub propagate_constants: bool,
+    /// When enabled, checking fails should any warnings be reported (each
+    /// warning becoming a `Failure::WarningAsError`). Disabled by default.
+    pub warnings_as_errors: bool,
+    /// When enabled, the program is checked as a library of functions to be
+    /// called from elsewhere rather than as a program with an entry point: no
+    /// function need be named after `entry_point` and such a function is
+    /// treated like any other (no warnings are reported about functions that
+    /// are never called either). Disabled by default.
+    pub library: bool
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            strict_types: true, mark_lines: false, remove_dead_branches: false, entry_point: "main".to_string(),
+            max_expression_depth: 100, hoist_loop_invariants: false, fold_boolean_identities: false,
+            propagate_constants: false, warnings_as_errors: false, library: false
         }
     }
 }
@@ -120,12 +461,87 @@ impl Scope {
 
 pub type Id = usize;
 
-/// Definition of a variable with a given identifier and type.
+/// Take the next unique ID from the given counter. Panics should the counter
+/// overflow rather than wrapping around and producing duplicate IDs.
+pub fn take_id(counter: &mut Id) -> Id {
+    let id = *counter;
+    *counter = counter.checked_add(1).expect("Ran out of unique IDs");
+    id
+}
+
+/// Prefix of every label generated by the compiler. As `.` cannot appear in a
+/// till identifier, no generated label can be the same as the identifier of the
+/// entry point (the only till identifier used as a label).
+const LABEL_PREFIX: &str = "till.";
+
+/// The kinds of label generated by the compiler. Each generated label is made
+/// up of the reserved label prefix, a prefix specific to its kind, and then a
+/// unique ID, so generated labels cannot collide with one another nor with
+/// user identifiers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LabelKind {
+    /// The beginning of a function other than the entry point.
+    Function,
+    /// A point in the instructions of a function that can be jumped to.
+    Jump,
+    /// A constant value stored in read-only data.
+    Literal,
+    /// The beginning of the loop that calculates a remainder in generated
+    /// assembly code.
+    Remainder,
+    /// The message reported by freestanding assembly code should an assertion
+    /// fail, identified by the line number of that assertion.
+    AssertionMessage
+}
+
+impl LabelKind {
+    /// Check whether the given label is of this kind (i.e. is the prefix of
+    /// this kind followed by an ID).
+    pub fn is_kind_of(self, label: &str) -> bool {
+        label.strip_prefix(LABEL_PREFIX).and_then(|x| x.strip_prefix(self.prefix()))
+            .is_some_and(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()))
+    }
+
+    fn prefix(self) -> &'static str {
+        match self {
+            LabelKind::Function => "func",
+            LabelKind::Jump => "label",
+            LabelKind::Literal => "literal",
+            LabelKind::Remainder => "remainder",
+            LabelKind::AssertionMessage => "assertion_failed_line"
+        }
+    }
+
+    pub fn label(self, id: Id) -> String {
+        format!("{}{}{}", LABEL_PREFIX, self.prefix(), id)
+    }
+
+    /// Label of the data or code of the given name of which there is at most
+    /// one in generated assembly code (e.g. `arg_count` or `display_num`),
+    /// reserved in the same way as labels of the other kinds.
+    pub fn named(name: &str) -> String {
+        format!("{}{}", LABEL_PREFIX, name)
+    }
+}
+
+/// External symbols referenced by generated code.
+const RESERVED_IDENTIFIERS: &[&str] = &["printf", "cast"];
+
+/// Check whether a given identifier is the name of an external symbol used by
+/// generated code. Such identifiers cannot be used to name functions as the
+/// identifier of the entry point is also its label.
+fn is_reserved_identifier(ident: &str) -> bool {
+    RESERVED_IDENTIFIERS.contains(&ident)
+}
+
+/// Definition of a variable (or parameter) with a given identifier and type.
 #[derive(Debug, PartialEq)]
 struct VariableDef {
     identifier: String,
     var_type: Type,
-    id: Id
+    id: Id,
+    pos: stream::Position,
+    kind: SymbolKind
 }
 
 /// Definition of a function with an identifier, set of parameters, and a return
@@ -135,50 +551,218 @@ struct FunctionDef {
     identifier: String,
     parameter_types: Vec<Type>,
     return_type: Option<Type>,
-    label: String
+    label: String,
+    pos: stream::Position
 }
 
-#[derive(Debug, PartialEq)]
+impl FunctionDef {
+    /// The type of a value referring to this function.
+    fn function_type(&self) -> Type {
+        Type::Function(Box::new(FunctionType {
+            params: self.parameter_types.clone(),
+            ret: self.return_type.clone()
+        }))
+    }
+}
+
+/// The kinds of definition that can be made in a till program.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SymbolKind { Function, Parameter, Variable }
+
+/// Describes a function, parameter, or variable definition encountered during
+/// the checking of a till program. In the case of a function, the symbol type
+/// is the return type of that function (`None` if it does not return a value).
+/// The scope depth of a function is always 0 while the depth of a variable is
+/// the number of scopes it is nested within.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub symbol_type: Option<Type>,
+    pub pos: stream::Position,
+    pub scope_depth: usize
+}
+
+/// The lines of the input program spanned by a scope (i.e. the body of a
+/// function, if statement, or while statement), including the line on which
+/// any parameters are declared. The depth is that of the symbols defined
+/// directly within the scope.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScopeSpan {
+    pub depth: usize,
+    pub first_line: u64,
+    pub last_line: u64
+}
+
+impl ScopeSpan {
+    fn contains_line(&self, line: u64) -> bool {
+        self.first_line <= line && line <= self.last_line
+    }
+}
+
+/// A reference made to a function or variable (e.g. a function call or the use
+/// of a variable in an expression) along with the position of the definition
+/// that the reference resolved to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reference {
+    pub pos: stream::Position,
+    pub definition: stream::Position
+}
+
+/// Names built in to till that may be used anywhere within a function body
+/// (offered alongside the names of definitions by `Analysis::suggestions_at`).
+pub const BUILTIN_NAMES: &[&str] = &["approx_eq", "arg_count", "assert", "assert_eq", "cast", "display", "print", "println"];
+
+/// Information gathered during the checking of a till program that is of use
+/// to tooling such as editor integrations.
+#[derive(Debug, Default, PartialEq)]
+pub struct Analysis {
+    /// Every definition in the order that it was encountered.
+    pub symbols: Vec<Symbol>,
+    /// Every reference in the order that it was encountered.
+    pub references: Vec<Reference>,
+    /// Every scope in the order that it ended.
+    pub scopes: Vec<ScopeSpan>,
+    /// Every distinct type used by the program (by a definition or as the type
+    /// of an expression) in the order that it was first encountered. The
+    /// parameter and return types of a function type are included alongside
+    /// the function type itself.
+    pub types: Vec<Type>,
+    /// The type of every expression checked (including sub-expressions) by the
+    /// position of that expression, in the order that checking of each
+    /// expression completed (so sub-expressions precede the expressions that
+    /// contain them).
+    pub expression_types: Vec<(stream::Position, Type)>
+}
+
+impl Analysis {
+    /// Find the position of the definition referred to by the reference made at
+    /// the given position (i.e. "go to definition").
+    pub fn definition_at(&self, pos: &stream::Position) -> Option<&stream::Position> {
+        self.references.iter().find(|r| r.pos == *pos).map(|r| &r.definition)
+    }
+
+    /// Find the positions of all references made to the given definition (i.e.
+    /// "find all references"), in the order they were encountered.
+    pub fn references_of(&self, def: &Symbol) -> Vec<stream::Position> {
+        self.references.iter()
+            .filter(|r| r.definition == def.pos)
+            .map(|r| r.pos.clone())
+            .collect()
+    }
+
+    /// Find the type of the expression at the given position (e.g. to annotate
+    /// source code with the type of each expression or show the type of an
+    /// expression on hover). As a binary operation has the position of its
+    /// left operand, the type of the outer-most expression at the position is
+    /// given (that of `x * 2 > y` rather than of `x` or `x * 2`).
+    pub fn type_at(&self, pos: &stream::Position) -> Option<&Type> {
+        self.expression_types.iter().rev().find(|(expr_pos, _)| expr_pos == pos).map(|(_, t)| t)
+    }
+
+    /// Find the functions, parameters, and variables that are accessible at the
+    /// given position (e.g. for scope-aware completion), in the order they were
+    /// defined. Only definitions made before the position are included and a
+    /// variable shadowed by another of the same name is excluded.
+    pub fn symbols_at(&self, pos: &stream::Position) -> Vec<Symbol> {
+        let mut visible: Vec<Symbol> = Vec::new();
+
+        for sym in self.symbols.iter().filter(|sym| sym.pos.position <= pos.position) {
+            // Functions are accessible from anywhere after their definition
+            // while other symbols are only accessible within their scope:
+            let in_scope = sym.scope_depth == 0 || self.scopes.iter()
+                .find(|scope| scope.depth == sym.scope_depth && scope.contains_line(sym.pos.line_number))
+                .is_some_and(|scope| scope.contains_line(pos.line_number));
+
+            if in_scope {
+                if sym.kind != SymbolKind::Function {
+                    visible.retain(|other| other.kind == SymbolKind::Function || other.name != sym.name);
+                }
+                visible.push(sym.clone());
+            }
+        }
+
+        visible
+    }
+
+    /// Suggest completions of the given prefix at the given position (e.g. for
+    /// editor autocompletion) from the names of the definitions accessible at
+    /// that position and the names of built-ins. A name exactly matching the
+    /// prefix is suggested first with the remaining names sorted
+    /// alphabetically.
+    pub fn suggestions_at(&self, pos: &stream::Position, prefix: &str) -> Vec<String> {
+        let mut suggestions: Vec<String> = self.symbols_at(pos).into_iter().map(|sym| sym.name)
+            .chain(BUILTIN_NAMES.iter().map(|name| name.to_string()))
+            .filter(|name| name.starts_with(prefix))
+            .collect();
+
+        suggestions.sort_by_key(|name| (name != prefix, name.clone()));
+        suggestions.dedup();
+        suggestions
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum Value {
     /// Value is determined by that of the variable with the specified ID.
     Variable(Id),
     Num(f64),
     Char(char),
-    Bool(bool)
+    Bool(bool),
+    /// The function with the specified label.
+    Function(String)
 }
 
 /// Represents the simple, assembly-like instructions that make up the final
 /// immediate representation of a till program.
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Instruction {
     /// Create a global variable with a given ID.
     //Global(Id),
-    /// Create a function parameter with a given ID.
+    /// Create a function parameter with a given ID. The arguments of a call are
+    /// pushed on to the stack in order (first argument first) before the call
+    /// instruction so the parameter instructions at the beginning of a function
+    /// introduce its parameters in reverse order (last parameter first).
     Parameter(Id),
     /// Reserve stack space for a local variable with a given ID.
     Local(Id),
     /// Pop a value off the stack and store it in the specified variable.
     Store(Id),
+    /// Store the value on top of the stack in the specified variable as with
+    /// `Store` but leave that value on the stack (e.g. so that the assignment
+    /// in `x = y = 5` also gives the value to be assigned to `x`).
+    StoreAndKeep(Id),
     /// Push the specified value onto the stack.
     Push(Value),
     /// Identify a point in the series of instructions that can be jumped to (e.g.
     /// the beginning of a function or loop).
     Label(Id),
-    /// Identify the start of a function which can be later called upon.
+    /// Identify the start of a function which can be later called upon. The
+    /// local variable count includes the variables declared in every block of
+    /// the function body (not just those at the top level) so that space for all
+    /// of them is reserved upon entering the function.
     Function { label: String, local_variable_count: usize },
     /// Jump to the function with the specified label, return here when return
     /// instruction encountered. The function called should not return a value.
     CallExpectingVoid(String),
     CallExpectingValue(String),
+    /// Pop a function off the stack and then call it as with
+    /// `CallExpectingValue`. The given number of arguments should be pushed
+    /// before the function.
+    CallIndirect(usize),
     /// Return from call, returning value on top of stack. Will also result in
-    /// the deallocation of all variables allocated since the last begin scope
-    /// instruction.
+    /// the deallocation of the entire frame of the function (all parameters and
+    /// local variables) regardless of how deeply nested within blocks the
+    /// return is.
     ReturnValue,
-    /// Return from call without including a value. Also deallocates all variables
-    /// since the last begin scope instruction.
+    /// Return from call without including a value. Also deallocates the entire
+    /// frame of the function.
     ReturnVoid,
     /// Pop value off stack and display via stdout.
     Display { value_type: Type, line_number: u64 },
+    /// Pop value off stack and write just that value to stdout (without line
+    /// number or trailing newline).
+    Print(Type),
     /// Jump to a given label.
     Jump(Id),
     /// Pop a value off the stack, if that value is true then jump to the particular
@@ -193,6 +777,93 @@ pub enum Instruction {
     Subtract,
     Multiply,
     Divide,
+    /// Pop 2 items off the stack and push the remainder of dividing the first
+    /// by the second. The remainder takes the sign of the dividend (i.e. the
+    /// quotient is truncated towards zero as with C's `fmod`).
+    Modulo,
     /// Pop top of stack, perform boolean not, push result.
-    Not
+    Not,
+    /// Pop value of the first type off the stack, convert it to the second type,
+    /// and push the result. A Char is converted to the Num of its character
+    /// code and a Bool to either 1 (true) or 0 (false). A Num is converted to
+    /// the Char with the character code given by the integer part of that Num,
+    /// unless that integer is not a valid Unicode scalar value (i.e. is
+    /// negative, greater than 0x10FFFF, or a surrogate code point), in which
+    /// case the result is the replacement character U+FFFD. Conversion of NaN
+    /// and infinite values also results in U+FFFD.
+    Convert { from: Type, to: Type },
+    /// Indicates that execution has reached the statement beginning on the given
+    /// line of the input program. Has no effect on the program itself.
+    MarkLine(u64),
+    /// Report that the assertion on the given line of the input program failed
+    /// and then end the program unsuccessfully.
+    AssertionFailed(u64),
+    /// Push the number of command-line arguments the program was run with
+    /// (including the program name) as a Num.
+    ArgCount
+}
+
+/// Describes the effect an instruction has on the operand stack. Each entry is
+/// the type of a value taken from or placed on the stack (in the order they
+/// would be pushed, so the top of the stack is last). A type of `None`
+/// indicates a value whose type cannot be known from the instruction alone.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StackEffect {
+    pub consumed: Vec<Option<Type>>,
+    pub produced: Vec<Option<Type>>
+}
+
+impl Instruction {
+    /// The values popped off and then pushed on to the stack by this
+    /// instruction. Arguments to a call are not considered consumed by the call
+    /// instruction itself but rather by the parameter instructions of the
+    /// function called.
+    pub fn stack_effect(&self) -> StackEffect {
+        let (consumed, produced) = match self {
+            Instruction::Parameter(_) | Instruction::Store(_) | Instruction::ReturnValue => (vec![None], vec![]),
+
+            Instruction::Push(Value::Variable(_)) | Instruction::Push(Value::Function(_)) => (vec![], vec![None]),
+            Instruction::Push(Value::Num(_)) | Instruction::ArgCount => (vec![], vec![Some(Type::Num)]),
+            Instruction::Push(Value::Char(_)) => (vec![], vec![Some(Type::Char)]),
+            Instruction::Push(Value::Bool(_)) => (vec![], vec![Some(Type::Bool)]),
+
+            Instruction::CallExpectingValue(_) => (vec![], vec![None]),
+            Instruction::CallIndirect(_) | Instruction::StoreAndKeep(_) => (vec![None], vec![None]),
+
+            Instruction::Display { value_type, .. } | Instruction::Print(value_type) => (vec![Some(value_type.clone())], vec![]),
+
+            Instruction::JumpIfTrue(_) | Instruction::JumpIfFalse(_) => (vec![Some(Type::Bool)], vec![]),
+
+            Instruction::Equals => (vec![None, None], vec![Some(Type::Bool)]),
+
+            Instruction::GreaterThan | Instruction::LessThan =>
+                (vec![Some(Type::Num), Some(Type::Num)], vec![Some(Type::Bool)]),
+
+            Instruction::Add | Instruction::Subtract | Instruction::Multiply | Instruction::Divide | Instruction::Modulo =>
+                (vec![Some(Type::Num), Some(Type::Num)], vec![Some(Type::Num)]),
+
+            Instruction::Not => (vec![Some(Type::Bool)], vec![Some(Type::Bool)]),
+
+            Instruction::Convert { from, to } => (vec![Some(from.clone())], vec![Some(to.clone())]),
+
+            Instruction::Local(_) | Instruction::Label(_) | Instruction::Function { .. } |
+            Instruction::CallExpectingVoid(_) | Instruction::ReturnVoid | Instruction::Jump(_) |
+            Instruction::MarkLine(_) | Instruction::AssertionFailed(_) => (vec![], vec![])
+        };
+
+        StackEffect { consumed, produced }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ Instruction, StackEffect, Type, Value };
+
+    #[test]
+    fn stack_effects() {
+        assert_eq!(Instruction::Equals.stack_effect(), StackEffect { consumed: vec![None, None], produced: vec![Some(Type::Bool)] });
+        assert_eq!(Instruction::Push(Value::Num(1.0)).stack_effect(), StackEffect { consumed: vec![], produced: vec![Some(Type::Num)] });
+        assert_eq!(Instruction::Push(Value::Variable(0)).stack_effect().produced.len(), 1);
+        assert_eq!(Instruction::Label(0).stack_effect(), StackEffect { consumed: vec![], produced: vec![] });
+    }
 }
\ No newline at end of file
@@ -0,0 +1,316 @@
+//! Contains an optimisation pass over the final IR instructions of a function
+//! that replaces references to variables known to hold a literal value with
+//! that literal (constant propagation) and then calculates the results of
+//! operations performed only on literals (constant folding).
+
+use super::{ Instruction, Value, Id, cfg };
+
+/// Propagate and fold constants within a single function (the given
+/// instructions should begin with a function instruction). A reference to a
+/// variable is replaced by a literal only should every definition of that
+/// variable that could reach the reference (found through reaching-definitions
+/// analysis over the control-flow graph of the function) assign that same
+/// literal. This is repeated until no more references can be replaced, as
+/// folding may result in further variables being assigned literals.
+pub fn propagate_constants(instructions: &mut Vec<Instruction>) {
+    loop {
+        let propagated = propagate(instructions);
+        let folded = fold(instructions);
+
+        if !propagated && !folded { break; }
+    }
+}
+
+/// A definition of a variable - an instruction that gives a variable a value.
+/// Parameters and uninitialised local variables have an unknown value.
+struct Definition {
+    index: usize,
+    id: Id,
+    value: Option<Value>
+}
+
+/// Replace references to variables with literals where possible, returning
+/// whether any references were replaced.
+fn propagate(instructions: &mut [Instruction]) -> bool {
+    let graph = match cfg::build(instructions).into_iter().next() {
+        Some(graph) => graph,
+        None => return false
+    };
+
+    let definitions = find_definitions(instructions);
+    let reaching = reaching_definitions(&graph, &definitions);
+
+    let mut replacements = Vec::new();
+
+    for (n, block) in graph.blocks.iter().enumerate() {
+        let mut current = reaching[n].clone();
+
+        for i in block.clone() {
+            match instructions[i] {
+                Instruction::Push(Value::Variable(id)) => {
+                    if let Some(value) = known_value(id, &current, &definitions) {
+                        replacements.push((i, value));
+                    }
+                }
+                Instruction::Parameter(id) | Instruction::Local(id) | Instruction::Store(id) | Instruction::StoreAndKeep(id) => {
+                    for (def, reaches) in definitions.iter().zip(current.iter_mut()) {
+                        *reaches = if def.index == i { true } else { *reaches && def.id != id };
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    for (i, value) in &replacements {
+        log::trace!("Propagating constant {:?} to instruction at index {}", value, i);
+        instructions[*i] = Instruction::Push(value.clone());
+    }
+
+    !replacements.is_empty()
+}
+
+fn find_definitions(instructions: &[Instruction]) -> Vec<Definition> {
+    instructions.iter().enumerate().filter_map(|(index, instruction)| match instruction {
+        Instruction::Parameter(id) | Instruction::Local(id) => Some(Definition { index, id: *id, value: None }),
+
+        Instruction::Store(id) | Instruction::StoreAndKeep(id) => {
+            let value = match index.checked_sub(1).map(|previous| &instructions[previous]) {
+                Some(Instruction::Push(value @ Value::Num(_))) | Some(Instruction::Push(value @ Value::Char(_))) |
+                Some(Instruction::Push(value @ Value::Bool(_))) => Some(value.clone()),
+                _ => None
+            };
+            Some(Definition { index, id: *id, value })
+        }
+
+        _ => None
+    }).collect()
+}
+
+/// Find which definitions reach the beginning of each block of the given
+/// graph. The definitions reaching a block are those that reach the end of any
+/// of its predecessors, so this is repeated until no more changes are made
+/// (which may take several passes when the graph contains loops).
+fn reaching_definitions(graph: &cfg::Graph, definitions: &[Definition]) -> Vec<Vec<bool>> {
+    // Index of the block containing each definition:
+    let blocks: Vec<Option<usize>> = definitions.iter()
+        .map(|def| graph.blocks.iter().position(|block| block.contains(&def.index)))
+        .collect();
+
+    // Definitions made in each block that reach the end of that block:
+    let generated: Vec<Vec<bool>> = (0..graph.blocks.len()).map(|n| {
+        definitions.iter().enumerate().map(|(d, def)| {
+            blocks[d] == Some(n) && !definitions.iter().enumerate().any(|(l, later)| {
+                later.id == def.id && later.index > def.index && blocks[l] == Some(n)
+            })
+        }).collect()
+    }).collect();
+
+    // Whether each block contains a definition of the variable of each definition:
+    let killed: Vec<Vec<bool>> = (0..graph.blocks.len()).map(|n| {
+        definitions.iter().map(|def| {
+            definitions.iter().enumerate().any(|(o, other)| other.id == def.id && blocks[o] == Some(n))
+        }).collect()
+    }).collect();
+
+    let mut reaching_start = vec![vec![false; definitions.len()]; graph.blocks.len()];
+    let mut reaching_end = generated.clone();
+    let mut changed = true;
+
+    while changed {
+        changed = false;
+
+        for n in 0..graph.blocks.len() {
+            let mut start = vec![false; definitions.len()];
+
+            for (from, _) in graph.edges.iter().filter(|(_, to)| *to == Some(n)) {
+                for (reaches, pred_reaches) in start.iter_mut().zip(&reaching_end[*from]) { *reaches |= *pred_reaches; }
+            }
+
+            let end: Vec<bool> = (0..definitions.len())
+                .map(|d| generated[n][d] || (start[d] && !killed[n][d]))
+                .collect();
+
+            if end != reaching_end[n] { changed = true; }
+
+            reaching_start[n] = start;
+            reaching_end[n] = end;
+        }
+    }
+
+    reaching_start
+}
+
+/// The literal value of the variable with the given ID, should all of the
+/// reaching definitions of that variable assign it the same literal.
+fn known_value(id: Id, reaching: &[bool], definitions: &[Definition]) -> Option<Value> {
+    let mut values = definitions.iter().zip(reaching)
+        .filter(|(def, reaches)| **reaches && def.id == id)
+        .map(|(def, _)| def.value.clone());
+
+    let first = values.next()??;
+    if values.all(|value| value.as_ref() == Some(&first)) { Some(first) } else { None }
+}
+
+/// Replace operations performed only on literals with the literal result,
+/// returning whether any operations were folded.
+fn fold(instructions: &mut Vec<Instruction>) -> bool {
+    let mut folded = false;
+    let mut i = 0;
+
+    while i < instructions.len() {
+        if let Some((start, value)) = fold_at(instructions, i) {
+            log::trace!("Folding instructions {:?} into {:?}", &instructions[start..=i], value);
+            instructions.splice(start..=i, vec![Instruction::Push(value)]);
+            folded = true;
+            i = start;
+        }
+        i += 1;
+    }
+
+    folded
+}
+
+/// Calculate the result of the operation at the given index should its operands
+/// be pushed as literals by the instructions immediately before it. Returns the
+/// index of the first such operand alongside the result.
+fn fold_at(instructions: &[Instruction], i: usize) -> Option<(usize, Value)> {
+    let operand = |offset: usize| match instructions.get(i.checked_sub(offset)?) {
+        Some(Instruction::Push(Value::Variable(_))) | Some(Instruction::Push(Value::Function(_))) => None,
+        Some(Instruction::Push(value)) => Some(value.clone()),
+        _ => None
+    };
+
+    match &instructions[i] {
+        Instruction::Not => match operand(1)? {
+            Value::Bool(x) => Some((i - 1, Value::Bool(!x))),
+            _ => None
+        },
+
+        // Numbers are compared by bit pattern, as is done at run time (so 0
+        // and -0 differ yet NaN equals itself):
+        Instruction::Equals => match (operand(2)?, operand(1)?) {
+            (Value::Num(l), Value::Num(r)) => Some((i - 2, Value::Bool(l.to_bits() == r.to_bits()))),
+            (l, r) => Some((i - 2, Value::Bool(l == r)))
+        },
+
+        operator => {
+            let (l, r) = match (operand(2)?, operand(1)?) {
+                (Value::Num(l), Value::Num(r)) => (l, r),
+                _ => return None
+            };
+
+            let result = match operator {
+                Instruction::Add => Value::Num(l + r),
+                Instruction::Subtract => Value::Num(l - r),
+                Instruction::Multiply => Value::Num(l * r),
+                Instruction::Divide => Value::Num(l / r),
+                Instruction::Modulo => Value::Num(l % r),
+                Instruction::GreaterThan => Value::Bool(l > r),
+                Instruction::LessThan => Value::Bool(l < r),
+                _ => return None
+            };
+
+            Some((i - 2, result))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ lexing::lexer, parsing, checking::{ self, Instruction, Value }, stream::Stream };
+
+    fn quick_check(inp: &str, propagate: bool) -> Vec<checking::Instruction> {
+        let final_inp = inp.trim().replace("    ", "\t");
+        let tokens = lexer::input(Stream::from_str(&final_inp)).map(Result::unwrap);
+        let stmts = parsing::parser::input(tokens).map(Result::unwrap);
+        checking::checker::input_with_options(stmts, checking::Options { propagate_constants: propagate, ..Default::default() }).unwrap()
+    }
+
+    fn displayed_values(instructions: &[Instruction]) -> Vec<Instruction> {
+        instructions.windows(2)
+            .filter(|pair| matches!(pair[1], Instruction::Display { .. }))
+            .map(|pair| pair[0].clone())
+            .collect()
+    }
+
+    #[test]
+    fn propagation() {
+        let src = "
+main()
+    Num x = 5
+    Num y = x + 1
+    display y
+    display y == 6";
+
+        assert!(quick_check(src, false).contains(&Instruction::Add));
+
+        let instructions = quick_check(src, true);
+        assert!(!instructions.contains(&Instruction::Add));
+        assert!(!instructions.iter().any(|x| matches!(x, Instruction::Push(Value::Variable(_)))));
+        assert_eq!(displayed_values(&instructions), vec![Instruction::Push(Value::Num(6.0)), Instruction::Push(Value::Bool(true))]);
+    }
+
+    #[test]
+    fn reassignment() {
+        let instructions = quick_check("
+f() -> Bool
+    return true
+
+main()
+    Num x = 1
+    display x
+    if f()
+        x = 2
+    display x
+    x = 3
+    display x", true);
+
+        assert_eq!(displayed_values(&instructions)[0], Instruction::Push(Value::Num(1.0)));
+        assert!(matches!(displayed_values(&instructions)[1], Instruction::Push(Value::Variable(_))));
+        assert_eq!(displayed_values(&instructions)[2], Instruction::Push(Value::Num(3.0)));
+    }
+
+    #[test]
+    fn loops() {
+        let instructions = quick_check("
+main()
+    Num x = 5
+    Num y = 2
+    while x > 0
+        display y
+        x = x - 1
+    display x", true);
+
+        // Variable reassigned within the loop (the value from the previous
+        // iteration reaches the condition through the loop back-edge):
+        assert!(instructions.contains(&Instruction::Subtract));
+        assert!(instructions.contains(&Instruction::GreaterThan));
+        assert!(matches!(displayed_values(&instructions)[1], Instruction::Push(Value::Variable(_))));
+
+        // Variable never reassigned within the loop:
+        assert_eq!(displayed_values(&instructions)[0], Instruction::Push(Value::Num(2.0)));
+    }
+
+    #[test]
+    fn num_equality() {
+        let instructions = quick_check("
+main()
+    display 0 == (~1) * 0
+    display 0 / 0 == 0 / 0", true);
+
+        assert_eq!(displayed_values(&instructions), vec![Instruction::Push(Value::Bool(false)), Instruction::Push(Value::Bool(true))]);
+    }
+
+    #[test]
+    fn parameters() {
+        let instructions = quick_check("
+f(Num x) -> Num
+    return x + 1
+
+main()
+    display f(1)", true);
+
+        assert!(instructions.contains(&Instruction::Add));
+    }
+}
@@ -0,0 +1,87 @@
+//! Contains a pass over final IR instructions that ensures every jump and call
+//! refers to a label that is actually defined. Such faults would otherwise only
+//! be discovered when assembling the generated code (or when interpreting it),
+//! far from the stage of the compiler actually responsible.
+
+use super::{ Instruction, Value, Id };
+use std::{ collections::HashSet, fmt };
+
+/// Represents faults in final IR instructions. As the checker should only ever
+/// produce valid IR, these indicate a fault in the compiler itself.
+#[derive(Debug, PartialEq)]
+pub enum IrError {
+    /// A jump is made to the label with the given ID yet no such label exists.
+    UndefinedLabel(Id),
+    /// A call is made to (or a value refers to) the function with the given
+    /// label yet no such function exists.
+    UndefinedFunction(String)
+}
+
+impl fmt::Display for IrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IrError::UndefinedLabel(id) =>
+                write!(f, "Jump made to label with ID {} which is never defined", id),
+
+            IrError::UndefinedFunction(label) =>
+                write!(f, "Reference made to function with label '{}' which is never defined", label)
+        }
+    }
+}
+
+/// Ensure that every jump target and function referred to by the given
+/// instructions is defined. The first undefined label encountered is reported.
+pub fn verify(instructions: &[Instruction]) -> Result<(), IrError> {
+    let mut labels = HashSet::new();
+    let mut functions = HashSet::new();
+
+    for instruction in instructions {
+        match instruction {
+            Instruction::Label(id) => { labels.insert(*id); }
+            Instruction::Function { label, .. } => { functions.insert(label.as_str()); }
+            _ => {}
+        }
+    }
+
+    for instruction in instructions {
+        match instruction {
+            Instruction::Jump(id) | Instruction::JumpIfTrue(id) | Instruction::JumpIfFalse(id)
+            if !labels.contains(id) => return Err(IrError::UndefinedLabel(*id)),
+
+            Instruction::CallExpectingVoid(label) | Instruction::CallExpectingValue(label) |
+            Instruction::Push(Value::Function(label))
+            if !functions.contains(label.as_str()) => return Err(IrError::UndefinedFunction(label.clone())),
+
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checking::{ Instruction, Value };
+    use super::IrError;
+
+    #[test]
+    fn undefined_labels() {
+        let function = |label: &str| Instruction::Function { label: label.to_string(), local_variable_count: 0 };
+
+        let valid = vec![
+            function("func0"), Instruction::Label(1), Instruction::Push(Value::Bool(true)), Instruction::JumpIfTrue(1), Instruction::ReturnVoid,
+            function("main"), Instruction::CallExpectingVoid("func0".to_string()), Instruction::ReturnVoid
+        ];
+        assert_eq!(super::verify(&valid), Ok(()));
+
+        let dangling_jump = vec![
+            function("main"), Instruction::Label(0), Instruction::Jump(0), Instruction::Jump(2), Instruction::ReturnVoid
+        ];
+        assert_eq!(super::verify(&dangling_jump), Err(IrError::UndefinedLabel(2)));
+
+        let dangling_call = vec![
+            function("main"), Instruction::CallExpectingValue("func3".to_string()), Instruction::ReturnValue
+        ];
+        assert_eq!(super::verify(&dangling_call), Err(IrError::UndefinedFunction("func3".to_string())));
+    }
+}
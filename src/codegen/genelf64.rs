@@ -3,46 +3,434 @@
 
 use crate::checking;
 use super::Generator;
-use std::collections::HashMap;
+use std::{ collections::{ BTreeMap, BTreeSet, HashMap, HashSet }, str::FromStr };
 
 pub fn input(instructions: Vec<checking::Instruction>) -> String {
-    GenerateElf64::new().execute(instructions)
+    input_with_options(instructions, Options::default())
+}
+
+pub fn input_with_options(instructions: Vec<checking::Instruction>, options: Options) -> String {
+    GenerateElf64::for_instructions(&instructions, options).execute(instructions)
+}
+
+/// Count the assembly instructions generated for each function of the given
+/// final IR (labels, comments, and directives are not counted), giving a map
+/// of function label to instruction count (ordered by label). Useful for
+/// finding which functions compile to the most code.
+pub fn function_sizes(instructions: Vec<checking::Instruction>, options: Options) -> BTreeMap<String, usize> {
+    let mut generator = GenerateElf64::for_instructions(&instructions, options);
+    let mut sizes = BTreeMap::new();
+    let mut current_function = None;
+
+    for instruction in instructions {
+        if let checking::Instruction::Function { label, .. } = &instruction {
+            current_function = Some(label.clone());
+        }
+
+        let previous_len = generator.text_section.len();
+        generator.handle_instruction(instruction);
+
+        if let Some(label) = &current_function {
+            let generated = generator.text_section[previous_len..].iter()
+                .filter(|x| !matches!(x,
+                    Instruction::Comment(_) | Instruction::Label(_) | Instruction::Section(_) |
+                    Instruction::FunctionSection(_) | Instruction::Extern(_) | Instruction::Global(_)
+                ))
+                .count();
+            *sizes.entry(label.clone()).or_insert(0) += generated;
+        }
+    }
+
+    sizes
+}
+
+/// Options that alter the assembly code generated.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+    /// Declare Num constants as the hexadecimal representation of their exact
+    /// IEEE-754 bit pattern rather than in decimal (disabled by default).
+    pub hex_floats: bool,
+    /// Label of the function at which execution begins, made global so that it
+    /// is visible to the linker (`main` by default). Should match the entry
+    /// point used when checking.
+    pub entry_point: String,
+    /// How till functions (other than the entry point, which is called
+    /// according to the C calling convention) call one another.
+    pub calling_convention: CallingConvention,
+    /// Generate position-independent code (as required to produce a PIE) by
+    /// referring to labels relative to the instruction pointer and calling
+    /// external functions through the procedure linkage table (disabled by
+    /// default).
+    pub position_independent: bool,
+    /// Place each function in its own section (named `.text.` followed by the
+    /// function label) rather than all in `.text` so that a linker can discard
+    /// the code of functions that are never referred to (e.g. when ld is given
+    /// `--gc-sections`). Disabled by default.
+    pub function_sections: bool,
+    /// Set every local variable of a function to zero upon entering that
+    /// function so that reading a variable before it is assigned gives a
+    /// predictable value (0 for Num, false for Bool, and the null character
+    /// for Char) rather than whatever was previously on the stack. Disabled by
+    /// default.
+    pub zero_locals: bool,
+    /// Size of the floating-point representation of Num values (64-bit by
+    /// default).
+    pub num_precision: Precision,
+    /// End the program by making an exit system call when returning from the
+    /// entry point (or should an assertion fail) rather than by returning to
+    /// (or calling `exit` within) the C runtime. Failed assertions and
+    /// divisions by zero are reported with a write system call, so the C
+    /// library is only referred to (through `printf`) by programs that display
+    /// or print values. Disabled by default.
+    pub freestanding: bool,
+    /// Assembler for which the directives (sections, symbols, data
+    /// declarations, and comments) of the output are written (NASM by
+    /// default).
+    pub dialect: Dialect,
+    /// Generate a library of functions rather than a program: no entry point is
+    /// made global and instead every till function is made global under its
+    /// label (e.g. `till.func0`) so that it may be called from other object files.
+    /// Should match the library option used when checking. Disabled by
+    /// default.
+    pub library: bool,
+    /// Precede the declaration of each Num literal in read-only data with a
+    /// comment giving the value of that literal in the source code as well as
+    /// its line, should the line be known (i.e. the checker's `mark_lines`
+    /// option was enabled). Disabled by default.
+    pub literal_comments: bool,
+    /// Declare each distinct Num literal in read-only data only once, with
+    /// every push of that value referring to the same label, rather than once
+    /// for every time the literal appears. Literals are considered identical
+    /// only should they have the same bit pattern. Disabled by default.
+    pub pool_literals: bool,
+    /// Check the divisor of each division and modulo operation, ending the
+    /// program unsuccessfully (with exit status 1) after reporting the division
+    /// should the divisor be zero rather than continuing with an infinite or
+    /// NaN result. Disabled by default.
+    pub check_division: bool,
+    /// Compile an equals, greater than, or less than comparison that is
+    /// directly followed by a conditional jump (as is the condition of an if or
+    /// while statement) as a single conditional jump on the result of that
+    /// comparison, without placing the resulting Bool on the stack. Disabled by
+    /// default.
+    pub fuse_comparisons: bool,
+    /// Platform the code is generated for, as named in the comment at the
+    /// start of the output and determining the symbols of C library functions.
+    /// The other options are not changed by the target - those following its
+    /// conventions are given by `Target::elf64_options`.
+    pub target: super::target::Target
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            hex_floats: false, entry_point: "main".to_string(),
+            calling_convention: CallingConvention::default(), position_independent: false,
+            function_sections: false, zero_locals: false, num_precision: Precision::default(),
+            freestanding: false, dialect: Dialect::default(), library: false,
+            literal_comments: false, pool_literals: false, check_division: false,
+            fuse_comparisons: false, target: super::target::Target::default()
+        }
+    }
+}
+
+/// Floating-point representation of Num values. Every value occupies 8 bytes
+/// regardless, so a single-precision Num is stored in the lower 4 bytes with
+/// the upper 4 bytes clear (allowing values to still be compared for equality
+/// as 64-bit integers). Single-precision Nums are converted to double precision
+/// when passed to printf.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Precision {
+    /// 32-bit IEEE-754 floats.
+    Single,
+    /// 64-bit IEEE-754 floats.
+    #[default]
+    Double
+}
+
+impl Precision {
+    /// Size specifier of a memory operand holding a float of this precision.
+    fn size(self) -> &'static str {
+        match self {
+            Precision::Single => "dword",
+            Precision::Double => "qword"
+        }
+    }
+}
+
+/// Assembler for which output is produced. Instructions are written in Intel
+/// syntax for either assembler, although the two differ in the form of
+/// directives, operand sizes, and references to labels.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Dialect {
+    /// The Netwide Assembler (e.g. `section .text`, `global main`, `dq 1.5`).
+    #[default]
+    Nasm,
+    /// The GNU assembler (e.g. `.text`, `.globl main`, `.double 1.5`, `qword
+    /// ptr [rsp]`, `[rip + till.literal0]`).
+    Gas
+}
+
+impl FromStr for Dialect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nasm" => Ok(Dialect::Nasm),
+            "gas" => Ok(Dialect::Gas),
+            _ => Err(format!("Unknown assembler dialect '{}' (expected nasm or gas)", s))
+        }
+    }
+}
+
+/// Describes how till functions call one another. The caller pushes each
+/// argument on to the stack in order (so the last argument is nearest the top
+/// of the stack) and then reserves any shadow space before calling the
+/// function. The function returns its result in rax and removes both the
+/// arguments and the shadow space from the stack as it returns.
+///
+/// Within a function, the stack therefore looks like the following (from
+/// higher to lower addresses):
+///
+/// ```text
+/// first argument ... last argument | shadow space | return address | saved rbp <- rbp | local variables
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CallingConvention {
+    /// Bytes of unused stack space reserved by the caller between the
+    /// arguments and the return address (0 by default).
+    pub shadow_space: usize
+}
+
+/// Number of values placed on the stack between the shadow space and the local
+/// variables of a function (the return address and the saved base pointer).
+const SAVED_FRAME_VALUES: usize = 2;
+
+impl CallingConvention {
+    /// Location of the parameter with the given index (0 being the parameter
+    /// nearest the top of the stack, i.e. the last argument).
+    fn parameter_location(&self, index: usize) -> Oprand {
+        Oprand::AddressDisplaced(
+            Box::new(Oprand::Register(Reg::BasePointer)),
+            ((SAVED_FRAME_VALUES + index) * BYTES_IN_VALUE + self.shadow_space) as isize
+        )
+    }
+
+    /// Instructions that call a function (the arguments having already been
+    /// pushed) using the given call instruction. Should the function return a
+    /// value, that value is placed on the stack.
+    fn call(&self, call_instruction: Instruction, expecting_value: bool) -> Vec<Instruction> {
+        let mut instructions = Vec::new();
+
+        if self.shadow_space > 0 {
+            instructions.push(Instruction::Sub {
+                dest: Oprand::Register(Reg::StackPointer),
+                src: Oprand::Value(Val::Int(self.shadow_space as isize))
+            });
+        }
+
+        instructions.push(call_instruction);
+
+        if expecting_value {
+            // Place the function return value on the stack:
+            instructions.push(Instruction::Push(Oprand::Register(Reg::Rax)));
+        }
+
+        instructions
+    }
+
+    /// Instructions at the beginning of a function with the given label.
+    fn prologue(&self, label: String, local_variable_count: usize) -> Vec<Instruction> {
+        vec![
+            Instruction::Label(label),
+            // Preserve the base pointer of the previous frame:
+            Instruction::Push(Oprand::Register(Reg::BasePointer)),
+            // Create a new frame beginning at the current stack top:
+            Instruction::Mov {
+                dest: Oprand::Register(Reg::BasePointer),
+                src: Oprand::Register(Reg::StackPointer)
+            },
+            // Reserve stack space for the storage of local variables:
+            Instruction::Sub {
+                dest: Oprand::Register(Reg::StackPointer),
+                src: Oprand::Value(Val::Int((local_variable_count * BYTES_IN_VALUE) as isize))
+            }
+        ]
+    }
+
+    /// Instructions that return from a function with the given number of
+    /// parameters (any return value having already been placed in rax). The
+    /// entry point is called from outside till code and so removes nothing
+    /// from the stack of its caller.
+    fn epilogue(&self, parameter_count: usize, is_entry_point: bool) -> Vec<Instruction> {
+        let caller_values_size = if is_entry_point { 0 } else { parameter_count * BYTES_IN_VALUE + self.shadow_space };
+
+        vec![
+            // Restore stack pointer:
+            Instruction::Mov {
+                dest: Oprand::Register(Reg::StackPointer),
+                src: Oprand::Register(Reg::BasePointer)
+            },
+            // Restore the base pointer of the previous frame:
+            Instruction::Pop(Oprand::Register(Reg::BasePointer)),
+            // Remove parameter values (and shadow space) from the stack and return:
+            Instruction::Ret(caller_values_size)
+        ]
+    }
 }
 
 struct GenerateElf64 {
+    options: Options,
     text_section: Vec<Instruction>,
     rodata_section: Vec<Instruction>,
-    num_label_counter: usize,
+    num_label_counter: checking::Id,
+    /// Labels of the Num literals declared so far by bit pattern (only used
+    /// should literals be pooled). Only ever used for lookups.
+    pooled_num_labels: HashMap<u64, String>,
+    remainder_label_counter: checking::Id,
+    /// Only ever used for lookups so the order of iteration cannot affect the
+    /// output.
     function_variable_locations: HashMap<checking::Id, Oprand>,
     local_variable_num: usize,
     parameter_variable_num: usize,
-    display_num_used: bool,
-    display_bool_used: bool,
-    display_char_used: bool
+    in_entry_point: bool,
+    /// Line of the statement currently being generated, if known (see
+    /// `checking::Instruction::MarkLine`).
+    current_line: Option<u64>,
+    /// Format in which a value of each type is displayed.
+    display_formats: Vec<DisplayFormat>,
+    /// Labels of the display formats used so far. Only ever used for lookups.
+    display_formats_used: HashSet<String>,
+    print_num_used: bool,
+    print_bool_used: bool,
+    print_char_used: bool,
+    assertion_failed_used: bool,
+    /// Lines of the assertions that may fail when freestanding, each of which
+    /// has its own message (as that message is written without printf).
+    freestanding_assertion_lines: BTreeSet<u64>,
+    division_by_zero_used: bool,
+    /// Whether the program refers to its command-line arguments, in which case
+    /// the argument count is stored by the entry point for later use.
+    args_used: bool,
+    /// Comparison whose code is yet to be generated as it may be fused with a
+    /// conditional jump that follows (only should comparisons be fused).
+    pending_comparison: Option<checking::Instruction>
 }
 
 impl GenerateElf64 {
-    fn new() -> Self {
+    fn new(options: Options) -> Self {
+        let mut text_section = vec![
+            Instruction::Comment(format!("Target: {}", options.target)),
+            Instruction::Section("text".to_string())
+        ];
+        // Freestanding code instead refers only to the C library functions it
+        // actually calls (see `construct_output`):
+        if !options.freestanding {
+            text_section.extend(vec![
+                Instruction::Extern(options.target.c_symbol("printf")), Instruction::Extern(options.target.c_symbol("exit"))
+            ]);
+        }
+        // The functions of a library are instead each made global as they are
+        // generated:
+        if !options.library { text_section.push(Instruction::Global(options.entry_point.clone())); }
+
         GenerateElf64 {
-            text_section: vec![
-                Instruction::Comment(format!("Target: {}", Self::TARGET_NAME)),
-                Instruction::Section("text".to_string()),
-                Instruction::Extern("printf".to_string()),
-                Instruction::Global("main".to_string())
-            ],
+            text_section,
+            options,
             rodata_section: vec![Instruction::Section("rodata".to_string())],
             num_label_counter: 0,
+            pooled_num_labels: HashMap::new(),
+            remainder_label_counter: 0,
             function_variable_locations: HashMap::new(),
             local_variable_num: 0,
             parameter_variable_num: 0,
-            display_num_used: false,
-            display_bool_used: false,
-            display_char_used: false
+            in_entry_point: false,
+            current_line: None,
+            display_formats: default_display_formats(),
+            display_formats_used: HashSet::new(),
+            print_num_used: false,
+            print_bool_used: false,
+            print_char_used: false,
+            assertion_failed_used: false,
+            freestanding_assertion_lines: BTreeSet::new(),
+            division_by_zero_used: false,
+            args_used: false,
+            pending_comparison: None
         }
     }
+
+    /// Prepare to generate code for the given final IR, noting anything about
+    /// the program as a whole that must be known before any instruction is
+    /// handled (i.e. whether the argument count is referred to).
+    fn for_instructions(instructions: &[checking::Instruction], options: Options) -> Self {
+        let mut generator = GenerateElf64::new(options);
+        generator.args_used = instructions.contains(&checking::Instruction::ArgCount);
+        generator
+    }
+}
+
+/// How a displayed value is given to printf.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ArgumentPassing {
+    /// As an integer in rdx (the third argument).
+    Integer,
+    /// As a double in xmm0 (the first floating-point argument).
+    Float
+}
+
+/// Format in which values of a particular type are written by display
+/// instructions.
+#[derive(Clone, Debug, PartialEq)]
+struct DisplayFormat {
+    value_type: checking::Type,
+    /// Label under which the format string is declared in read-only data.
+    label: String,
+    /// printf format string, given the line number followed by the value.
+    format: String,
+    passing: ArgumentPassing
+}
+
+impl DisplayFormat {
+    fn new(value_type: checking::Type, label: &str, format: &str, passing: ArgumentPassing) -> Self {
+        DisplayFormat { value_type, label: checking::LabelKind::named(label), format: format.to_string(), passing }
+    }
+}
+
+/// Display formats of every type that can be displayed. Format strings are
+/// declared in this order.
+fn default_display_formats() -> Vec<DisplayFormat> {
+    vec![
+        DisplayFormat::new(checking::Type::Char, "display_char", r"Line %u character value: '%c'\n\0", ArgumentPassing::Integer),
+        DisplayFormat::new(checking::Type::Bool, "display_bool", r"Line %u boolean value: %lld\n\0", ArgumentPassing::Integer),
+        DisplayFormat::new(checking::Type::Num, "display_num", r"Line %u number value: %f\n\0", ArgumentPassing::Float)
+    ]
 }
 
 const BYTES_IN_VALUE: usize = 8;
+
+/// Number of the Linux x86_64 system call that ends the calling process.
+const SYS_EXIT: isize = 60;
+const SYS_WRITE: isize = 1;
+const STDOUT_FILENO: isize = 1;
+
+/// Reported should a checked division by zero take place.
+const DIVISION_BY_ZERO_MESSAGE: &str = "Division by zero\n";
+
+/// Reported by a freestanding program should the assertion on the given line
+/// fail (as is done with the `assertion_failed` format otherwise).
+fn assertion_failed_message(line_number: u64) -> String { format!("Line {} assertion failed\n", line_number) }
+
+fn assertion_failed_label(line_number: u64) -> String { checking::LabelKind::AssertionMessage.label(line_number as checking::Id) }
+
+/// Location of the local variable with the given index (0 being the first local
+/// variable of the function, nearest the base pointer).
+fn local_location(index: usize) -> Oprand {
+    Oprand::AddressDisplaced(
+        Box::new(Oprand::Register(Reg::BasePointer)),
+        -(BYTES_IN_VALUE as isize) * (index as isize + 1)
+    )
+}
+
 const CARRY_FLAG_BIT_OFFSET: usize = 8;
 const ZERO_FLAG_BIT_OFFSET: usize = 14;
 
@@ -52,33 +440,45 @@ const POP_AND_CMP_WITH_ZERO_INSTRUCTIONS: &[Instruction] = &[
 ];
 
 impl Generator for GenerateElf64 {
-    const TARGET_NAME: &'static str = "Linux elf64";
-
     fn handle_instruction(&mut self, instruction: checking::Instruction) {
+        if let Some(comparison) = self.pending_comparison.take() {
+            if let checking::Instruction::JumpIfTrue(id) | checking::Instruction::JumpIfFalse(id) = instruction {
+                self.text_section.push(Instruction::Comment(format!("{:?}", instruction)));
+                self.add_comparison_jump_instructions(comparison, matches!(instruction, checking::Instruction::JumpIfTrue(_)), id);
+                return;
+            }
+            self.add_comparison_instructions(comparison);
+        }
+
         self.text_section.push(Instruction::Comment(format!("{:?}", instruction)));
         match instruction {
             checking::Instruction::Push(val) => {
                 let oprand = match val {
                     checking::Value::Num(num_val) => {
-                        let label = literal_label(self.num_label_counter);
-                        self.num_label_counter += 1;
-
-                        self.rodata_section.extend(vec![
-                            Instruction::Label(label.clone()),
-                            Instruction::Declare(Val::Float(num_val))
-                        ]);
-
-                        Oprand::Address(Box::new(Oprand::Label(label)))
+                        let label = self.num_literal_label(num_val);
+                        self.label_contents(label)
                     }
 
                     checking::Value::Variable(var_id) =>
                         self.function_variable_locations.get(&var_id).unwrap().clone(),
 
+                    // Character codes are unsigned and at most 0x10FFFF so
+                    // always fit in the sign-extended 32-bit immediate of a
+                    // push instruction as a positive integer:
                     checking::Value::Char(chr_val) =>
-                        Oprand::Value(Val::Int(chr_val as isize)),
+                        Oprand::Value(Val::Int(u32::from(chr_val) as isize)),
 
                     checking::Value::Bool(bool_val) =>
-                        Oprand::Value(Val::Int(if bool_val { 1 } else { 0 }))
+                        Oprand::Value(Val::Int(if bool_val { 1 } else { 0 })),
+
+                    // Address of the function:
+                    checking::Value::Function(label) => {
+                        if self.options.position_independent {
+                            self.text_section.push(self.label_address(Reg::Rax, label));
+                            Oprand::Register(Reg::Rax)
+                        }
+                        else { Oprand::Label(label) }
+                    }
                 };
 
                 self.text_section.push(Instruction::Push(oprand));
@@ -90,26 +490,29 @@ impl Generator for GenerateElf64 {
                 self.text_section.push(Instruction::Pop(location.clone()));
             }
 
+            checking::Instruction::StoreAndKeep(id) => {
+                let location = self.function_variable_locations.get(&id).unwrap().clone();
+
+                // Copy the value on top of the stack without popping it:
+                self.text_section.extend(vec![
+                    Instruction::Mov {
+                        dest: Oprand::Register(Reg::Rax),
+                        src: Oprand::Address(Box::new(Oprand::Register(Reg::StackPointer)))
+                    },
+                    Instruction::Mov { dest: location, src: Oprand::Register(Reg::Rax) }
+                ]);
+            }
+
             checking::Instruction::Parameter(id) => {
                 self.function_variable_locations.insert(
-                    id,
-                    Oprand::AddressDisplaced(
-                        Box::new(Oprand::Register(Reg::BasePointer)),
-                        ((self.parameter_variable_num + 2) * BYTES_IN_VALUE) as isize
-                    )
+                    id, self.options.calling_convention.parameter_location(self.parameter_variable_num)
                 );
 
                 self.parameter_variable_num += 1;
             }
 
             checking::Instruction::Local(id) => {
-                self.function_variable_locations.insert(
-                    id,
-                    Oprand::AddressDisplaced(
-                        Box::new(Oprand::Register(Reg::BasePointer)),
-                        -(BYTES_IN_VALUE as isize) * (self.local_variable_num as isize + 1)
-                    )
-                );
+                self.function_variable_locations.insert(id, local_location(self.local_variable_num));
 
                 self.local_variable_num += 1;
             }
@@ -122,32 +525,49 @@ impl Generator for GenerateElf64 {
                 self.local_variable_num = 0;
                 self.parameter_variable_num = 0;
                 self.function_variable_locations.clear();
+                self.in_entry_point = label == self.options.entry_point && !self.options.library;
 
-                self.text_section.extend(vec![
-                    Instruction::Label(label),
-                    // Preserve the base pointer of the previous frame:
-                    Instruction::Push(Oprand::Register(Reg::BasePointer)),
-                    // Create a new frame beginning at the current stack top:
-                    Instruction::Mov {
-                        dest: Oprand::Register(Reg::BasePointer),
-                        src: Oprand::Register(Reg::StackPointer)
-                    },
-                    // Reserve stack space for the storage of local variables:
-                    Instruction::Sub {
-                        dest: Oprand::Register(Reg::StackPointer),
-                        src: Oprand::Value(Val::Int((local_variable_count * BYTES_IN_VALUE) as isize))
+                if self.options.library && checking::LabelKind::Function.is_kind_of(&label) {
+                    self.text_section.push(Instruction::Global(label.clone()));
+                }
+
+                if self.options.function_sections {
+                    self.text_section.push(Instruction::FunctionSection(label.clone()));
+                }
+
+                let prologue = self.options.calling_convention.prologue(label, local_variable_count);
+                self.text_section.extend(prologue);
+
+                if self.options.zero_locals && local_variable_count > 0 {
+                    self.text_section.push(Instruction::Mov { dest: Oprand::Register(Reg::Rax), src: Oprand::Value(Val::Int(0)) });
+
+                    for index in 0..local_variable_count {
+                        self.text_section.push(Instruction::Mov {
+                            dest: local_location(index),
+                            src: Oprand::Register(Reg::Rax)
+                        });
                     }
-                ]);
+                }
+
+                if self.in_entry_point && self.args_used { self.add_store_arg_count_instructions(); }
             }
 
-            checking::Instruction::CallExpectingVoid(label) => { self.text_section.push(Instruction::Call(label)); }
+            checking::Instruction::CallExpectingVoid(label) => {
+                let call = self.options.calling_convention.call(Instruction::Call(label), false);
+                self.text_section.extend(call);
+            }
 
             checking::Instruction::CallExpectingValue(label) => {
-                self.text_section.extend(vec![
-                    Instruction::Call(label),
-                    // Place the function return value on the stack:
-                    Instruction::Push(Oprand::Register(Reg::Rax))
-                ]);
+                let call = self.options.calling_convention.call(Instruction::Call(label), true);
+                self.text_section.extend(call);
+            }
+
+            checking::Instruction::CallIndirect(_) => {
+                // Take the address of the function to call off the stack:
+                self.text_section.push(Instruction::Pop(Oprand::Register(Reg::Rax)));
+
+                let call = self.options.calling_convention.call(Instruction::CallIndirect(Oprand::Register(Reg::Rax)), true);
+                self.text_section.extend(call);
             }
 
             checking::Instruction::ReturnVoid => self.add_return_instructions(),
@@ -159,49 +579,59 @@ impl Generator for GenerateElf64 {
             }
 
             checking::Instruction::Display { value_type, line_number } => {
+                let format = self.display_formats.iter().find(|format| format.value_type == value_type).cloned()
+                    .unwrap_or_else(|| unreachable!("Checker does not allow values of type {:?} to be displayed", value_type));
+                self.display_formats_used.insert(format.label.clone());
+
+                let float_args_count = match format.passing {
+                    ArgumentPassing::Integer => {
+                        // Pop value from stack into rdx (third argument):
+                        self.text_section.push(Instruction::Pop(Oprand::Register(Reg::Rdx)));
+                        0
+                    }
+                    ArgumentPassing::Float => {
+                        self.add_pop_float_argument_instructions();
+                        1
+                    }
+                };
+
+                // Load line number (second argument):
+                self.text_section.push(
+                    Instruction::Mov { dest: Oprand::Register(Reg::SrcIndex), src: Oprand::Value(Val::Int(line_number as isize)) }
+                );
+
+                self.add_printf_call_instructions(&format.label, float_args_count);
+            }
+
+            checking::Instruction::Print(value_type) => {
                 let (format_label, float_args_count) = match value_type {
                     checking::Type::Char => {
-                        self.display_char_used = true;
-                        // Pop character from stack into rdx (third argument):
-                        self.text_section.push(Instruction::Pop(Oprand::Register(Reg::Rdx)));
-                        ("display_char", 0)
+                        self.print_char_used = true;
+                        // Pop character from stack into rsi (second argument):
+                        self.text_section.push(Instruction::Pop(Oprand::Register(Reg::SrcIndex)));
+                        (checking::LabelKind::named("print_char"), 0)
                     }
                     checking::Type::Bool => {
-                        self.display_bool_used = true;
-                        // Pop bool from stack into rdx (third argument):
-                        self.text_section.push(Instruction::Pop(Oprand::Register(Reg::Rdx)));
-                        ("display_bool", 0)
-                    }
-                    checking::Type::Num => {
-                        self.display_num_used = true;
-                        // Pop and store float in xmm0 register (first floating-point argument):
+                        self.print_bool_used = true;
                         self.text_section.extend(vec![
                             Instruction::Pop(Oprand::Register(Reg::Rax)),
-                            Instruction::Movq {
-                                dest: Oprand::Register(Reg::Xmm0),
-                                src: Oprand::Register(Reg::Rax)
-                            }
+                            // Select either the "true" or "false" string as the second argument:
+                            self.label_address(Reg::SrcIndex, checking::LabelKind::named("print_false")),
+                            self.label_address(Reg::Rdx, checking::LabelKind::named("print_true")),
+                            Instruction::Cmp { dest: Oprand::Register(Reg::Rax), src: Oprand::Value(Val::Int(0)) },
+                            Instruction::Cmovne { dest: Oprand::Register(Reg::SrcIndex), src: Oprand::Register(Reg::Rdx) }
                         ]);
-                        ("display_num", 1)
+                        (checking::LabelKind::named("print_bool"), 0)
+                    }
+                    checking::Type::Num => {
+                        self.print_num_used = true;
+                        self.add_pop_float_argument_instructions();
+                        (checking::LabelKind::named("print_num"), 1)
                     }
+                    checking::Type::Function(_) => unreachable!("Checker does not allow functions to be printed")
                 };
 
-                self.text_section.extend(vec![
-                    // Load format string (first argument):
-                    Instruction::Mov { dest: Oprand::Register(Reg::DestIndex), src: Oprand::Label(format_label.to_string()) },
-                    // Load line number (second argument):
-                    Instruction::Mov { dest: Oprand::Register(Reg::SrcIndex), src: Oprand::Value(Val::Int(line_number as isize)) },
-                    // Indicate number of floating-point arguments:
-                    Instruction::Mov { dest: Oprand::Register(Reg::Rax), src: Oprand::Value(Val::Int(float_args_count)) },
-                    // Preserve stack pointer:
-                    Instruction::Mov { dest: Oprand::Register(Reg::Rbx), src: Oprand::Register(Reg::StackPointer) },
-                    // Align stack to 16-byte boundary:
-                    Instruction::BitwiseAnd { dest: Oprand::Register(Reg::StackPointer), src: Oprand::Value(Val::Int(-16)) },
-                    // Call printf function:
-                    Instruction::Call("printf".to_string()),
-                    // Restore stack pointer:
-                    Instruction::Mov { dest: Oprand::Register(Reg::StackPointer), src: Oprand::Register(Reg::Rbx) }
-                ]);
+                self.add_printf_call_instructions(&format_label, float_args_count);
             }
 
             checking::Instruction::Jump(id) => { self.text_section.push(Instruction::Jmp(label(id))); }
@@ -218,54 +648,22 @@ impl Generator for GenerateElf64 {
                 self.text_section.push(Instruction::Je(label(id)));
             }
 
-            checking::Instruction::Equals => {
-                self.text_section.extend(vec![
-                    // Take first value in comparison off the stack:
-                    Instruction::Pop(Oprand::Register(Reg::Rax)),
-                    // Subtract that value by the second top value on stack:
-                    Instruction::Sub {
-                        dest: Oprand::Register(Reg::Rax),
-                        src: Oprand::Address(Box::new(Oprand::Register(Reg::StackPointer)))
-                    },
-                    // Push flags register onto the stack:
-                    Instruction::PushFlags,
-                    // Pop the flags register into rax:
-                    Instruction::Pop(Oprand::Register(Reg::Rax)),
-                    // Extract the value of the zero flag:
-                    Instruction::Shr { dest: Oprand::Register(Reg::Rax), shift_by: 6 },
-                    Instruction::BitwiseAnd { dest: Oprand::Register(Reg::Rax), src: Oprand::Value(Val::Int(1)) },
-                    // Place the value of the zero flag onto the stack:
-                    Instruction::Mov {
-                        dest: Oprand::Address(Box::new(Oprand::Register(Reg::StackPointer))),
-                        src: Oprand::Register(Reg::Rax)
-                    }
-                ]);
+            comparison @ (checking::Instruction::Equals | checking::Instruction::GreaterThan | checking::Instruction::LessThan) => {
+                if self.options.fuse_comparisons { self.pending_comparison = Some(comparison); }
+                else { self.add_comparison_instructions(comparison); }
             }
 
             checking::Instruction::Add => self.add_arithmetic_instructions(Instruction::FpuAdd),
             checking::Instruction::Subtract => self.add_arithmetic_instructions(Instruction::FpuSubtract),
             checking::Instruction::Multiply => self.add_arithmetic_instructions(Instruction::FpuMultiply),
-            checking::Instruction::Divide => self.add_arithmetic_instructions(Instruction::FpuDivide),
-
-            checking::Instruction::GreaterThan => {
-                self.add_comparison_instructions(vec![
-                    // Extract the carry flag bit (indicates greater than when set in this instance):
-                    Instruction::Shr { dest: Oprand::Register(Reg::Ax), shift_by: CARRY_FLAG_BIT_OFFSET }
-                ]);
+            checking::Instruction::Divide => {
+                if self.options.check_division { self.add_divisor_check_instructions(); }
+                self.add_arithmetic_instructions(Instruction::FpuDivide);
             }
 
-            checking::Instruction::LessThan => {
-                self.add_comparison_instructions(vec![
-                    // Create second copy of FPU status word:
-                    Instruction::Mov { dest: Oprand::Register(Reg::Bx), src: Oprand::Register(Reg::Ax) },
-                    // Have carry flag as least significant bit of ax:
-                    Instruction::Shr { dest: Oprand::Register(Reg::Ax), shift_by: CARRY_FLAG_BIT_OFFSET },
-                    // Have zero flag as least significant bit of bx:
-                    Instruction::Shr { dest: Oprand::Register(Reg::Bx), shift_by: ZERO_FLAG_BIT_OFFSET },
-                    // Both carry flag and zero flag being 0 indicates less than:
-                    Instruction::BitwiseOr { dest: Oprand::Register(Reg::Ax), src: Oprand::Register(Reg::Bx) },
-                    Instruction::BitwiseNot(Oprand::Register(Reg::Ax))
-                ]);
+            checking::Instruction::Modulo => {
+                if self.options.check_division { self.add_divisor_check_instructions(); }
+                self.add_modulo_instructions();
             }
 
             checking::Instruction::Not => {
@@ -279,34 +677,189 @@ impl Generator for GenerateElf64 {
                     }
                 ]);
             }
+
+            // Both character codes and Bool values (1 or 0) are stored as
+            // integers:
+            checking::Instruction::Convert { from: checking::Type::Char | checking::Type::Bool, to: checking::Type::Num } => {
+                self.text_section.extend(vec![
+                    Instruction::FpuReset,
+                    // Load the integer on top of the stack onto the FPU stack:
+                    Instruction::FpuPushInt(Oprand::Address(Box::new(Oprand::Register(Reg::StackPointer)))),
+                    // Replace the top of the stack with that value as a float:
+                    Instruction::FpuPop(Oprand::Address(Box::new(Oprand::Register(Reg::StackPointer))), self.options.num_precision)
+                ]);
+            }
+
+            checking::Instruction::Convert { from: checking::Type::Num, to: checking::Type::Char } => {
+                self.text_section.extend(vec![
+                    // Truncate the Num on top of the stack to an integer (NaN and
+                    // values too large for 64 bits give the most negative integer):
+                    Instruction::FloatToInt {
+                        dest: Oprand::Register(Reg::Rax),
+                        src: Oprand::Address(Box::new(Oprand::Register(Reg::StackPointer))),
+                        precision: self.options.num_precision
+                    },
+                    Instruction::Mov {
+                        dest: Oprand::Register(Reg::Rdx),
+                        src: Oprand::Value(Val::Int(char::REPLACEMENT_CHARACTER as isize))
+                    },
+                    // Use the replacement character should the integer be a
+                    // surrogate code point (0xD800 to 0xDFFF):
+                    Instruction::Mov { dest: Oprand::Register(Reg::Rbx), src: Oprand::Register(Reg::Rax) },
+                    Instruction::Sub { dest: Oprand::Register(Reg::Rbx), src: Oprand::Value(Val::Int(0xD800)) },
+                    Instruction::Cmp { dest: Oprand::Register(Reg::Rbx), src: Oprand::Value(Val::Int(0x800)) },
+                    Instruction::Cmovb { dest: Oprand::Register(Reg::Rax), src: Oprand::Register(Reg::Rdx) },
+                    // Also when above the maximum character code (as the
+                    // comparison is unsigned, this includes negative integers):
+                    Instruction::Cmp { dest: Oprand::Register(Reg::Rax), src: Oprand::Value(Val::Int(0x10FFFF)) },
+                    Instruction::Cmova { dest: Oprand::Register(Reg::Rax), src: Oprand::Register(Reg::Rdx) },
+                    // Replace the top of the stack with the character code:
+                    Instruction::Mov {
+                        dest: Oprand::Address(Box::new(Oprand::Register(Reg::StackPointer))),
+                        src: Oprand::Register(Reg::Rax)
+                    }
+                ]);
+            }
+
+            // Line markers produce no code but give the line of any literals
+            // that follow:
+            checking::Instruction::MarkLine(line_number) => self.current_line = Some(line_number),
+
+            checking::Instruction::AssertionFailed(line_number) => {
+                if self.options.freestanding {
+                    self.freestanding_assertion_lines.insert(line_number);
+
+                    let message = assertion_failed_message(line_number);
+                    self.add_write_instructions(&assertion_failed_label(line_number), message.len());
+                }
+                else {
+                    self.assertion_failed_used = true;
+
+                    // Load line number (second argument):
+                    self.text_section.push(
+                        Instruction::Mov { dest: Oprand::Register(Reg::SrcIndex), src: Oprand::Value(Val::Int(line_number as isize)) }
+                    );
+                    self.add_printf_call_instructions(&checking::LabelKind::named("assertion_failed"), 0);
+                }
+                self.add_exit_instructions(1);
+            }
+
+            checking::Instruction::ArgCount => {
+                let arg_count = self.label_contents(checking::LabelKind::named("arg_count"));
+
+                self.text_section.extend(vec![
+                    Instruction::Push(arg_count),
+                    Instruction::FpuReset,
+                    // Convert the integer argument count to a Num in place on
+                    // top of the stack:
+                    Instruction::FpuPushInt(Oprand::Address(Box::new(Oprand::Register(Reg::StackPointer)))),
+                    Instruction::FpuPop(Oprand::Address(Box::new(Oprand::Register(Reg::StackPointer))), self.options.num_precision)
+                ]);
+            }
+
+            checking::Instruction::Convert { from, to } =>
+                unreachable!("Checker does not allow conversion from {:?} to {:?}", from, to)
         }
     }
 
     fn construct_output(mut self) -> String {
-        if self.display_char_used {
+        if let Some(comparison) = self.pending_comparison.take() { self.add_comparison_instructions(comparison); }
+
+        if self.division_by_zero_used {
+            // Jumped to from any function performing a checked division (see
+            // `add_divisor_check_instructions`):
+            self.text_section.push(Instruction::Label(checking::LabelKind::named("division_by_zero")));
+
+            if self.options.freestanding { self.add_write_instructions(&checking::LabelKind::named("division_by_zero_message"), DIVISION_BY_ZERO_MESSAGE.len()); }
+            else { self.add_printf_call_instructions(&checking::LabelKind::named("division_by_zero_message"), 0); }
+            self.add_exit_instructions(1);
+
+            self.rodata_section.extend(vec![
+                Instruction::Label(checking::LabelKind::named("division_by_zero_message")),
+                Instruction::DeclareString(format!(r"{}\0", DIVISION_BY_ZERO_MESSAGE.replace('\n', r"\n")))
+            ]);
+        }
+
+        for format in &self.display_formats {
+            if self.display_formats_used.contains(&format.label) {
+                self.rodata_section.extend(vec![
+                    Instruction::Label(format.label.clone()),
+                    Instruction::DeclareString(format.format.clone())
+                ]);
+            }
+        }
+
+        if self.print_char_used {
+            self.rodata_section.extend(vec![
+                Instruction::Label(checking::LabelKind::named("print_char")),
+                Instruction::DeclareString(r"%c\0".to_string())
+            ]);
+        }
+
+        if self.print_bool_used {
+            self.rodata_section.extend(vec![
+                Instruction::Label(checking::LabelKind::named("print_bool")),
+                Instruction::DeclareString(r"%s\0".to_string()),
+                Instruction::Label(checking::LabelKind::named("print_true")),
+                Instruction::DeclareString(r"true\0".to_string()),
+                Instruction::Label(checking::LabelKind::named("print_false")),
+                Instruction::DeclareString(r"false\0".to_string())
+            ]);
+        }
+
+        if self.print_num_used {
             self.rodata_section.extend(vec![
-                Instruction::Label("display_char".to_string()),
-                Instruction::DeclareString(r"Line %u character value: '%c'\n\0".to_string())
+                Instruction::Label(checking::LabelKind::named("print_num")),
+                Instruction::DeclareString(r"%g\0".to_string())
             ]);
         }
 
-        if self.display_bool_used {
+        if self.assertion_failed_used {
             self.rodata_section.extend(vec![
-                Instruction::Label("display_bool".to_string()),
-                Instruction::DeclareString(r"Line %u boolean value: %lld\n\0".to_string())
+                Instruction::Label(checking::LabelKind::named("assertion_failed")),
+                Instruction::DeclareString(r"Line %u assertion failed\n\0".to_string())
             ]);
         }
 
-        if self.display_num_used {
+        for line_number in &self.freestanding_assertion_lines {
             self.rodata_section.extend(vec![
-                Instruction::Label("display_num".to_string()),
-                Instruction::DeclareString(r"Line %u number value: %f\n\0".to_string())
+                Instruction::Label(assertion_failed_label(*line_number)),
+                Instruction::DeclareString(assertion_failed_message(*line_number).replace('\n', r"\n"))
             ]);
         }
 
-        self.text_section.extend(self.rodata_section.into_iter());
+        // Freestanding code only refers to the C library should it display or
+        // print values:
+        if self.options.freestanding {
+            let externs: Vec<Instruction> = ["printf", "exit"].iter()
+                .filter(|name| {
+                    let function = self.external_function(name);
+                    self.text_section.iter().any(|x| matches!(x, Instruction::Call(called) if *called == function))
+                })
+                .map(|name| Instruction::Extern(self.options.target.c_symbol(name)))
+                .collect();
 
-        self.text_section.into_iter().map(|x| x.intel_syntax()).collect::<Vec<String>>().join("")
+            let text_start = self.text_section.iter().position(|x| matches!(x, Instruction::Section(_))).unwrap() + 1;
+            self.text_section.splice(text_start..text_start, externs);
+        }
+
+        self.text_section.extend(self.rodata_section);
+
+        if self.args_used {
+            self.text_section.extend(vec![
+                Instruction::Section("data".to_string()),
+                Instruction::Label(checking::LabelKind::named("arg_count")),
+                Instruction::Declare(Val::Int(0))
+            ]);
+        }
+
+        match self.options.dialect {
+            Dialect::Nasm => self.text_section.into_iter().map(Instruction::intel_syntax).collect(),
+            // The GNU assembler otherwise expects AT&T syntax:
+            Dialect::Gas => std::iter::once(".intel_syntax noprefix\n".to_string())
+                .chain(self.text_section.into_iter().map(Instruction::gas_syntax))
+                .collect()
+        }
     }
 }
 
@@ -315,9 +868,9 @@ impl GenerateElf64 {
         self.text_section.extend(vec![
             Instruction::FpuReset,
             // Load second-to-top of stack onto FPU stack:
-            Instruction::FpuPush(Oprand::AddressDisplaced(Box::new(Oprand::Register(Reg::StackPointer)), BYTES_IN_VALUE as isize)),
+            Instruction::FpuPush(Oprand::AddressDisplaced(Box::new(Oprand::Register(Reg::StackPointer)), BYTES_IN_VALUE as isize), self.options.num_precision),
             // Load top of stack onto FPU stack:
-            Instruction::FpuPush(Oprand::Address(Box::new(Oprand::Register(Reg::StackPointer)))),
+            Instruction::FpuPush(Oprand::Address(Box::new(Oprand::Register(Reg::StackPointer))), self.options.num_precision),
             // Perform the given operation:
             operation,
             // Move stack pointer:
@@ -329,48 +882,333 @@ impl GenerateElf64 {
         self.two_stack_items_to_fpu_stack(operation);
 
         self.text_section.push( // Move result from FPU stack to regular stack:
-            Instruction::FpuPop(Oprand::Address(Box::new(Oprand::Register(Reg::StackPointer)))),
+            Instruction::FpuPop(Oprand::Address(Box::new(Oprand::Register(Reg::StackPointer))), self.options.num_precision),
         );
     }
     
-    fn add_comparison_instructions(&mut self, operations: Vec<Instruction>) {
-        self.two_stack_items_to_fpu_stack(Instruction::FpuCompare);
-       
-        self.text_section.push( // Store the FPU status register in ax:
-            Instruction::FpuStatusReg(Oprand::Register(Reg::Ax))
-        );
-        
-        self.text_section.extend(operations);
-        
+    /// Jump to the division by zero handler should the divisor on top of the
+    /// stack be zero (either positive or negative).
+    fn add_divisor_check_instructions(&mut self) {
+        self.division_by_zero_used = true;
+
+        self.text_section.push(Instruction::Mov {
+            dest: Oprand::Register(Reg::Rax),
+            src: Oprand::Address(Box::new(Oprand::Register(Reg::StackPointer)))
+        });
+
+        // Discard the sign bit so that the zero flag is set for both 0 and -0:
+        self.text_section.push(match self.options.num_precision {
+            Precision::Double => Instruction::Add { dest: Oprand::Register(Reg::Rax), src: Oprand::Register(Reg::Rax) },
+            Precision::Single => Instruction::BitwiseAnd { dest: Oprand::Register(Reg::Rax), src: Oprand::Value(Val::Int(0x7FFFFFFF)) }
+        });
+
+        self.text_section.push(Instruction::Je(checking::LabelKind::named("division_by_zero")));
+    }
+
+    fn add_modulo_instructions(&mut self) {
+        let loop_label = checking::LabelKind::Remainder.label(checking::take_id(&mut self.remainder_label_counter));
+
         self.text_section.extend(vec![
-            // Ensure all bits except the least significant one are clear:
-            Instruction::BitwiseAnd { dest: Oprand::Register(Reg::Rax), src: Oprand::Value(Val::Int(1)) },
-            //  Store result:
-            Instruction::Mov {
-                dest: Oprand::Address(Box::new(Oprand::Register(Reg::StackPointer))),
-                src: Oprand::Register(Reg::Rax)
-            }
+            Instruction::FpuReset,
+            // Load divisor (top of stack) and then dividend onto FPU stack:
+            Instruction::FpuPush(Oprand::Address(Box::new(Oprand::Register(Reg::StackPointer))), self.options.num_precision),
+            Instruction::FpuPush(Oprand::AddressDisplaced(Box::new(Oprand::Register(Reg::StackPointer)), BYTES_IN_VALUE as isize), self.options.num_precision),
+            // Calculate partial remainder (result has the sign of the dividend),
+            // repeating until the C2 status flag indicates it is complete:
+            Instruction::Label(loop_label.clone()),
+            Instruction::FpuRemainder,
+            Instruction::FpuStatusReg(Oprand::Register(Reg::Ax)),
+            Instruction::BitwiseAnd { dest: Oprand::Register(Reg::Rax), src: Oprand::Value(Val::Int(0x400)) },
+            Instruction::Jne(loop_label),
+            // Move stack pointer and store result:
+            Instruction::Add { dest: Oprand::Register(Reg::StackPointer), src: Oprand::Value(Val::Int(BYTES_IN_VALUE as isize)) },
+            Instruction::FpuPop(Oprand::Address(Box::new(Oprand::Register(Reg::StackPointer))), self.options.num_precision)
         ]);
     }
 
-    fn add_return_instructions(&mut self) {
-        self.text_section.extend(vec![
-            // Restore stack pointer:
+    /// Generate the code of an equals, greater than, or less than comparison
+    /// that pushes the resulting Bool.
+    fn add_comparison_instructions(&mut self, comparison: checking::Instruction) {
+        match comparison {
+            checking::Instruction::Equals => {
+                self.text_section.extend(vec![
+                    // Take first value in comparison off the stack:
+                    Instruction::Pop(Oprand::Register(Reg::Rax)),
+                    // Subtract that value by the second top value on stack:
+                    Instruction::Sub {
+                        dest: Oprand::Register(Reg::Rax),
+                        src: Oprand::Address(Box::new(Oprand::Register(Reg::StackPointer)))
+                    },
+                    // Push flags register onto the stack:
+                    Instruction::PushFlags,
+                    // Pop the flags register into rax:
+                    Instruction::Pop(Oprand::Register(Reg::Rax)),
+                    // Extract the value of the zero flag:
+                    Instruction::Shr { dest: Oprand::Register(Reg::Rax), shift_by: 6 },
+                    Instruction::BitwiseAnd { dest: Oprand::Register(Reg::Rax), src: Oprand::Value(Val::Int(1)) },
+                    // Place the value of the zero flag onto the stack:
+                    Instruction::Mov {
+                        dest: Oprand::Address(Box::new(Oprand::Register(Reg::StackPointer))),
+                        src: Oprand::Register(Reg::Rax)
+                    }
+                ]);
+            }
+
+            checking::Instruction::GreaterThan => {
+                self.add_fpu_comparison_instructions(vec![
+                    // Extract the carry flag bit (indicates greater than when set in this instance):
+                    Instruction::Shr { dest: Oprand::Register(Reg::Ax), shift_by: CARRY_FLAG_BIT_OFFSET }
+                ]);
+            }
+
+            checking::Instruction::LessThan => {
+                self.add_fpu_comparison_instructions(vec![
+                    // Create second copy of FPU status word:
+                    Instruction::Mov { dest: Oprand::Register(Reg::Bx), src: Oprand::Register(Reg::Ax) },
+                    // Have carry flag as least significant bit of ax:
+                    Instruction::Shr { dest: Oprand::Register(Reg::Ax), shift_by: CARRY_FLAG_BIT_OFFSET },
+                    // Have zero flag as least significant bit of bx:
+                    Instruction::Shr { dest: Oprand::Register(Reg::Bx), shift_by: ZERO_FLAG_BIT_OFFSET },
+                    // Both carry flag and zero flag being 0 indicates less than:
+                    Instruction::BitwiseOr { dest: Oprand::Register(Reg::Ax), src: Oprand::Register(Reg::Bx) },
+                    Instruction::BitwiseNot(Oprand::Register(Reg::Ax))
+                ]);
+            }
+
+            _ => unreachable!("Not a comparison: {:?}", comparison)
+        }
+    }
+
+    /// Generate the code of an equals, greater than, or less than comparison
+    /// directly followed by a jump to the label with the given ID should the
+    /// result of that comparison be the given Bool value.
+    fn add_comparison_jump_instructions(&mut self, comparison: checking::Instruction, jump_if: bool, id: checking::Id) {
+        let target = label(id);
+
+        if let checking::Instruction::Equals = comparison {
+            // Popping does not alter the flags set by the comparison:
+            self.text_section.extend(vec![
+                Instruction::Pop(Oprand::Register(Reg::Rax)),
+                Instruction::Pop(Oprand::Register(Reg::Rbx)),
+                Instruction::Cmp { dest: Oprand::Register(Reg::Rax), src: Oprand::Register(Reg::Rbx) }
+            ]);
+            self.text_section.push(if jump_if { Instruction::Je(target) } else { Instruction::Jne(target) });
+            return;
+        }
+
+        self.text_section.extend(vec![
+            Instruction::FpuReset,
+            Instruction::FpuPush(Oprand::AddressDisplaced(Box::new(Oprand::Register(Reg::StackPointer)), BYTES_IN_VALUE as isize), self.options.num_precision),
+            Instruction::FpuPush(Oprand::Address(Box::new(Oprand::Register(Reg::StackPointer))), self.options.num_precision),
+            // Both values are removed from the stack before comparing as an add
+            // would overwrite the flags:
+            Instruction::Add { dest: Oprand::Register(Reg::StackPointer), src: Oprand::Value(Val::Int(2 * BYTES_IN_VALUE as isize)) },
+            // Sets the carry and zero flags as an unsigned integer comparison
+            // of the top (second) value against the first would:
+            Instruction::FpuCompareFlags
+        ]);
+
+        // Follows the flags used by `add_fpu_comparison_instructions`, such
+        // that comparisons involving NaN give the same result either way:
+        self.text_section.push(match (comparison, jump_if) {
+            (checking::Instruction::GreaterThan, true) => Instruction::Jb(target),
+            (checking::Instruction::GreaterThan, false) => Instruction::Jae(target),
+            (checking::Instruction::LessThan, true) => Instruction::Ja(target),
+            (checking::Instruction::LessThan, false) => Instruction::Jbe(target),
+            (comparison, _) => unreachable!("Not a comparison: {:?}", comparison)
+        });
+    }
+
+    fn add_fpu_comparison_instructions(&mut self, operations: Vec<Instruction>) {
+        self.two_stack_items_to_fpu_stack(Instruction::FpuCompare);
+       
+        self.text_section.push( // Store the FPU status register in ax:
+            Instruction::FpuStatusReg(Oprand::Register(Reg::Ax))
+        );
+        
+        self.text_section.extend(operations);
+        
+        self.text_section.extend(vec![
+            // Ensure all bits except the least significant one are clear:
+            Instruction::BitwiseAnd { dest: Oprand::Register(Reg::Rax), src: Oprand::Value(Val::Int(1)) },
+            //  Store result:
             Instruction::Mov {
-                dest: Oprand::Register(Reg::StackPointer),
-                src: Oprand::Register(Reg::BasePointer)
-            },
-            // Restore the base pointer of the previous frame:
-            Instruction::Pop(Oprand::Register(Reg::BasePointer)),
-            // Remove parameter values from the stack and return:
-            Instruction::Ret(self.parameter_variable_num * BYTES_IN_VALUE)
+                dest: Oprand::Address(Box::new(Oprand::Register(Reg::StackPointer))),
+                src: Oprand::Register(Reg::Rax)
+            }
         ]);
     }
+
+    /// Pop a Num off the stack and place it in the xmm0 register as a double
+    /// (the first floating-point argument of a printf call).
+    fn add_pop_float_argument_instructions(&mut self) {
+        self.text_section.extend(vec![
+            Instruction::Pop(Oprand::Register(Reg::Rax)),
+            Instruction::Movq {
+                dest: Oprand::Register(Reg::Xmm0),
+                src: Oprand::Register(Reg::Rax)
+            }
+        ]);
+
+        if self.options.num_precision == Precision::Single {
+            self.text_section.push(Instruction::SingleToDouble { dest: Oprand::Register(Reg::Xmm0), src: Oprand::Register(Reg::Xmm0) });
+        }
+    }
+
+    /// Write the message of the given length at the given label to standard
+    /// output by means of a system call (rather than calling printf).
+    fn add_write_instructions(&mut self, message_label: &str, length: usize) {
+        self.text_section.extend(vec![
+            Instruction::Mov { dest: Oprand::Register(Reg::Rax), src: Oprand::Value(Val::Int(SYS_WRITE)) },
+            Instruction::Mov { dest: Oprand::Register(Reg::DestIndex), src: Oprand::Value(Val::Int(STDOUT_FILENO)) },
+            self.label_address(Reg::SrcIndex, message_label.to_string()),
+            Instruction::Mov { dest: Oprand::Register(Reg::Rdx), src: Oprand::Value(Val::Int(length as isize)) },
+            Instruction::Syscall
+        ]);
+    }
+
+    /// Call printf with the format string at the given label as the first
+    /// argument. Any other arguments should already have been placed in the
+    /// appropriate registers.
+    fn add_printf_call_instructions(&mut self, format_label: &str, float_args_count: isize) {
+        self.text_section.extend(vec![
+            // Arithmetic leaves values on the FPU stack (each operation resets
+            // it before use) whereas the C calling convention requires the FPU
+            // stack be empty when calling a function:
+            Instruction::FpuReset,
+            // Load format string (first argument):
+            self.label_address(Reg::DestIndex, format_label.to_string()),
+            // Indicate number of floating-point arguments:
+            Instruction::Mov { dest: Oprand::Register(Reg::Rax), src: Oprand::Value(Val::Int(float_args_count)) },
+            // Preserve stack pointer:
+            Instruction::Mov { dest: Oprand::Register(Reg::Rbx), src: Oprand::Register(Reg::StackPointer) },
+            // Align stack to 16-byte boundary:
+            Instruction::BitwiseAnd { dest: Oprand::Register(Reg::StackPointer), src: Oprand::Value(Val::Int(-16)) },
+            // Call printf function:
+            Instruction::Call(self.external_function("printf")),
+            // Restore stack pointer:
+            Instruction::Mov { dest: Oprand::Register(Reg::StackPointer), src: Oprand::Register(Reg::Rbx) }
+        ]);
+    }
+
+    /// Label of the given Num literal in read-only data, declaring it should it
+    /// not already be declared (i.e. should literals not be pooled or this be
+    /// the first occurrence of the value).
+    fn num_literal_label(&mut self, num_val: f64) -> String {
+        // Keyed by bit pattern rather than value so that 0 and -0 (which are
+        // equal) are kept distinct while NaN (which is not even equal to
+        // itself) can still be pooled:
+        if let Some(label) = self.pooled_num_labels.get(&num_val.to_bits()) { return label.clone(); }
+
+        let label = checking::LabelKind::Literal.label(checking::take_id(&mut self.num_label_counter));
+        if self.options.pool_literals { self.pooled_num_labels.insert(num_val.to_bits(), label.clone()); }
+
+        let declaration = match (self.options.num_precision, self.options.hex_floats) {
+            (Precision::Double, false) => Instruction::Declare(Val::Float(num_val)),
+            (Precision::Double, true) => Instruction::Declare(Val::FloatBits(num_val.to_bits())),
+            (Precision::Single, false) => Instruction::DeclareSingle(Val::Float(num_val)),
+            (Precision::Single, true) => Instruction::DeclareSingle(Val::SingleBits((num_val as f32).to_bits()))
+        };
+
+        if self.options.literal_comments {
+            self.rodata_section.push(Instruction::Comment(match self.current_line {
+                Some(line_number) => format!("Num literal {} (line {})", num_val, line_number),
+                None => format!("Num literal {}", num_val)
+            }));
+        }
+
+        self.rodata_section.extend(vec![
+            Instruction::Label(label.clone()),
+            declaration
+        ]);
+
+        label
+    }
+
+    /// Operand referring to the value stored at the given label.
+    fn label_contents(&self, label: String) -> Oprand {
+        if self.options.position_independent { Oprand::RipRelative(label) }
+        else { Oprand::Address(Box::new(Oprand::Label(label))) }
+    }
+
+    /// Instruction that places the address of the given label in a register.
+    fn label_address(&self, dest: Reg, label: String) -> Instruction {
+        if self.options.position_independent {
+            Instruction::Lea { dest: Oprand::Register(dest), src: Oprand::RipRelative(label) }
+        }
+        else { Instruction::Mov { dest: Oprand::Register(dest), src: Oprand::Label(label) } }
+    }
+
+    /// Target with which to call the given external function.
+    fn external_function(&self, name: &str) -> String {
+        let symbol = self.options.target.c_symbol(name);
+
+        match (self.options.position_independent, self.options.dialect) {
+            (true, Dialect::Nasm) => format!("{} wrt ..plt", symbol),
+            (true, Dialect::Gas) => format!("{}@PLT", symbol),
+            (false, _) => symbol
+        }
+    }
+
+    /// End the program with the given exit status.
+    fn add_exit_instructions(&mut self, status: isize) {
+        if self.options.freestanding {
+            self.text_section.extend(vec![
+                Instruction::Mov { dest: Oprand::Register(Reg::Rax), src: Oprand::Value(Val::Int(SYS_EXIT)) },
+                Instruction::Mov { dest: Oprand::Register(Reg::DestIndex), src: Oprand::Value(Val::Int(status)) },
+                Instruction::Syscall
+            ]);
+        }
+        else {
+            self.text_section.extend(vec![
+                // Exit status (first argument):
+                Instruction::Mov { dest: Oprand::Register(Reg::DestIndex), src: Oprand::Value(Val::Int(status)) },
+                Instruction::BitwiseAnd { dest: Oprand::Register(Reg::StackPointer), src: Oprand::Value(Val::Int(-16)) },
+                // Flushes the output of any previous printf calls:
+                Instruction::Call(self.external_function("exit"))
+            ]);
+        }
+    }
+
+    /// Store the number of command-line arguments the program was run with so
+    /// that it can be pushed by any function (see `checking::Instruction::ArgCount`).
+    /// Must directly follow the prologue of the entry point.
+    fn add_store_arg_count_instructions(&mut self) {
+        let arg_count = self.label_contents(checking::LabelKind::named("arg_count"));
+
+        if self.options.freestanding {
+            // Without a C library the argument count is instead found at the
+            // top of the stack as it was on entry (just above the preserved
+            // base pointer):
+            self.text_section.extend(vec![
+                Instruction::Mov {
+                    dest: Oprand::Register(Reg::Rax),
+                    src: Oprand::AddressDisplaced(Box::new(Oprand::Register(Reg::BasePointer)), BYTES_IN_VALUE as isize)
+                },
+                Instruction::Mov { dest: arg_count, src: Oprand::Register(Reg::Rax) }
+            ]);
+        }
+        else {
+            // Given by the C runtime as the first argument to main (argc):
+            self.text_section.push(Instruction::Mov { dest: arg_count, src: Oprand::Register(Reg::DestIndex) });
+        }
+    }
+
+    fn add_return_instructions(&mut self) {
+        if self.in_entry_point && self.options.freestanding {
+            self.add_exit_instructions(0);
+            return;
+        }
+
+        let epilogue = self.options.calling_convention.epilogue(self.parameter_variable_num, self.in_entry_point);
+        self.text_section.extend(epilogue);
+    }
 }
 
 /// Trait for conversion to Intel or AT&T assembly syntax.
 trait AssemblyDisplay {
     fn intel_syntax(self) -> String;
+    #[allow(dead_code)]
     fn at_and_t_syntax(self) -> String where Self: Sized { unimplemented!() }
 }
 
@@ -378,28 +1216,40 @@ trait AssemblyDisplay {
 enum Instruction {
     Comment(String),
     Section(String),
+    /// Begin the executable section (named `.text.` followed by the given
+    /// label) containing only the function of that label.
+    FunctionSection(String),
     Extern(String),
     Global(String),
     Label(String),
     Declare(Val),
+    /// Declare a single-precision float followed by 4 zero bytes.
+    DeclareSingle(Val),
     DeclareString(String),
     Mov { dest: Oprand, src: Oprand },
+    Lea { dest: Oprand, src: Oprand },
     Movq { dest: Oprand, src: Oprand },
     Add { dest: Oprand, src: Oprand },
     Sub { dest: Oprand, src: Oprand },
     Push(Oprand),
     Pop(Oprand),
-    FpuPush(Oprand),
-    FpuPop(Oprand),
+    FpuPush(Oprand, Precision),
+    FpuPushInt(Oprand),
+    FpuPop(Oprand, Precision),
     FpuStatusReg(Oprand),
     FpuReset,
     FpuCompare,
+    /// Compare the top two values of the FPU stack, setting the flags register.
+    FpuCompareFlags,
     FpuAdd,
     FpuSubtract,
     FpuMultiply,
     FpuDivide,
+    FpuRemainder,
     Ret(usize),
+    Syscall,
     Call(String),
+    CallIndirect(Oprand),
     Jmp(String),
     Shr { dest: Oprand, shift_by: usize },
     BitwiseAnd { dest: Oprand, src: Oprand },
@@ -407,8 +1257,75 @@ enum Instruction {
     BitwiseNot(Oprand),
     PushFlags,
     Cmp { dest: Oprand, src: Oprand },
+    Cmovne { dest: Oprand, src: Oprand },
+    Cmovb { dest: Oprand, src: Oprand },
+    Cmova { dest: Oprand, src: Oprand },
+    /// Convert a float in memory to an integer (truncating towards zero).
+    FloatToInt { dest: Oprand, src: Oprand, precision: Precision },
+    SingleToDouble { dest: Oprand, src: Oprand },
     Je(String),
-    Jne(String)
+    Jne(String),
+    Ja(String),
+    Jae(String),
+    Jb(String),
+    Jbe(String)
+}
+
+impl Instruction {
+    /// Write this instruction in the form expected by the GNU assembler in
+    /// Intel syntax mode (i.e. following `.intel_syntax noprefix`).
+    fn gas_syntax(self) -> String {
+        match self {
+            Instruction::Comment(x) => format!("# {}\n", x),
+            Instruction::Section(x) => match x.as_str() {
+                "text" => ".text\n".to_string(),
+                "rodata" => ".section .rodata\n".to_string(),
+                "data" => ".data\n".to_string(),
+                _ => format!(".section .{}\n", x)
+            }
+            Instruction::FunctionSection(x) => format!(".section .text.{},\"ax\",@progbits\n.p2align 4\n", x),
+            Instruction::Extern(x) => format!(".extern {}\n", x),
+            Instruction::Global(x) => format!(".globl {}\n", x),
+            Instruction::Declare(Val::Float(x)) => format!(".double {:.16}\n", x),
+            Instruction::Declare(x) => format!(".quad {}\n", x.intel_syntax()),
+            Instruction::DeclareSingle(Val::Float(x)) => format!(".float {:.16}\n.long 0\n", x),
+            Instruction::DeclareSingle(x) => format!(".long {}\n.long 0\n", x.intel_syntax()),
+            Instruction::DeclareString(x) => format!(".ascii \"{}\"\n", x),
+            Instruction::Label(x) => format!("{}:\n", x),
+            Instruction::Mov { dest, src } => format!("mov {}, {}\n", dest.gas_syntax(), src.gas_syntax()),
+            Instruction::Lea { dest, src } => format!("lea {}, {}\n", dest.gas_syntax(), src.gas_syntax()),
+            Instruction::Movq { dest, src } => format!("movq {}, {}\n", dest.gas_syntax(), src.gas_syntax()),
+            Instruction::Add { dest, src } => format!("add {}, {}\n", dest.gas_syntax(), src.gas_syntax()),
+            Instruction::Sub { dest, src } => format!("sub {}, {}\n", dest.gas_syntax(), src.gas_syntax()),
+            Instruction::Push(x) => format!("push {}\n", x.gas_sized("qword")),
+            Instruction::Pop(x) => format!("pop {}\n", x.gas_sized("qword")),
+            Instruction::FpuPush(x, precision) => format!("fld {}\n", x.gas_sized(precision.size())),
+            Instruction::FpuPushInt(x) => format!("fild {}\n", x.gas_sized("qword")),
+            Instruction::FpuPop(x, precision) => format!("fst {}\n", x.gas_sized(precision.size())),
+            Instruction::FpuStatusReg(x) => format!("fstsw {}\n", x.gas_syntax()),
+            Instruction::FpuCompareFlags => "fcomi st, st(1)\n".to_string(),
+            // NASM assembles these without operands as the popping forms:
+            Instruction::FpuAdd => "faddp st(1), st\n".to_string(),
+            Instruction::FpuSubtract => "fsubp st(1), st\n".to_string(),
+            Instruction::FpuMultiply => "fmulp st(1), st\n".to_string(),
+            Instruction::FpuDivide => "fdivp st(1), st\n".to_string(),
+            Instruction::CallIndirect(x) => format!("call {}\n", x.gas_syntax()),
+            Instruction::Shr { dest, shift_by } => format!("shr {}, {}\n", dest.gas_syntax(), shift_by),
+            Instruction::BitwiseAnd { dest, src } => format!("and {}, {}\n", dest.gas_sized("qword"), src.gas_syntax()),
+            Instruction::BitwiseOr { dest, src } => format!("or {}, {}\n", dest.gas_sized("qword"), src.gas_syntax()),
+            Instruction::BitwiseNot(x) => format!("not {}\n", x.gas_sized("qword")),
+            Instruction::Cmp { dest, src } => format!("cmp {}, {}\n", dest.gas_syntax(), src.gas_syntax()),
+            Instruction::Cmovne { dest, src } => format!("cmovne {}, {}\n", dest.gas_syntax(), src.gas_syntax()),
+            Instruction::Cmovb { dest, src } => format!("cmovb {}, {}\n", dest.gas_syntax(), src.gas_syntax()),
+            Instruction::Cmova { dest, src } => format!("cmova {}, {}\n", dest.gas_syntax(), src.gas_syntax()),
+            Instruction::FloatToInt { dest, src, precision: Precision::Double } => format!("cvttsd2si {}, {}\n", dest.gas_syntax(), src.gas_sized("qword")),
+            Instruction::FloatToInt { dest, src, precision: Precision::Single } => format!("cvttss2si {}, {}\n", dest.gas_syntax(), src.gas_sized("dword")),
+            Instruction::SingleToDouble { dest, src } => format!("cvtss2sd {}, {}\n", dest.gas_syntax(), src.gas_syntax()),
+            // Instructions without operands (or that only refer to labels) are
+            // written identically:
+            x => x.intel_syntax()
+        }
+    }
 }
 
 impl AssemblyDisplay for Instruction {
@@ -416,28 +1333,38 @@ impl AssemblyDisplay for Instruction {
         match self {
             Instruction::Comment(x) => format!("; {}\n", x),
             Instruction::Section(x) => format!("section .{}\n", x),
+            // NASM would otherwise treat a section of an unrecognised name as
+            // writable data rather than executable code:
+            Instruction::FunctionSection(x) => format!("section .text.{} progbits alloc exec nowrite align=16\n", x),
             Instruction::Extern(x) => format!("extern {}\n", x),
             Instruction::Global(x) => format!("global {}\n", x),
             Instruction::Label(x) => format!("{}:\n", x),
             Instruction::Declare(x) => format!("dq {}\n", x.intel_syntax()),
+            Instruction::DeclareSingle(x) => format!("dd {}, 0\n", x.intel_syntax()),
             Instruction::DeclareString(x) => format!("db `{}`\n", x),
             Instruction::Mov { dest, src } => format!("mov {}, {}\n", dest.intel_syntax(), src.intel_syntax()),
+            Instruction::Lea { dest, src } => format!("lea {}, {}\n", dest.intel_syntax(), src.intel_syntax()),
             Instruction::Movq { dest, src } => format!("movq {}, {}\n", dest.intel_syntax(), src.intel_syntax()),
             Instruction::Add { dest, src } => format!("add {}, {}\n", dest.intel_syntax(), src.intel_syntax()),
             Instruction::Sub { dest, src } => format!("sub {}, {}\n", dest.intel_syntax(), src.intel_syntax()),
             Instruction::Push(x) => format!("push qword {}\n", x.intel_syntax()),
             Instruction::Pop(x) => format!("pop qword {}\n", x.intel_syntax()),
-            Instruction::FpuPush(x) => format!("fld qword {}\n", x.intel_syntax()),
-            Instruction::FpuPop(x) => format!("fst qword {}\n", x.intel_syntax()),
+            Instruction::FpuPush(x, precision) => format!("fld {} {}\n", precision.size(), x.intel_syntax()),
+            Instruction::FpuPushInt(x) => format!("fild qword {}\n", x.intel_syntax()),
+            Instruction::FpuPop(x, precision) => format!("fst {} {}\n", precision.size(), x.intel_syntax()),
             Instruction::FpuStatusReg(x) => format!("fstsw {}\n", x.intel_syntax()),
             Instruction::FpuReset => "finit\n".to_string(),
             Instruction::FpuCompare => "fcom\n".to_string(),
+            Instruction::FpuCompareFlags => "fcomi st1\n".to_string(),
             Instruction::FpuAdd => "fadd\n".to_string(),
             Instruction::FpuSubtract => "fsub\n".to_string(),
             Instruction::FpuMultiply => "fmul\n".to_string(),
             Instruction::FpuDivide => "fdiv\n".to_string(),
+            Instruction::FpuRemainder => "fprem\n".to_string(),
             Instruction::Ret(x) => format!("ret {}\n", x),
+            Instruction::Syscall => "syscall\n".to_string(),
             Instruction::Call(x) => format!("call {}\n", x),
+            Instruction::CallIndirect(x) => format!("call {}\n", x.intel_syntax()),
             Instruction::Jmp(x) => format!("jmp {}\n", x),
             Instruction::Shr { dest, shift_by } => format!("shr {}, {}\n", dest.intel_syntax(), shift_by),
             Instruction::BitwiseAnd { dest, src } => format!("and qword {}, {}\n", dest.intel_syntax(), src.intel_syntax()),
@@ -445,8 +1372,18 @@ impl AssemblyDisplay for Instruction {
             Instruction::BitwiseNot(x) => format!("not qword {}\n", x.intel_syntax()),
             Instruction::PushFlags => "pushfq\n".to_string(),
             Instruction::Cmp { dest, src } => format!("cmp {}, {}\n", dest.intel_syntax(), src.intel_syntax()),
+            Instruction::Cmovne { dest, src } => format!("cmovne {}, {}\n", dest.intel_syntax(), src.intel_syntax()),
+            Instruction::Cmovb { dest, src } => format!("cmovb {}, {}\n", dest.intel_syntax(), src.intel_syntax()),
+            Instruction::Cmova { dest, src } => format!("cmova {}, {}\n", dest.intel_syntax(), src.intel_syntax()),
+            Instruction::FloatToInt { dest, src, precision: Precision::Double } => format!("cvttsd2si {}, qword {}\n", dest.intel_syntax(), src.intel_syntax()),
+            Instruction::FloatToInt { dest, src, precision: Precision::Single } => format!("cvttss2si {}, dword {}\n", dest.intel_syntax(), src.intel_syntax()),
+            Instruction::SingleToDouble { dest, src } => format!("cvtss2sd {}, {}\n", dest.intel_syntax(), src.intel_syntax()),
             Instruction::Je(x) => format!("je {}\n", x),
-            Instruction::Jne(x) => format!("jne {}\n", x)
+            Instruction::Jne(x) => format!("jne {}\n", x),
+            Instruction::Ja(x) => format!("ja {}\n", x),
+            Instruction::Jae(x) => format!("jae {}\n", x),
+            Instruction::Jb(x) => format!("jb {}\n", x),
+            Instruction::Jbe(x) => format!("jbe {}\n", x)
         }
     }
 }
@@ -458,6 +1395,8 @@ enum Oprand {
     Register(Reg),
     Address(Box<Oprand>),
     AddressDisplaced(Box<Oprand>, isize),
+    /// Address of the given label relative to the instruction pointer.
+    RipRelative(String)
 }
 
 impl AssemblyDisplay for Oprand {
@@ -467,19 +1406,50 @@ impl AssemblyDisplay for Oprand {
             Oprand::Value(x) => x.intel_syntax(),
             Oprand::Register(x) => x.intel_syntax(),
             Oprand::Address(x) => format!("[{}]", x.intel_syntax()),
-            Oprand::AddressDisplaced(x, displacement) => format!("[{}{:+}]", x.intel_syntax(), displacement)
+            Oprand::AddressDisplaced(x, displacement) => format!("[{}{:+}]", x.intel_syntax(), displacement),
+            Oprand::RipRelative(x) => format!("[rel {}]", x)
+        }
+    }
+}
+
+impl Oprand {
+    /// Write this operand in the form expected by the GNU assembler in Intel
+    /// syntax mode, where a bare label refers to the memory at that label
+    /// rather than to its address.
+    fn gas_syntax(self) -> String {
+        match self {
+            Oprand::Label(x) => format!("offset {}", x),
+            Oprand::Address(x) => match *x {
+                Oprand::Label(label) => format!("[{}]", label),
+                x => format!("[{}]", x.gas_syntax())
+            },
+            Oprand::RipRelative(x) => format!("[rip + {}]", x),
+            x => x.intel_syntax()
+        }
+    }
+
+    /// Write this operand for the GNU assembler preceded by the given size
+    /// should it be a memory operand (a register or immediate operand is
+    /// never given a size).
+    fn gas_sized(self, size: &str) -> String {
+        match self {
+            Oprand::Address(_) | Oprand::AddressDisplaced(..) | Oprand::RipRelative(_) =>
+                format!("{} ptr {}", size, self.gas_syntax()),
+            x => x.gas_syntax()
         }
     }
 }
 
 #[derive(Clone)]
-enum Val { Int(isize), Float(f64) }
+enum Val { Int(isize), Float(f64), FloatBits(u64), SingleBits(u32) }
 
 impl AssemblyDisplay for Val {
     fn intel_syntax(self) -> String {
         match self {
             Val::Int(x) => x.to_string(),
-            Val::Float(x) => format!("{:.16}", x)
+            Val::Float(x) => format!("{:.16}", x),
+            Val::FloatBits(x) => format!("0x{:016X}", x),
+            Val::SingleBits(x) => format!("0x{:08X}", x)
         }
     }
 }
@@ -504,6 +1474,682 @@ impl AssemblyDisplay for Reg {
     }
 }
 
-fn label(id: usize) -> String { format!("label{}", id) }
+fn label(id: checking::Id) -> String { checking::LabelKind::Jump.label(id) }
+
+#[cfg(test)]
+mod tests {
+    use crate::{ lexing::lexer, parsing, checking, stream::Stream };
+    use std::collections::HashSet;
 
-fn literal_label(counter: usize) -> String { format!("literal{}", counter) }
+    fn quick_compile(inp: &str) -> String {
+        quick_compile_with_options(inp, super::Options::default())
+    }
+
+    fn quick_compile_with_options(inp: &str, options: super::Options) -> String {
+        let checking_options = checking::Options { entry_point: options.entry_point.clone(), ..Default::default() };
+        super::input_with_options(quick_check(inp, checking_options), options)
+    }
+
+    fn quick_check(inp: &str, options: checking::Options) -> Vec<checking::Instruction> {
+        let final_inp = inp.trim().replace("    ", "\t");
+        let tokens = lexer::input(Stream::from_str(&final_inp)).map(Result::unwrap);
+        let stmts = parsing::parser::input(tokens).map(Result::unwrap);
+        checking::checker::input_with_options(stmts, options).unwrap()
+    }
+
+    #[test]
+    fn entry_point() {
+        let asm = quick_compile_with_options("
+main() -> Num
+    return 1
+
+start()
+    display main()", super::Options { entry_point: "start".to_string(), ..Default::default() });
+
+        assert!(asm.contains("global start\n"));
+        assert!(asm.contains("\nstart:\n"));
+        assert!(!asm.contains("global main\n"));
+        assert!(!asm.contains("\nmain:\n"));
+    }
+
+    #[test]
+    fn hex_floats() {
+        use super::AssemblyDisplay;
+
+        assert_eq!(super::Val::FloatBits(1.0f64.to_bits()).intel_syntax(), "0x3FF0000000000000");
+        assert_eq!(super::Val::FloatBits((-0.1f64).to_bits()).intel_syntax(), "0xBFB999999999999A");
+
+        let hex_asm = quick_compile_with_options("main()\n    display 1", super::Options { hex_floats: true, ..Default::default() });
+        assert!(hex_asm.contains("dq 0x3FF0000000000000\n"));
+
+        let decimal_asm = quick_compile("main()\n    display 1");
+        assert!(decimal_asm.contains("dq 1.0000000000000000\n"));
+    }
+
+    #[test]
+    fn position_independent() {
+        let src = "
+double(Num x) -> Num
+    return x * 2
+
+main()
+    Fn(Num) -> Num f = double
+    display f(1.5)
+    print(true)";
+
+        let asm = quick_compile_with_options(src, super::Options { position_independent: true, ..Default::default() });
+        assert!(asm.contains("; Push(Num(2.0))\npush qword [rel till.literal0]\n"));
+        assert!(asm.contains("lea rax, [rel till.func0]\npush qword rax\n"));
+        assert!(asm.contains("lea rdi, [rel till.display_num]\n"));
+        assert!(asm.contains("lea rsi, [rel till.print_false]\nlea rdx, [rel till.print_true]\n"));
+        assert!(asm.contains("call printf wrt ..plt\n"));
+        assert!(!asm.contains("[till.literal"));
+
+        let absolute_asm = quick_compile(src);
+        assert!(absolute_asm.contains("push qword [till.literal0]\n"));
+        assert!(absolute_asm.contains("mov rdi, till.display_num\n"));
+        assert!(!absolute_asm.contains("[rel "));
+    }
+
+    #[test]
+    fn function_sections() {
+        let src = "
+double(Num x) -> Num
+    return x * 2
+
+unused() -> Num
+    return 1
+
+main()
+    display double(1.5)";
+
+        let asm = quick_compile_with_options(src, super::Options { function_sections: true, ..Default::default() });
+
+        for label in &["till.func0", "till.func2", "main"] {
+            let directive = format!("section .text.{} progbits alloc exec nowrite align=16\n", label);
+            assert_eq!(asm.matches(&directive).count(), 1);
+            assert!(asm.contains(&format!("{}{}:\n", directive, label)));
+        }
+
+        assert!(asm.contains("section .rodata\n"));
+        assert!(!quick_compile(src).contains("section .text."));
+    }
+
+    #[test]
+    fn single_precision() {
+        let src = "
+main()
+    Num x = 1.5 + 2
+    display x
+    print(x % 2, cast(x, Char))";
+
+        let asm = quick_compile_with_options(src, super::Options { num_precision: super::Precision::Single, ..Default::default() });
+        assert!(asm.contains("till.literal0:\ndd 1.5000000000000000, 0\n"));
+        assert!(asm.contains("fld dword [rsp+8]\nfld dword [rsp]\nfadd\n"));
+        assert!(asm.contains("fst dword [rsp]\n"));
+        assert!(asm.contains("cvttss2si rax, dword [rsp]\n"));
+        assert!(asm.contains("movq xmm0, rax\ncvtss2sd xmm0, xmm0\n"));
+        assert!(!asm.contains("qword [rsp+8]\n") && !asm.contains("dq "));
+
+        let asm = quick_compile_with_options(src, super::Options { num_precision: super::Precision::Single, hex_floats: true, ..Default::default() });
+        assert!(asm.contains("till.literal0:\ndd 0x3FC00000, 0\n"));
+
+        let asm = quick_compile(src);
+        assert!(asm.contains("till.literal0:\ndq 1.5000000000000000\n"));
+        assert!(asm.contains("fld qword [rsp+8]\nfld qword [rsp]\nfadd\n"));
+        assert!(!asm.contains("dword") && !asm.contains("cvtss2sd"));
+    }
+
+    #[test]
+    fn zero_locals() {
+        let src = "
+no_locals(Num x) -> Num
+    return x
+
+main()
+    Num a
+    if a == 0
+        Bool b
+        display b
+    display no_locals(a)";
+
+        let asm = quick_compile_with_options(src, super::Options { zero_locals: true, ..Default::default() });
+        assert!(asm.contains("main:\npush qword rbp\nmov rbp, rsp\nsub rsp, 16\nmov rax, 0\nmov [rbp-8], rax\nmov [rbp-16], rax\n"));
+
+        // Nothing to zero in a function without local variables:
+        assert!(asm.contains("sub rsp, 0\n; Parameter"));
+
+        assert!(!quick_compile(src).contains("mov [rbp-8], rax\n"));
+    }
+
+    #[test]
+    fn reproducible_output() {
+        let src = "
+fraction(Num x, Num y) -> Num
+    Num result = x / y
+    return result
+
+main()
+    Num i = 0.1
+    while i < 3
+        if i > 1.5
+            display fraction(i, 2.5)
+        println(i, 'c', i > 2)
+        i = i + 0.7
+    display i % 2";
+
+        let first = quick_compile(src);
+        assert_eq!(quick_compile(src), first);
+        assert_eq!(quick_compile_with_options(src, super::Options { hex_floats: true, ..Default::default() }),
+                   quick_compile_with_options(src, super::Options { hex_floats: true, ..Default::default() }));
+    }
+
+    #[test]
+    fn function_sizes() {
+        let instructions = quick_check("
+identity(Num x) -> Num
+    return x
+
+countdown(Num x) -> Num
+    while x > 0
+        display x
+        x = x - 1
+    return x
+
+main()
+    display identity(countdown(3))", checking::Options::default());
+
+        let labels: Vec<String> = instructions.iter().filter_map(|x| match x {
+            checking::Instruction::Function { label, .. } => Some(label.clone()),
+            _ => None
+        }).collect();
+
+        let sizes = super::function_sizes(instructions.clone(), super::Options::default());
+        assert_eq!(sizes.len(), 3);
+        assert!(sizes[&labels[0]] < 15);
+        assert!(sizes[&labels[1]] > 2 * sizes[&labels[0]]);
+
+        // Directives placing functions in sections are not counted:
+        let options = super::Options { function_sections: true, ..Default::default() };
+        assert_eq!(super::function_sizes(instructions, options), sizes);
+    }
+
+    #[test]
+    fn modulo() {
+        let asm = quick_compile("
+main()
+    display 7 % 3
+    display 1 % 2");
+
+        // Dividend loaded last so that it is ST0 when calculating the remainder:
+        assert!(asm.contains("fld qword [rsp]\nfld qword [rsp+8]\ntill.remainder0:\nfprem\nfstsw ax\nand qword rax, 1024\njne till.remainder0\n"));
+        assert!(asm.contains("jne till.remainder1\n"));
+    }
+
+    #[test]
+    fn function_values() {
+        let asm = quick_compile("
+double(Num x) -> Num
+    return x * 2
+
+main()
+    Fn(Num) -> Num f = double
+    display f(3)");
+
+        // Address of the function is stored in the variable and then called
+        // after pushing the argument:
+        assert!(asm.contains("push qword till.func0\n"));
+        assert!(asm.contains("push qword [rbp-8]\n; CallIndirect(1)\npop qword rax\ncall rax\npush qword rax\n"));
+    }
+
+    #[test]
+    fn calling_convention() {
+        let src = "
+add(Num x, Num y) -> Num
+    return x + y
+
+main()
+    display add(1, 2)";
+
+        let default_asm = quick_compile(src);
+        assert!(default_asm.contains("push qword [rbp+16]\n"));
+        assert!(default_asm.contains("push qword [rbp+24]\n"));
+        assert!(default_asm.contains("; CallExpectingValue(\"till.func0\")\ncall till.func0\npush qword rax\n"));
+        assert!(default_asm.contains("ret 16\n"));
+
+        let asm = quick_compile_with_options(src, super::Options {
+            calling_convention: super::CallingConvention { shadow_space: 32 },
+            ..Default::default()
+        });
+
+        // Caller reserves the shadow space, parameters are found beyond it, and
+        // the callee removes it along with the arguments:
+        assert!(asm.contains("; CallExpectingValue(\"till.func0\")\nsub rsp, 32\ncall till.func0\npush qword rax\n"));
+        assert!(asm.contains("push qword [rbp+48]\n"));
+        assert!(asm.contains("push qword [rbp+56]\n"));
+        assert!(!asm.contains("[rbp+16]"));
+        assert!(asm.contains("ret 48\n"));
+
+        // Entry point is not called by till code so removes nothing:
+        assert!(asm.contains("mov rsp, rbp\npop qword rbp\nret 0\n"));
+    }
+
+    #[test]
+    fn many_parameters() {
+        let asm = quick_compile("
+first(Num a, Num b, Num c, Num d, Num e, Num f) -> Num
+    return a
+
+last(Num a, Num b, Num c, Num d, Num e, Num f) -> Num
+    return f
+
+main()
+    display first(1, 2, 3, 4, 5, 6) + last(1, 2, 3, 4, 5, 6)");
+
+        // First argument is pushed first so is furthest from the frame base
+        // pointer while the last argument is nearest:
+        assert!(asm.contains("till.func0:\n"));
+        assert!(asm.contains("push qword [rbp+56]\n; ReturnValue\npop qword rax\nmov rsp, rbp\npop qword rbp\nret 48\n"));
+        assert!(asm.contains("push qword [rbp+16]\n; ReturnValue\npop qword rax\nmov rsp, rbp\npop qword rbp\nret 48\n"));
+    }
+
+    #[test]
+    fn num_to_char() {
+        let asm = super::input(vec![
+            checking::Instruction::Function { label: "main".to_string(), local_variable_count: 0 },
+            checking::Instruction::Push(checking::Value::Num(0x110000 as f64)),
+            checking::Instruction::Convert { from: checking::Type::Num, to: checking::Type::Char },
+            checking::Instruction::Display { value_type: checking::Type::Char, line_number: 1 },
+            checking::Instruction::ReturnVoid
+        ]);
+
+        assert!(asm.contains(concat!(
+            "cvttsd2si rax, qword [rsp]\nmov rdx, 65533\n",
+            "mov rbx, rax\nsub rbx, 55296\ncmp rbx, 2048\ncmovb rax, rdx\n",
+            "cmp rax, 1114111\ncmova rax, rdx\nmov [rsp], rax\n"
+        )));
+    }
+
+    #[test]
+    fn assertions() {
+        let asm = quick_compile("main()\n    assert(1 > 2)");
+
+        assert!(asm.contains("; JumpIfTrue(0)\npop qword rax\ncmp rax, 0\njne till.label0\n"));
+        assert!(asm.contains("mov rsi, 2\nfinit\nmov rdi, till.assertion_failed\n"));
+        assert!(asm.contains("mov rdi, 1\nand qword rsp, -16\ncall exit\n"));
+        assert!(asm.contains("till.assertion_failed:\ndb "));
+        assert!(!quick_compile("main()\n    display 1").contains("till.assertion_failed"));
+    }
+
+    #[test]
+    fn gas_dialect() {
+        assert_eq!("gas".parse(), Ok(super::Dialect::Gas));
+        assert_eq!("nasm".parse(), Ok(super::Dialect::Nasm));
+        assert!("masm".parse::<super::Dialect>().is_err());
+
+        let src = "
+main()
+    display 1.5";
+
+        let asm = quick_compile_with_options(src, super::Options { dialect: super::Dialect::Gas, ..Default::default() });
+        assert!(asm.contains(".text\n.extern printf\n.extern exit\n.globl main\n"));
+        assert!(asm.contains(".section .rodata\n"));
+        assert!(asm.contains("till.literal0:\n.double 1.5000000000000000\n"));
+        assert!(asm.contains("till.display_num:\n.ascii \"Line %u number value: %f\\n\\0\"\n"));
+        assert!(asm.contains("# Push(Num(1.5))\n"));
+        assert!(asm.starts_with(".intel_syntax noprefix\n"));
+        assert!(asm.contains("push qword ptr [till.literal0]\n"));
+        assert!(!asm.contains("section .text") && !asm.contains("global main") && !asm.contains("dq ") && !asm.contains("; "));
+
+        let asm = quick_compile_with_options(src, super::Options { dialect: super::Dialect::Gas, hex_floats: true, ..Default::default() });
+        assert!(asm.contains("till.literal0:\n.quad 0x3FF8000000000000\n"));
+
+        let asm = quick_compile_with_options(src, super::Options { dialect: super::Dialect::Gas, function_sections: true, ..Default::default() });
+        assert!(asm.contains(".section .text.main,\"ax\",@progbits\n.p2align 4\nmain:\n"));
+
+        let asm = quick_compile_with_options(src, super::Options { dialect: super::Dialect::Gas, position_independent: true, ..Default::default() });
+        assert!(asm.contains("push qword ptr [rip + till.literal0]\n"));
+        assert!(asm.contains("call printf@PLT\n"));
+
+        let asm = quick_compile(src);
+        assert!(asm.contains("section .text\nextern printf\nextern exit\nglobal main\n"));
+    }
+
+    #[test]
+    #[ignore]
+    fn gas_output_assembles() {
+        use std::{ env, fs, process::Command };
+
+        let src = "
+square(Num x) -> Num
+    return x * x
+
+main()
+    Num i = 0
+    while i < 10
+        i = i + 1
+        display square(i) / 2 - 1 % 3
+        display 'c'
+        display i == 3";
+
+        let option_sets = [
+            super::Options { dialect: super::Dialect::Gas, ..Default::default() },
+            super::Options { dialect: super::Dialect::Gas, position_independent: true, function_sections: true, ..Default::default() },
+            super::Options { dialect: super::Dialect::Gas, num_precision: super::Precision::Single, check_division: true, ..Default::default() },
+            super::Options { dialect: super::Dialect::Gas, fuse_comparisons: true, freestanding: true, ..Default::default() }
+        ];
+
+        for (i, options) in IntoIterator::into_iter(option_sets).enumerate() {
+            let asm = quick_compile_with_options(src, options);
+
+            let asm_path = env::temp_dir().join(format!("till_gas_test_{}_{}.s", i, std::process::id()));
+            let object_path = asm_path.with_extension("o");
+            fs::write(&asm_path, &asm).unwrap();
+
+            let output = Command::new("as").arg(&asm_path).arg("-o").arg(&object_path).output().unwrap();
+            let _ = fs::remove_file(&asm_path);
+            let _ = fs::remove_file(&object_path);
+
+            assert!(output.status.success(), "as failed on:\n{}\n{}", asm, String::from_utf8_lossy(&output.stderr));
+            assert!(output.stderr.is_empty(), "as warned:\n{}", String::from_utf8_lossy(&output.stderr));
+        }
+    }
+
+    #[test]
+    fn display_formats() {
+        use super::{ Generator, DisplayFormat, ArgumentPassing };
+
+        let function_type = checking::Type::Function(Box::new(checking::FunctionType { params: vec![], ret: None }));
+
+        let mut generator = super::GenerateElf64::new(super::Options::default());
+        generator.display_formats.push(
+            DisplayFormat::new(function_type.clone(), "display_function", r"Line %u function address: %p\n\0", ArgumentPassing::Integer)
+        );
+
+        let asm = generator.execute(vec![
+            checking::Instruction::Function { label: "main".to_string(), local_variable_count: 0 },
+            checking::Instruction::Push(checking::Value::Function("main".to_string())),
+            checking::Instruction::Display { value_type: function_type, line_number: 1 },
+            checking::Instruction::ReturnVoid
+        ]);
+
+        assert!(asm.contains("pop qword rdx\nmov rsi, 1\nfinit\nmov rdi, till.display_function\n"));
+        assert!(asm.contains("till.display_function:\ndb `Line %u function address: %p\\n\\0`\n"));
+        // Only formats that are used are declared:
+        assert!(!asm.contains("till.display_num"));
+    }
+
+    #[test]
+    fn fused_comparisons() {
+        let src = "
+main()
+    Num a = 1
+    Num b = 2
+    if a < b
+        display a
+    while b > a
+        b = b - 1
+    if a == b
+        display b";
+
+        let asm = quick_compile_with_options(src, super::Options { fuse_comparisons: true, ..Default::default() });
+
+        // The Bool result of each comparison is never pushed and then popped:
+        assert!(!asm.contains("fstsw") && !asm.contains("pushfq"));
+        assert!(!asm.contains("cmp rax, 0\n"));
+
+        assert!(asm.contains("; LessThan\n; JumpIfFalse(2)\nfinit\nfld qword [rsp+8]\nfld qword [rsp]\nadd rsp, 16\nfcomi st1\njbe till.label2\n"));
+        assert!(asm.contains("fcomi st1\njb till.label"));
+        assert!(asm.contains("pop qword rax\npop qword rbx\ncmp rax, rbx\njne till.label"));
+
+        // A comparison not followed by a jump still gives a Bool:
+        let asm = quick_compile_with_options("main()\n    display 1 < 2", super::Options { fuse_comparisons: true, ..Default::default() });
+        assert!(asm.contains("fstsw ax\n"));
+    }
+
+    #[test]
+    fn arg_count() {
+        let asm = quick_compile("main()\n    display arg_count()");
+        assert!(asm.contains("main:\npush qword rbp\nmov rbp, rsp\nsub rsp, 0\nmov [till.arg_count], rdi\n"));
+        assert!(asm.contains("push qword [till.arg_count]\nfinit\nfild qword [rsp]\n"));
+        assert!(asm.ends_with("section .data\ntill.arg_count:\ndq 0\n"));
+
+        let asm = quick_compile_with_options("main()\n    display arg_count()", super::Options { freestanding: true, ..Default::default() });
+        assert!(asm.contains("mov rax, [rbp+8]\nmov [till.arg_count], rax\n"));
+
+        // Not stored should the arguments never be referred to:
+        let asm = quick_compile("main()\n    display 1");
+        assert!(!asm.contains("till.arg_count") && !asm.contains("section .data"));
+    }
+
+    #[test]
+    fn freestanding() {
+        let src = "
+double(Num x) -> Num
+    return x * 2
+
+main()
+    assert(double(1) == 2)
+    return";
+
+        let asm = quick_compile_with_options(src, super::Options { freestanding: true, ..Default::default() });
+        let main = &asm[asm.find("main:\n").unwrap()..asm.find("section .rodata").unwrap()];
+
+        assert!(main.ends_with("; ReturnVoid\nmov rax, 60\nmov rdi, 0\nsyscall\n"));
+        assert!(main.contains("mov rax, 60\nmov rdi, 1\nsyscall\n"));
+        assert!(!main.contains("ret ") && !main.contains("call exit"));
+        // Other functions still return to their callers:
+        assert!(asm.contains("pop qword rbp\nret 8\n"));
+
+        let asm = quick_compile(src);
+        assert!(asm.contains("; ReturnVoid\nmov rsp, rbp\npop qword rbp\nret 0\n"));
+        assert!(!asm.contains("syscall"));
+    }
+
+    #[test]
+    fn freestanding_failures() {
+        let src = "
+main()
+    Num x = 4
+    assert(x / 2 == 2)";
+
+        let options = super::Options { freestanding: true, check_division: true, ..Default::default() };
+        let asm = quick_compile_with_options(src, options);
+        assert!(!asm.contains("extern printf\n") && !asm.contains("extern exit\n") && !asm.contains("call printf"));
+        assert!(asm.contains("till.assertion_failed_line3:\ndb `Line 3 assertion failed\\n`\n"));
+        assert!(asm.contains("mov rax, 1\nmov rdi, 1\nmov rsi, till.assertion_failed_line3\nmov rdx, 24\nsyscall\n"));
+        assert!(asm.contains("mov rax, 1\nmov rdi, 1\nmov rsi, till.division_by_zero_message\nmov rdx, 17\nsyscall\n"));
+
+        // The C library is still required to display values:
+        let asm = quick_compile_with_options("main()\n    display 1", super::Options { freestanding: true, ..Default::default() });
+        assert!(asm.contains("extern printf\n") && !asm.contains("extern exit\n"));
+
+        let asm = quick_compile(src);
+        assert!(asm.contains("extern printf\n") && asm.contains("extern exit\n"));
+        assert!(!asm.contains("till.assertion_failed_line3"));
+    }
+
+    #[test]
+    fn char_codes() {
+        let asm = super::input(quick_check("
+main()
+    display '話' > 'a'
+    display cast('\u{10FFFF}', Num)", checking::Options { strict_types: false, ..Default::default() }));
+
+        assert!(asm.contains("push qword 35441\n"));
+        assert!(asm.contains("push qword 97\n"));
+        assert!(asm.contains("push qword 1114111\n"));
+        assert!(!asm.contains("push qword -"));
+        assert!(asm.contains("fild qword [rsp]\n"));
+    }
+
+    #[test]
+    fn nested_returns() {
+        let asm = quick_compile("
+find(Num x) -> Num
+    Num i = 0
+    while i < x
+        Num j = i * 2
+        if j > 4
+            Num k = j
+            return k
+        i = i + 1
+    return 0
+
+main()
+    display find(10)");
+
+        // Space for all three local variables is reserved on entry:
+        assert!(asm.contains("till.func0:\npush qword rbp\nmov rbp, rsp\nsub rsp, 24\n"));
+
+        // Both returns (nested or not) restore the stack pointer to the
+        // beginning of the frame and remove the single parameter:
+        assert_eq!(asm.matches("pop qword rax\nmov rsp, rbp\npop qword rbp\nret 8\n").count(), 2);
+    }
+
+    #[test]
+    fn unique_labels() {
+        let asm = quick_compile("
+func(Num x) -> Num
+    while x > 1.5
+        if x == 3.25
+            return 2.75
+        x = x - 0.5
+    return x * 10
+
+label() -> Num
+    return func(2.5) + func(8.125)
+
+main()
+    Num x = 1
+    while x < 4
+        if label() > x
+            display 3.5
+        x = x + 1");
+
+        let labels: Vec<&str> = asm.lines().filter(|line| line.ends_with(':')).collect();
+        assert!(labels.len() > 15);
+
+        let unique: HashSet<&&str> = labels.iter().collect();
+        assert_eq!(unique.len(), labels.len(), "Duplicate labels in output:\n{}", asm);
+    }
+
+    #[test]
+    fn library() {
+        let src = "
+double(Num x) -> Num
+    return x * 2
+
+close(Num x, Num y) -> Bool
+    return approx_eq(double(x), y, 0.5)";
+
+        let final_ir = quick_check(src, checking::Options { library: true, ..Default::default() });
+        let labels: Vec<String> = final_ir.iter().filter_map(|x| match x {
+            checking::Instruction::Function { label, .. } if checking::LabelKind::Function.is_kind_of(label) => Some(label.clone()),
+            _ => None
+        }).collect();
+        assert_eq!(labels.len(), 2);
+
+        let asm = super::input_with_options(final_ir, super::Options { library: true, ..Default::default() });
+
+        for label in labels {
+            assert!(asm.contains(&format!("global {}\n{}:\n", label, label)));
+        }
+        // Neither the entry point nor built-in functions are made global:
+        assert!(!asm.contains("global main"));
+        assert_eq!(asm.matches("global ").count(), 2);
+        assert!(asm.contains("builtin_approx_eq:\n"));
+    }
+
+    #[test]
+    fn display_num_arguments() {
+        let asm = quick_compile("main()\n    display 1.5 + 2");
+        let display = &asm[asm.find("; Display").unwrap()..asm.find("; ReturnVoid").unwrap()];
+
+        // Passed as a double in xmm0 (with al giving the number of vector
+        // registers used) and not as an integer argument:
+        assert!(display.contains("pop qword rax\nmovq xmm0, rax\n"));
+        assert!(display.contains("mov rsi, 2\nfinit\nmov rdi, till.display_num\nmov rax, 1\n"));
+        assert!(!display.contains("rdx"));
+        assert!(asm.contains(r"Line %u number value: %f\n\0"));
+
+        // The addition leaves its operands on the FPU stack, which is emptied
+        // before printf is called:
+        let addition = &asm[asm.find("; Add").unwrap()..asm.find("; Display").unwrap()];
+        assert!(addition.contains("fadd\n") && addition.contains("fst qword [rsp]\n"));
+        assert!(display.find("finit\n").unwrap() < display.find("call printf\n").unwrap());
+    }
+
+    #[test]
+    fn checked_division() {
+        let src = "
+halve(Num x, Num y) -> Num
+    return x / y
+
+main()
+    display halve(1, 0)
+    display 3 % halve(0, 1)";
+        let options = super::Options { check_division: true, ..Default::default() };
+
+        let asm = quick_compile_with_options(src, options.clone());
+        let check = "mov rax, [rsp]\nadd rax, rax\nje till.division_by_zero\n";
+        assert!(asm.contains(&format!("; Divide\n{}finit\n", check)));
+        assert_eq!(asm.matches(check).count(), 2);
+
+        // A single handler that reports the division and exits unsuccessfully:
+        let handler = &asm[asm.find("till.division_by_zero:\n").unwrap()..asm.find("section .rodata").unwrap()];
+        assert!(handler.contains("mov rdi, till.division_by_zero_message\n"));
+        assert!(handler.ends_with("mov rdi, 1\nand qword rsp, -16\ncall exit\n"));
+        assert_eq!(asm.matches("till.division_by_zero:\n").count(), 1);
+        assert!(asm.contains("till.division_by_zero_message:\n"));
+
+        let single = quick_compile_with_options(src, super::Options { num_precision: super::Precision::Single, ..options });
+        assert!(single.contains("mov rax, [rsp]\nand qword rax, 2147483647\nje till.division_by_zero\n"));
+
+        assert!(!quick_compile(src).contains("till.division_by_zero"));
+    }
+
+    #[test]
+    fn literal_comments() {
+        let src = "main()\n    Num x = 3.14\n    display x * 2";
+        let options = super::Options { literal_comments: true, ..Default::default() };
+
+        let asm = quick_compile_with_options(src, options.clone());
+        assert!(asm.contains("; Num literal 3.14\ntill.literal0:\n"));
+        assert!(asm.contains("; Num literal 2\ntill.literal1:\n"));
+
+        // Lines are included when marked by the checker:
+        let asm = super::input_with_options(quick_check(src, checking::Options { mark_lines: true, ..Default::default() }), options);
+        assert!(asm.contains("; Num literal 3.14 (line 2)\ntill.literal0:\n"));
+        assert!(asm.contains("; Num literal 2 (line 3)\ntill.literal1:\n"));
+
+        assert!(!quick_compile(src).contains("Num literal"));
+    }
+
+    #[test]
+    fn pooled_literals() {
+        let src = "main()\n    display 1.5\n    display 1.5\n    display 1.5\n    display 0\n    display ~0";
+        let options = super::Options { pool_literals: true, ..Default::default() };
+
+        let asm = quick_compile_with_options(src, options.clone());
+        assert_eq!(asm.matches("dq 1.5000000000000000\n").count(), 1);
+        assert_eq!(asm.matches("push qword [till.literal0]\n").count(), 3);
+
+        let asm = quick_compile(src);
+        assert_eq!(asm.matches("dq 1.5000000000000000\n").count(), 3);
+
+        // Values that are equal yet have different bit patterns are kept apart
+        // as are NaNs with different payloads:
+        let final_ir = vec![
+            checking::Instruction::Function { label: "main".to_string(), local_variable_count: 0 },
+            checking::Instruction::Push(checking::Value::Num(0.0)),
+            checking::Instruction::Push(checking::Value::Num(-0.0)),
+            checking::Instruction::Push(checking::Value::Num(f64::NAN)),
+            checking::Instruction::Push(checking::Value::Num(f64::NAN)),
+            checking::Instruction::Push(checking::Value::Num(f64::from_bits(f64::NAN.to_bits() + 1))),
+            checking::Instruction::ReturnVoid
+        ];
+        let asm = super::input_with_options(final_ir, options);
+        assert_eq!(asm.matches("dq ").count(), 4);
+    }
+}
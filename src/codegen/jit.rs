@@ -0,0 +1,482 @@
+//! Generates x86_64 machine code from final IR instructions directly into
+//! executable memory so that till functions can be called from within the
+//! compiler process itself (e.g. when testing or from a REPL) without invoking
+//! an assembler or linker. Only available when the `jit` feature is enabled.
+//!
+//! Code is generated in much the same way as by `genelf64` (every value
+//! occupies 8 bytes of the machine stack, and functions call one another
+//! according to `genelf64::CallingConvention` without shadow space) except that
+//! Num values are always double-precision and arithmetic is performed using SSE
+//! rather than the x87 FPU. Only instructions operating on Num, Bool, and Char
+//! values are supported - display, print, and assertion instructions (which
+//! call into the C library), modulo, conversion of a Num to a Char, and the
+//! command-line argument count are not.
+
+use crate::{ checking::{ self, Instruction, Value as IrValue }, interpreting::Value };
+use std::{ collections::HashMap, fmt, io, ptr };
+
+/// Represents the failures that can occur when generating or calling machine
+/// code.
+#[derive(Debug, PartialEq)]
+pub enum Failure {
+    /// The given instruction cannot be compiled to machine code.
+    Unsupported(Instruction),
+    /// A jump is made to the label with the given ID yet no such label exists.
+    UndefinedLabel(checking::Id),
+    /// A call is made to (or a value refers to) the function with the given
+    /// label yet no such function exists.
+    UndefinedFunction(String),
+    /// The function with the given label takes parameters and so cannot be
+    /// called from outside of till code.
+    HasParameters(String),
+    /// Values of the given type cannot be returned to the caller of a till
+    /// function.
+    UnsupportedReturnType(checking::Type),
+    /// Failed to allocate executable memory to hold the generated code.
+    MemoryAllocation(String)
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Failure::Unsupported(instruction) =>
+                write!(f, "Instruction {:?} cannot be compiled to machine code", instruction),
+
+            Failure::UndefinedLabel(id) =>
+                write!(f, "Jump made to label with ID {} which is never defined", id),
+
+            Failure::UndefinedFunction(label) =>
+                write!(f, "Reference made to function with label '{}' which is never defined", label),
+
+            Failure::HasParameters(label) =>
+                write!(f, "Function with label '{}' takes parameters and so cannot be called directly", label),
+
+            Failure::UnsupportedReturnType(return_type) =>
+                write!(f, "Cannot return a value of type {:?} from compiled code", return_type),
+
+            Failure::MemoryAllocation(e) =>
+                write!(f, "Failed to allocate executable memory: {}", e)
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, Failure>;
+
+/// Generate machine code from the given final IR instructions and then call
+/// the function with the given label, giving the value it returns (should its
+/// return type be given).
+///
+/// # Safety
+///
+/// The instructions must be valid final IR as produced by the checker (for
+/// example, every function must leave the stack as it found it) and the given
+/// return type must be that of the function called.
+pub unsafe fn run(instructions: &[Instruction], label: &str, return_type: Option<&checking::Type>) -> Result<Option<Value>> {
+    compile(instructions)?.call(label, return_type)
+}
+
+/// Generate machine code from the given final IR instructions, placing it in
+/// executable memory.
+pub fn compile(instructions: &[Instruction]) -> Result<Program> {
+    let mut assembler = Assembler::default();
+
+    for instruction in instructions {
+        assembler.handle_instruction(instruction)?;
+    }
+
+    assembler.resolve_fixups()?;
+
+    Ok(Program {
+        memory: ExecutableMemory::new(&assembler.code)?,
+        functions: assembler.functions
+    })
+}
+
+/// Machine code generated from final IR instructions and held in executable
+/// memory.
+pub struct Program {
+    memory: ExecutableMemory,
+    /// Offset into the code at which each function begins and the number of
+    /// parameters it takes, by function label.
+    functions: HashMap<String, (usize, usize)>
+}
+
+impl Program {
+    /// Call the function with the given label (which must not take any
+    /// parameters), giving the value it returns should its return type be
+    /// given.
+    ///
+    /// # Safety
+    ///
+    /// The code must have been generated from valid final IR and the given
+    /// return type must be that of the function called.
+    pub unsafe fn call(&self, label: &str, return_type: Option<&checking::Type>) -> Result<Option<Value>> {
+        let (offset, parameter_count) = *self.functions.get(label).ok_or_else(|| Failure::UndefinedFunction(label.to_string()))?;
+        if parameter_count > 0 { return Err(Failure::HasParameters(label.to_string())); }
+
+        // Check the return type before any code is executed:
+        if let Some(unsupported @ checking::Type::Function(_)) = return_type {
+            return Err(Failure::UnsupportedReturnType(unsupported.clone()));
+        }
+
+        // Results are always returned in rax (the callee-saved registers rbx,
+        // r12-r15, and rbp are never altered without being restored):
+        let function: extern "sysv64" fn() -> u64 = std::mem::transmute(self.memory.address.add(offset));
+        let result = function();
+
+        Ok(return_type.map(|return_type| match return_type {
+            checking::Type::Num => Value::Num(f64::from_bits(result)),
+            checking::Type::Char => Value::Char(char::from_u32(result as u32).unwrap_or(char::REPLACEMENT_CHARACTER)),
+            checking::Type::Bool => Value::Bool(result & 1 != 0),
+            checking::Type::Function(_) => unreachable!()
+        }))
+    }
+}
+
+/// A region of memory mapped as readable and executable (but not writable)
+/// that is unmapped when dropped.
+struct ExecutableMemory {
+    address: *mut u8,
+    size: usize
+}
+
+impl ExecutableMemory {
+    /// Map memory containing a copy of the given code.
+    fn new(code: &[u8]) -> Result<Self> {
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let size = code.len().max(1).div_ceil(page_size) * page_size;
+
+        unsafe {
+            let address = libc::mmap(
+                ptr::null_mut(), size, libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS, -1, 0
+            );
+            if address == libc::MAP_FAILED { return Err(Failure::MemoryAllocation(io::Error::last_os_error().to_string())); }
+
+            let memory = ExecutableMemory { address: address as *mut u8, size };
+            ptr::copy_nonoverlapping(code.as_ptr(), memory.address, code.len());
+
+            if libc::mprotect(address, size, libc::PROT_READ | libc::PROT_EXEC) != 0 {
+                return Err(Failure::MemoryAllocation(io::Error::last_os_error().to_string()));
+            }
+
+            Ok(memory)
+        }
+    }
+}
+
+impl Drop for ExecutableMemory {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.address as *mut libc::c_void, self.size); }
+    }
+}
+
+const BYTES_IN_VALUE: i32 = 8;
+
+/// Number of values placed on the stack between the arguments and the local
+/// variables of a function (the return address and the saved base pointer).
+const SAVED_FRAME_VALUES: i32 = 2;
+
+/// Destination of a 32-bit displacement relative to the end of that
+/// displacement that is only known once all code has been generated.
+enum Target {
+    Label(checking::Id),
+    Function(String)
+}
+
+#[derive(Default)]
+struct Assembler {
+    code: Vec<u8>,
+    /// Offset and number of parameters of each function generated so far.
+    functions: HashMap<String, (usize, usize)>,
+    /// Offset of each jump label generated so far by ID.
+    labels: HashMap<checking::Id, usize>,
+    /// Offsets of displacements yet to be filled in along with their targets.
+    fixups: Vec<(usize, Target)>,
+    current_function: Option<String>,
+    /// Displacement from the base pointer of each variable of the current
+    /// function by ID.
+    variable_displacements: HashMap<checking::Id, i32>,
+    local_variable_num: i32,
+    parameter_variable_num: i32
+}
+
+impl Assembler {
+    fn handle_instruction(&mut self, instruction: &Instruction) -> Result<()> {
+        log::trace!("Generating machine code for instruction: {:?}", instruction);
+
+        match instruction {
+            Instruction::Function { label, local_variable_count } => {
+                self.current_function = Some(label.clone());
+                self.functions.insert(label.clone(), (self.code.len(), 0));
+                self.variable_displacements.clear();
+                self.local_variable_num = 0;
+                self.parameter_variable_num = 0;
+
+                // push rbp; mov rbp, rsp; sub rsp, <locals size>
+                self.emit(&[0x55, 0x48, 0x89, 0xE5, 0x48, 0x81, 0xEC]);
+                self.emit_i32(*local_variable_count as i32 * BYTES_IN_VALUE);
+            }
+
+            Instruction::Parameter(id) => {
+                // Arguments are pushed in order so the last is nearest the
+                // return address:
+                self.variable_displacements.insert(*id, (SAVED_FRAME_VALUES + self.parameter_variable_num) * BYTES_IN_VALUE);
+                self.parameter_variable_num += 1;
+
+                let functions = &mut self.functions;
+                if let Some((_, parameter_count)) = self.current_function.as_ref().and_then(|label| functions.get_mut(label)) {
+                    *parameter_count += 1;
+                }
+            }
+
+            Instruction::Local(id) => {
+                self.variable_displacements.insert(*id, -(self.local_variable_num + 1) * BYTES_IN_VALUE);
+                self.local_variable_num += 1;
+            }
+
+            Instruction::Label(id) => { self.labels.insert(*id, self.code.len()); }
+
+            Instruction::MarkLine(_) => {}
+
+            Instruction::Push(IrValue::Variable(id)) => {
+                // push qword [rbp+<displacement>]
+                self.emit(&[0xFF, 0xB5]);
+                self.emit_i32(self.variable_displacement(*id, instruction)?);
+            }
+
+            Instruction::Push(IrValue::Num(x)) => self.emit_push_u64(x.to_bits()),
+            Instruction::Push(IrValue::Char(x)) => self.emit_push_u64(u32::from(*x) as u64),
+            Instruction::Push(IrValue::Bool(x)) => self.emit_push_u64(*x as u64),
+
+            Instruction::Push(IrValue::Function(label)) => {
+                // lea rax, [rip+<function>]; push rax
+                self.emit(&[0x48, 0x8D, 0x05]);
+                self.emit_fixup(Target::Function(label.clone()));
+                self.emit(&[0x50]);
+            }
+
+            Instruction::Store(id) => {
+                // pop qword [rbp+<displacement>]
+                self.emit(&[0x8F, 0x85]);
+                self.emit_i32(self.variable_displacement(*id, instruction)?);
+            }
+
+            Instruction::StoreAndKeep(id) => {
+                // mov rax, [rsp]; mov [rbp+<displacement>], rax
+                self.emit(&[0x48, 0x8B, 0x04, 0x24, 0x48, 0x89, 0x85]);
+                self.emit_i32(self.variable_displacement(*id, instruction)?);
+            }
+
+            Instruction::CallExpectingVoid(label) => self.emit_call(label),
+
+            Instruction::CallExpectingValue(label) => {
+                self.emit_call(label);
+                self.emit(&[0x50]); // push rax
+            }
+
+            // pop rax; call rax; push rax
+            Instruction::CallIndirect(_) => self.emit(&[0x58, 0xFF, 0xD0, 0x50]),
+
+            Instruction::ReturnValue => {
+                self.emit(&[0x58]); // pop rax
+                self.emit_return();
+            }
+
+            Instruction::ReturnVoid => self.emit_return(),
+
+            Instruction::Jump(id) => {
+                self.emit(&[0xE9]); // jmp <label>
+                self.emit_fixup(Target::Label(*id));
+            }
+
+            Instruction::JumpIfTrue(id) | Instruction::JumpIfFalse(id) => {
+                // pop rax; test rax, rax
+                self.emit(&[0x58, 0x48, 0x85, 0xC0]);
+                // jne/je <label>
+                self.emit(&[0x0F, if let Instruction::JumpIfTrue(_) = instruction { 0x85 } else { 0x84 }]);
+                self.emit_fixup(Target::Label(*id));
+            }
+
+            // addsd, subsd, mulsd, or divsd xmm0, [rsp]:
+            Instruction::Add => self.emit_arithmetic(0x58),
+            Instruction::Subtract => self.emit_arithmetic(0x5C),
+            Instruction::Multiply => self.emit_arithmetic(0x59),
+            Instruction::Divide => self.emit_arithmetic(0x5E),
+
+            Instruction::Equals => {
+                // pop rax; cmp rax, [rsp]; sete al
+                self.emit(&[0x58, 0x48, 0x3B, 0x04, 0x24, 0x0F, 0x94, 0xC0]);
+                // movzx eax, al; mov [rsp], rax
+                self.emit(&[0x0F, 0xB6, 0xC0, 0x48, 0x89, 0x04, 0x24]);
+            }
+
+            // Compare the first value against the second:
+            // movsd xmm0, [rsp+8]; comisd xmm0, [rsp]
+            Instruction::GreaterThan => self.emit_comparison(&[0xF2, 0x0F, 0x10, 0x44, 0x24, 0x08, 0x66, 0x0F, 0x2F, 0x04, 0x24]),
+            // Compare the second value against the first:
+            // movsd xmm0, [rsp]; comisd xmm0, [rsp+8]
+            Instruction::LessThan => self.emit_comparison(&[0xF2, 0x0F, 0x10, 0x04, 0x24, 0x66, 0x0F, 0x2F, 0x44, 0x24, 0x08]),
+
+            // xor qword [rsp], 1
+            Instruction::Not => self.emit(&[0x48, 0x83, 0x34, 0x24, 0x01]),
+
+            // Both character codes and Bool values are stored as integers:
+            Instruction::Convert { from: checking::Type::Char | checking::Type::Bool, to: checking::Type::Num } => {
+                // cvtsi2sd xmm0, qword [rsp]; movsd [rsp], xmm0
+                self.emit(&[0xF2, 0x48, 0x0F, 0x2A, 0x04, 0x24, 0xF2, 0x0F, 0x11, 0x04, 0x24]);
+            }
+
+            Instruction::Display { .. } | Instruction::Print(_) | Instruction::AssertionFailed(_) |
+            Instruction::Modulo | Instruction::Convert { .. } | Instruction::ArgCount =>
+                return Err(Failure::Unsupported(instruction.clone()))
+        }
+
+        Ok(())
+    }
+
+    /// Fill in the displacement of every jump, call, and function reference.
+    fn resolve_fixups(&mut self) -> Result<()> {
+        for (offset, target) in &self.fixups {
+            let destination = match target {
+                Target::Label(id) => *self.labels.get(id).ok_or(Failure::UndefinedLabel(*id))?,
+                Target::Function(label) => self.functions.get(label).ok_or_else(|| Failure::UndefinedFunction(label.clone()))?.0
+            };
+
+            let displacement = destination as i32 - (*offset as i32 + 4);
+            self.code[*offset..*offset + 4].copy_from_slice(&displacement.to_le_bytes());
+        }
+
+        Ok(())
+    }
+
+    fn variable_displacement(&self, id: checking::Id, instruction: &Instruction) -> Result<i32> {
+        // Variables are always introduced before use in valid IR:
+        self.variable_displacements.get(&id).copied().ok_or_else(|| Failure::Unsupported(instruction.clone()))
+    }
+
+    fn emit(&mut self, bytes: &[u8]) {
+        self.code.extend_from_slice(bytes);
+    }
+
+    fn emit_i32(&mut self, value: i32) {
+        self.code.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Emit a 32-bit displacement to be filled in once the given target is
+    /// known.
+    fn emit_fixup(&mut self, target: Target) {
+        self.fixups.push((self.code.len(), target));
+        self.emit_i32(0);
+    }
+
+    fn emit_push_u64(&mut self, value: u64) {
+        // mov rax, <value>; push rax
+        self.emit(&[0x48, 0xB8]);
+        self.code.extend_from_slice(&value.to_le_bytes());
+        self.emit(&[0x50]);
+    }
+
+    fn emit_call(&mut self, label: &str) {
+        self.emit(&[0xE8]); // call <function>
+        self.emit_fixup(Target::Function(label.to_string()));
+    }
+
+    /// Return from the current function, removing its arguments from the stack.
+    fn emit_return(&mut self) {
+        // mov rsp, rbp; pop rbp; ret <arguments size>
+        self.emit(&[0x48, 0x89, 0xEC, 0x5D, 0xC2]);
+        self.code.extend_from_slice(&((self.parameter_variable_num * BYTES_IN_VALUE) as u16).to_le_bytes());
+    }
+
+    /// Perform the SSE operation with the given opcode on the top two values of
+    /// the stack, replacing them with the result.
+    fn emit_arithmetic(&mut self, opcode: u8) {
+        // movsd xmm0, [rsp+8]; <operation> xmm0, [rsp]
+        self.emit(&[0xF2, 0x0F, 0x10, 0x44, 0x24, 0x08, 0xF2, 0x0F, opcode, 0x04, 0x24]);
+        // add rsp, 8; movsd [rsp], xmm0
+        self.emit(&[0x48, 0x83, 0xC4, 0x08, 0xF2, 0x0F, 0x11, 0x04, 0x24]);
+    }
+
+    /// Replace the top two values of the stack with true should the given
+    /// comparison instructions find the value they compare to be above the
+    /// other (false for NaN), otherwise false.
+    fn emit_comparison(&mut self, comparison: &[u8]) {
+        self.emit(comparison);
+        // seta al; movzx eax, al
+        self.emit(&[0x0F, 0x97, 0xC0, 0x0F, 0xB6, 0xC0]);
+        // add rsp, 8; mov [rsp], rax
+        self.emit(&[0x48, 0x83, 0xC4, 0x08, 0x48, 0x89, 0x04, 0x24]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ lexing::lexer, parsing, checking, interpreting::Value, stream::Stream };
+    use super::Failure;
+
+    fn quick_check(inp: &str) -> Vec<checking::Instruction> {
+        let final_inp = inp.trim().replace("    ", "\t");
+        let tokens = lexer::input(Stream::from_str(&final_inp)).map(Result::unwrap);
+        let stmts = parsing::parser::input(tokens).map(Result::unwrap);
+        let options = checking::Options { entry_point: "calc".to_string(), ..Default::default() };
+        checking::checker::input_with_options(stmts, options).unwrap()
+    }
+
+    #[test]
+    fn arithmetic() {
+        let instructions = quick_check("
+calc() -> Num
+    return 2 + 3");
+
+        let result = unsafe { super::run(&instructions, "calc", Some(&checking::Type::Num)) };
+        assert_eq!(result, Ok(Some(Value::Num(5.0))));
+
+        let instructions = quick_check("
+calc() -> Num
+    Num x = 1.5
+    x = x * cast('a', Num) - 0.5 / 2
+    if !(x < 100)
+        return x
+    return 0");
+
+        let result = unsafe { super::run(&instructions, "calc", Some(&checking::Type::Num)) };
+        assert_eq!(result, Ok(Some(Value::Num(145.25))));
+    }
+
+    #[test]
+    fn calls_and_loops() {
+        let instructions = quick_check("
+factorial(Num n) -> Num
+    if n < 2
+        return 1
+    return n * factorial(n - 1)
+
+sum_below(Num n) -> Num
+    Num total = 0
+    while n > 0
+        n = n - 1
+        total = total + n
+    return total
+
+calc() -> Bool
+    Fn(Num) -> Num f = factorial
+    return f(5) / 4 == sum_below(6) + 15");
+
+        let result = unsafe { super::run(&instructions, "calc", Some(&checking::Type::Bool)) };
+        assert_eq!(result, Ok(Some(Value::Bool(true))));
+
+        let program = super::compile(&instructions).unwrap();
+        assert_eq!(unsafe { program.call("till.func0", Some(&checking::Type::Num)) }, Err(Failure::HasParameters("till.func0".to_string())));
+        assert_eq!(unsafe { program.call("missing", None) }, Err(Failure::UndefinedFunction("missing".to_string())));
+    }
+
+    #[test]
+    fn unsupported() {
+        let instructions = quick_check("
+calc()
+    display 1");
+
+        assert_pattern!(super::compile(&instructions).err(), Some(Failure::Unsupported(checking::Instruction::Display { .. })));
+    }
+}
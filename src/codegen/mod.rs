@@ -2,13 +2,14 @@
 //! immediate representation.
 
 pub mod genelf64;
+pub mod target;
+#[cfg(feature = "jit")]
+pub mod jit;
 
 use crate::checking;
 
 /// Generate assembly code from final IR instructions trait.
 trait Generator {
-    const TARGET_NAME: &'static str;
-
     /// Convert a set of given final immediate representation instructions into
     /// assembly code.
     fn execute(mut self, instructions: Vec<checking::Instruction>) -> String where Self: Sized {
@@ -0,0 +1,215 @@
+//! Describes the platform that assembly code is generated for by a target
+//! triple (e.g. `x86_64-linux-gnu`), from which the backend and the options of
+//! that backend are chosen.
+
+use super::genelf64;
+use std::{ fmt, str::FromStr };
+
+/// Instruction set architecture of the target.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Arch {
+    X86_64
+}
+
+/// Operating system of the target.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Os {
+    Linux
+}
+
+/// Environment that programs are run in on the target, determining how they
+/// are linked and end.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Abi {
+    /// Linked with glibc as a position-independent executable (as is the
+    /// default of most Linux distributions).
+    Gnu,
+    /// Linked with musl. Position-dependent code is generated so that programs
+    /// may be linked statically.
+    Musl,
+    /// Linked without any C library, ending the program with a system call.
+    None
+}
+
+/// Backends that code can be generated by.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Backend {
+    /// x86_64 elf64 Intel-syntax assembly (see `genelf64`).
+    Elf64
+}
+
+/// A failure to understand a target triple.
+#[derive(Debug, PartialEq)]
+pub enum Failure {
+    /// The triple does not have between two and four components.
+    InvalidTriple(String),
+    UnknownArch(String),
+    UnknownOs(String),
+    UnknownAbi(String)
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Failure::InvalidTriple(triple) =>
+                write!(f, "Target '{}' is not of the form <arch>-<os>-<abi> (e.g. x86_64-linux-gnu)", triple),
+
+            Failure::UnknownArch(arch) => write!(f, "Unsupported target architecture '{}' (expected x86_64)", arch),
+
+            Failure::UnknownOs(os) => write!(f, "Unsupported target operating system '{}' (expected linux)", os),
+
+            Failure::UnknownAbi(abi) => write!(f, "Unsupported target ABI '{}' (expected gnu, musl, or none)", abi)
+        }
+    }
+}
+
+/// A target platform, parsed from a triple of the form `<arch>-<os>-<abi>`. A
+/// vendor may also be given after the architecture (as in
+/// `x86_64-unknown-linux-gnu`) but is ignored, while the ABI may be omitted (in
+/// which case it is `gnu`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Target {
+    pub arch: Arch,
+    pub os: Os,
+    pub abi: Abi
+}
+
+impl Default for Target {
+    fn default() -> Self {
+        Target { arch: Arch::X86_64, os: Os::Linux, abi: Abi::Gnu }
+    }
+}
+
+impl FromStr for Target {
+    type Err = Failure;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let components: Vec<&str> = s.split('-').collect();
+
+        let (arch, os, abi) = match components.as_slice() {
+            [arch, os] => (*arch, *os, "gnu"),
+            [arch, os, abi] => (*arch, *os, *abi),
+            [arch, _vendor, os, abi] => (*arch, *os, *abi),
+            _ => return Err(Failure::InvalidTriple(s.to_string()))
+        };
+
+        Ok(Target {
+            arch: match arch {
+                "x86_64" | "amd64" => Arch::X86_64,
+                _ => return Err(Failure::UnknownArch(arch.to_string()))
+            },
+            os: match os {
+                "linux" => Os::Linux,
+                _ => return Err(Failure::UnknownOs(os.to_string()))
+            },
+            abi: match abi {
+                "gnu" => Abi::Gnu,
+                "musl" => Abi::Musl,
+                "none" => Abi::None,
+                _ => return Err(Failure::UnknownAbi(abi.to_string()))
+            }
+        })
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let arch = match self.arch { Arch::X86_64 => "x86_64" };
+        let os = match self.os { Os::Linux => "linux" };
+        let abi = match self.abi { Abi::Gnu => "gnu", Abi::Musl => "musl", Abi::None => "none" };
+
+        write!(f, "{}-{}-{}", arch, os, abi)
+    }
+}
+
+impl Target {
+    /// The backend that generates code for this target.
+    pub fn backend(&self) -> Backend {
+        match (self.arch, self.os) {
+            (Arch::X86_64, Os::Linux) => Backend::Elf64
+        }
+    }
+
+    /// Options of the elf64 backend following the conventions of this target.
+    /// Options that do not depend on the target are left as their defaults.
+    pub fn elf64_options(&self) -> genelf64::Options {
+        genelf64::Options {
+            // Till functions call one another without shadow space on Linux:
+            calling_convention: genelf64::CallingConvention { shadow_space: 0 },
+            position_independent: self.abi == Abi::Gnu,
+            freestanding: self.abi == Abi::None,
+            target: *self,
+            ..Default::default()
+        }
+    }
+
+    /// Symbol by which the C library function of the given name is referred
+    /// to on this target. ELF platforms such as Linux use the C name as it is
+    /// (whereas Mach-O, for example, would prefix it with an underscore).
+    pub fn c_symbol(&self, name: &str) -> String {
+        match self.os {
+            Os::Linux => name.to_string()
+        }
+    }
+
+    /// Generate assembly code from the given final IR instructions using the
+    /// backend of this target with the given options (which would usually be
+    /// those of `elf64_options`, possibly with others enabled).
+    pub fn compile(&self, instructions: Vec<crate::checking::Instruction>, elf64_options: genelf64::Options) -> String {
+        match self.backend() {
+            Backend::Elf64 => genelf64::input_with_options(instructions, elf64_options)
+        }
+    }
+}
+
+/// Generate assembly code from the given final IR instructions for the target
+/// described by the given triple (e.g. `x86_64-linux-gnu`).
+pub fn compile(instructions: Vec<crate::checking::Instruction>, triple: &str) -> Result<String, Failure> {
+    let target: Target = triple.parse()?;
+    Ok(target.compile(instructions, target.elf64_options()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checking;
+    use super::{ Target, Arch, Os, Abi, Backend, Failure };
+
+    #[test]
+    fn triples() {
+        let target: Target = "x86_64-linux-gnu".parse().unwrap();
+        assert_eq!(target, Target { arch: Arch::X86_64, os: Os::Linux, abi: Abi::Gnu });
+        assert_eq!(target.backend(), Backend::Elf64);
+        assert!(target.elf64_options().position_independent);
+        assert!(!target.elf64_options().freestanding);
+        assert_eq!(target.to_string(), "x86_64-linux-gnu");
+        assert_eq!(target.c_symbol("printf"), "printf");
+        // The default target follows its own conventions:
+        assert_eq!(Target::default(), target);
+
+        assert_eq!("x86_64-unknown-linux-musl".parse(), Ok(Target { abi: Abi::Musl, ..target }));
+        assert_eq!("amd64-linux".parse(), Ok(target));
+        assert!(!"x86_64-linux-musl".parse::<Target>().unwrap().elf64_options().position_independent);
+
+        let freestanding = "x86_64-linux-none".parse::<Target>().unwrap().elf64_options();
+        assert!(freestanding.freestanding && !freestanding.position_independent);
+
+        assert_eq!("aarch64-linux-gnu".parse::<Target>(), Err(Failure::UnknownArch("aarch64".to_string())));
+        assert_eq!("x86_64-windows-msvc".parse::<Target>(), Err(Failure::UnknownOs("windows".to_string())));
+        assert_eq!("x86_64-linux-android".parse::<Target>(), Err(Failure::UnknownAbi("android".to_string())));
+        assert_eq!("x86_64".parse::<Target>(), Err(Failure::InvalidTriple("x86_64".to_string())));
+    }
+
+    #[test]
+    fn compile() {
+        let final_ir = vec![
+            checking::Instruction::Function { label: "main".to_string(), local_variable_count: 0 },
+            checking::Instruction::ReturnVoid
+        ];
+
+        let asm = super::compile(final_ir.clone(), "x86_64-linux-none").unwrap();
+        assert!(asm.contains("syscall\n"));
+        assert!(asm.starts_with("; Target: x86_64-linux-none\n"));
+        assert!(super::compile(final_ir.clone(), "x86_64-linux").unwrap().starts_with("; Target: x86_64-linux-gnu\n"));
+        assert_eq!(super::compile(final_ir, "riscv64-linux-gnu"), Err(Failure::UnknownArch("riscv64".to_string())));
+    }
+}
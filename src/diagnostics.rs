@@ -0,0 +1,134 @@
+//! Contains a common representation for the failures encountered during each
+//! stage of compilation that allows them to be reported in a structured form
+//! (JSON) to tooling such as editors and language server clients.
+
+use crate::{ stream, lexing, parsing, checking };
+use std::fmt::Write;
+
+/// How serious a given diagnostic is.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity { Error, Warning }
+
+impl Severity {
+    fn as_str(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning"
+        }
+    }
+}
+
+/// A message about the input program produced during compilation. The position
+/// is `None` should the diagnostic not relate to any particular point in the
+/// input (e.g. the main function being undefined).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub pos: Option<stream::Position>,
+    pub code: &'static str
+}
+
+impl Diagnostic {
+    fn error(message: String, pos: Option<&stream::Position>, code: &'static str) -> Self {
+        Diagnostic { severity: Severity::Error, message, pos: pos.cloned(), code }
+    }
+
+    /// Produce a JSON object with the fields `severity`, `message`, `line`,
+    /// `column`, and `code`. Line and column are `null` if the position of
+    /// the diagnostic is not known.
+    pub fn to_json(&self) -> String {
+        let (line, column) = match &self.pos {
+            Some(pos) => (pos.line_number.to_string(), pos.line_position.to_string()),
+            None => ("null".to_string(), "null".to_string())
+        };
+
+        format!(
+            "{{\"severity\":\"{}\",\"message\":{},\"line\":{},\"column\":{},\"code\":{}}}",
+            self.severity.as_str(), json_string(&self.message), line, column, json_string(self.code)
+        )
+    }
+}
+
+impl From<&lexing::Failure> for Diagnostic {
+    fn from(failure: &lexing::Failure) -> Self {
+        Diagnostic::error(failure.to_string(), failure.pos(), failure.code())
+    }
+}
+
+impl From<&parsing::Failure> for Diagnostic {
+    fn from(failure: &parsing::Failure) -> Self {
+        Diagnostic::error(failure.to_string(), failure.pos(), failure.code())
+    }
+}
+
+impl From<&checking::Failure> for Diagnostic {
+    fn from(failure: &checking::Failure) -> Self {
+        Diagnostic::error(failure.to_string(), failure.pos(), failure.code())
+    }
+}
+
+impl From<&checking::Warning> for Diagnostic {
+    fn from(warning: &checking::Warning) -> Self {
+        Diagnostic { severity: Severity::Warning, message: warning.to_string(), pos: Some(warning.pos().clone()), code: warning.code() }
+    }
+}
+
+/// Produce a JSON array containing the given diagnostics.
+pub fn to_json(diagnostics: &[Diagnostic]) -> String {
+    let objects: Vec<String> = diagnostics.iter().map(Diagnostic::to_json).collect();
+    format!("[{}]", objects.join(","))
+}
+
+/// Produce a quoted and escaped JSON string.
+pub(crate) fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+
+    for chr in s.chars() {
+        match chr {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => { write!(escaped, "\\u{:04x}", c as u32).unwrap(); }
+            c => escaped.push(c)
+        }
+    }
+
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ checking, stream::Position };
+
+    #[test]
+    fn type_error_to_json() {
+        let failure = checking::Failure::UnexpectedType {
+            pos: Position { position: 20, line_number: 3, line_position: 7 },
+            expected: checking::Type::Num,
+            encountered: checking::Type::Bool
+        };
+
+        assert_eq!(
+            super::Diagnostic::from(&failure).to_json(),
+            "{\"severity\":\"error\",\"message\":\"Expected type Num yet enountered Bool at character 7 of line 3\",\"line\":3,\"column\":7,\"code\":\"UnexpectedType\"}"
+        );
+
+        assert_eq!(
+            super::to_json(&[super::Diagnostic::from(&checking::Failure::MainUndefined("main".to_string()))]),
+            "[{\"severity\":\"error\",\"message\":\"All till programs are required to have a main function yet such a function could not be found\",\"line\":null,\"column\":null,\"code\":\"MainUndefined\"}]"
+        );
+
+        assert_eq!(super::to_json(&[]), "[]");
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(super::json_string("a \"quote\"\n\\"), "\"a \\\"quote\\\"\\n\\\\\"");
+        assert_eq!(super::json_string("\u{1}"), "\"\\u0001\"");
+    }
+}
@@ -0,0 +1,889 @@
+//! Contains code for directly executing the final immediate representation of a
+//! till program.
+
+use crate::checking::{ self, Instruction };
+use super::Value;
+use std::{ collections::HashMap, io };
+
+/// Execute the given final IR instructions, beginning with the main function.
+pub fn input(instructions: Vec<Instruction>) -> super::Result<()> {
+    Interpreter::new(instructions)?.run()
+}
+
+/// Execute the given final IR instructions, returning a record of every
+/// instruction executed along with the state of the stack after each.
+pub fn trace(instructions: Vec<Instruction>) -> super::Result<Vec<super::ExecStep>> {
+    let mut interp = Interpreter::new(instructions)?;
+    interp.enable_tracing();
+    interp.run()?;
+    Ok(interp.take_trace())
+}
+
+/// The variables of a function call as well as where execution should continue
+/// once that call returns.
+struct Frame {
+    /// Index of the instruction following the call instruction (`None` in the
+    /// case of the main function).
+    return_index: Option<usize>,
+    /// Variables (including parameters) by ID. A value of `None` indicates a
+    /// variable that has been introduced but not yet assigned a value.
+    variables: HashMap<checking::Id, Option<Value>>
+}
+
+/// Maximum number of frames on the call stack (including that of the main
+/// function).
+pub const MAX_CALL_DEPTH: usize = 10_000;
+
+/// Executes final IR instructions one at a time.
+pub struct Interpreter<'a> {
+    instructions: Vec<Instruction>,
+    /// Index of the next instruction to be executed.
+    index: usize,
+    stack: Vec<Value>,
+    /// The call stack. The frame at the end of this vector is that of the
+    /// function currently being executed.
+    frames: Vec<Frame>,
+    /// Indices of the function instructions by function label.
+    function_indices: HashMap<String, usize>,
+    /// Indices of the label instructions by label ID.
+    label_indices: HashMap<checking::Id, usize>,
+    /// Record of executed instructions (`None` if tracing is disabled).
+    trace: Option<Vec<super::ExecStep>>,
+    breakpoints: Vec<super::Breakpoint>,
+    /// Index of the instruction at which execution is currently paused due to
+    /// a breakpoint (ensures the same breakpoint is not hit again on resuming).
+    paused_at: Option<usize>,
+    /// Whether division by zero results in a failure rather than an infinite
+    /// or NaN value.
+    check_division: bool,
+    /// Command-line arguments the program is run with (including the program
+    /// name), which by default is just the label of the entry point so that,
+    /// as for a native program run without arguments, there is one.
+    args: Vec<String>,
+    /// Where the output of display and print instructions is written.
+    output: Box<dyn io::Write + 'a>
+}
+
+impl Interpreter<'static> {
+    pub fn new(instructions: Vec<Instruction>) -> super::Result<Self> {
+        Interpreter::with_entry_point(instructions, "main")
+    }
+
+    /// Prepare to execute the given instructions beginning with the function
+    /// with the given label, writing output to stdout.
+    pub fn with_entry_point(instructions: Vec<Instruction>, entry_point: &str) -> super::Result<Self> {
+        Interpreter::with_output(instructions, entry_point, io::stdout())
+    }
+}
+
+impl<'a> Interpreter<'a> {
+    /// Prepare to execute the given instructions beginning with the function
+    /// with the given label, writing output to the given sink rather than
+    /// stdout (e.g. so that output can be captured in a buffer).
+    pub fn with_output<W: io::Write + 'a>(instructions: Vec<Instruction>, entry_point: &str, output: W) -> super::Result<Self> {
+        let mut function_indices = HashMap::new();
+        let mut label_indices = HashMap::new();
+
+        for (i, instruction) in instructions.iter().enumerate() {
+            match instruction {
+                Instruction::Function { label, .. } => { function_indices.insert(label.clone(), i); }
+                Instruction::Label(id) => { label_indices.insert(*id, i); }
+                _ => {}
+            }
+        }
+
+        let index = *function_indices.get(entry_point).ok_or_else(|| super::Failure::UndefinedLabel(entry_point.to_string()))?;
+
+        Ok(Interpreter {
+            instructions, index,
+            stack: Vec::new(),
+            frames: vec![Frame { return_index: None, variables: HashMap::new() }],
+            function_indices, label_indices,
+            trace: None,
+            breakpoints: Vec::new(),
+            paused_at: None,
+            check_division: false,
+            args: vec![entry_point.to_string()],
+            output: Box::new(output)
+        })
+    }
+
+    /// Begin recording each instruction executed.
+    pub fn enable_tracing(&mut self) {
+        self.trace.get_or_insert_with(Vec::new);
+    }
+
+    /// Fail with `Failure::DivisionByZero` should a divide or modulo instruction
+    /// be executed with a divisor of zero. Otherwise (by default) the result is
+    /// infinite or NaN, as it is for the assembly produced by the compiler.
+    pub fn enable_division_checks(&mut self) {
+        self.check_division = true;
+    }
+
+    /// Set the command-line arguments given to the program (the first of which
+    /// would usually be the name of the program itself).
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.args = args;
+    }
+
+    /// Take all the execution steps recorded so far (tracing must be enabled
+    /// for any steps to be recorded).
+    pub fn take_trace(&mut self) -> Vec<super::ExecStep> {
+        self.trace.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Pause execution at the given point when running via
+    /// `run_until_breakpoint`.
+    pub fn add_breakpoint(&mut self, breakpoint: super::Breakpoint) {
+        self.breakpoints.push(breakpoint);
+    }
+
+    /// The contents of the stack (top of the stack last).
+    pub fn stack(&self) -> &[Value] { &self.stack }
+
+    /// The variables of the function currently being executed that have been
+    /// given a value, by variable ID.
+    pub fn variables(&self) -> HashMap<checking::Id, Value> {
+        self.frames.last()
+            .map(|frame| frame.variables.iter().filter_map(|(id, value)| value.map(|x| (*id, x))).collect())
+            .unwrap_or_default()
+    }
+
+    /// Execute instructions until either a breakpoint is reached (in which case
+    /// that breakpoint is returned) or the main function returns (in which case
+    /// `None` is returned). Calling again after reaching a breakpoint resumes
+    /// execution.
+    pub fn run_until_breakpoint(&mut self) -> super::Result<Option<super::Breakpoint>> {
+        while !self.is_finished() {
+            if self.paused_at != Some(self.index) {
+                if let Some(breakpoint) = self.breakpoint_at(self.index) {
+                    self.paused_at = Some(self.index);
+                    return Ok(Some(breakpoint));
+                }
+            }
+
+            self.paused_at = None;
+            self.step()?;
+        }
+        Ok(None)
+    }
+
+    /// Find the breakpoint (if any) that applies to the instruction at the
+    /// given index.
+    fn breakpoint_at(&self, index: usize) -> Option<super::Breakpoint> {
+        let instruction = self.instructions.get(index)?;
+
+        self.breakpoints.iter().find(|breakpoint| match (breakpoint, instruction) {
+            (super::Breakpoint::Line(line), Instruction::MarkLine(marked_line)) => line == marked_line,
+            (super::Breakpoint::Label(label), Instruction::Function { label: function_label, .. }) => label == function_label,
+            _ => false
+        }).cloned()
+    }
+
+    /// Has the main function returned?
+    pub fn is_finished(&self) -> bool { self.frames.is_empty() }
+
+    /// Execute instructions until the main function returns.
+    pub fn run(&mut self) -> super::Result<()> {
+        while !self.is_finished() { self.step()?; }
+        self.output.flush().map_err(|e| super::Failure::OutputFailed(e.to_string()))
+    }
+
+    /// Execute the next instruction.
+    pub fn step(&mut self) -> super::Result<()> {
+        let index = self.index;
+        let instruction = self.instructions.get(index).cloned().ok_or(super::Failure::EndOfInstructions)?;
+        self.index += 1;
+
+        if self.stack.len() < instruction.stack_effect().consumed.len() {
+            return Err(super::Failure::StackUnderflow);
+        }
+
+        log::trace!("Executing instruction {}: {:?}", index, instruction);
+        self.execute(&instruction)?;
+
+        if let Some(steps) = &mut self.trace {
+            steps.push(super::ExecStep { index, instruction, stack: self.stack.clone() });
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, instruction: &Instruction) -> super::Result<()> {
+        match instruction {
+            Instruction::Function { .. } | Instruction::Label(_) | Instruction::MarkLine(_) => {}
+
+            Instruction::AssertionFailed(line_number) => return Err(super::Failure::AssertionFailed(*line_number)),
+
+            Instruction::Parameter(id) => {
+                // Arguments are pushed on to the stack in order before a call
+                // while parameters are introduced in reverse order:
+                let value = self.pop()?;
+                self.current_frame().variables.insert(*id, Some(value));
+            }
+
+            Instruction::Local(id) => { self.current_frame().variables.entry(*id).or_insert(None); }
+
+            Instruction::Store(id) => {
+                let value = self.pop()?;
+                self.current_frame().variables.insert(*id, Some(value));
+            }
+
+            Instruction::StoreAndKeep(id) => {
+                let value = self.pop()?;
+                self.stack.push(value);
+                self.current_frame().variables.insert(*id, Some(value));
+            }
+
+            Instruction::Push(checking::Value::Variable(id)) => {
+                let value = self.current_frame().variables.get(id).copied().flatten()
+                    .ok_or(super::Failure::UninitialisedVariable(*id))?;
+                self.stack.push(value);
+            }
+            Instruction::Push(checking::Value::Num(x)) => self.stack.push(Value::Num(*x)),
+            Instruction::ArgCount => self.stack.push(Value::Num(self.args.len() as f64)),
+            Instruction::Push(checking::Value::Char(x)) => self.stack.push(Value::Char(*x)),
+            Instruction::Push(checking::Value::Bool(x)) => self.stack.push(Value::Bool(*x)),
+            Instruction::Push(checking::Value::Function(label)) => {
+                let function_index = self.function_index(label)?;
+                self.stack.push(Value::Function(function_index));
+            }
+
+            Instruction::CallExpectingVoid(label) | Instruction::CallExpectingValue(label) => {
+                let function_index = self.function_index(label)?;
+                self.call(function_index)?;
+            }
+
+            Instruction::CallIndirect(_) => {
+                match self.pop()? {
+                    Value::Function(function_index) => self.call(function_index)?,
+                    value => return Err(super::Failure::UnexpectedValue(instruction.clone(), value))
+                }
+            }
+
+            Instruction::ReturnValue => {
+                let value = self.pop()?;
+                self.return_from_function();
+                self.stack.push(value);
+            }
+
+            Instruction::ReturnVoid => self.return_from_function(),
+
+            Instruction::Display { line_number, .. } => {
+                let text = match self.pop()? {
+                    Value::Num(x) => format!("Line {} number value: {}\n", line_number, format_fixed(x)),
+                    Value::Char(x) => format!("Line {} character value: '{}'\n", line_number, x),
+                    Value::Bool(x) => format!("Line {} boolean value: {}\n", line_number, if x { 1 } else { 0 }),
+                    value => return Err(super::Failure::UnexpectedValue(instruction.clone(), value))
+                };
+                self.write_output(&text)?;
+            }
+
+            Instruction::Print(_) => {
+                let text = match self.pop()? {
+                    Value::Num(x) => format_general(x),
+                    Value::Char(x) => x.to_string(),
+                    Value::Bool(x) => x.to_string(),
+                    value => return Err(super::Failure::UnexpectedValue(instruction.clone(), value))
+                };
+                self.write_output(&text)?;
+            }
+
+            Instruction::Jump(id) => self.jump(*id)?,
+
+            Instruction::JumpIfTrue(id) => {
+                if self.pop_bool(instruction)? { self.jump(*id)?; }
+            }
+
+            Instruction::JumpIfFalse(id) => {
+                if !self.pop_bool(instruction)? { self.jump(*id)?; }
+            }
+
+            Instruction::Equals => {
+                let right = self.pop()?;
+                let left = self.pop()?;
+
+                // Numbers are compared by bit pattern, as is done by the
+                // generated code (so 0 and -0 differ yet NaN equals itself):
+                let equal = match (&left, &right) {
+                    (Value::Num(l), Value::Num(r)) => l.to_bits() == r.to_bits(),
+                    _ => left == right
+                };
+                self.stack.push(Value::Bool(equal));
+            }
+
+            Instruction::GreaterThan => self.num_operation(instruction, |l, r| Value::Bool(l > r))?,
+            Instruction::LessThan => self.num_operation(instruction, |l, r| Value::Bool(l < r))?,
+            Instruction::Add => self.num_operation(instruction, |l, r| Value::Num(l + r))?,
+            Instruction::Subtract => self.num_operation(instruction, |l, r| Value::Num(l - r))?,
+            Instruction::Multiply => self.num_operation(instruction, |l, r| Value::Num(l * r))?,
+            Instruction::Divide => {
+                self.check_divisor()?;
+                self.num_operation(instruction, |l, r| Value::Num(l / r))?
+            }
+            // Remainder of f64 takes the sign of the dividend as does fprem:
+            Instruction::Modulo => {
+                self.check_divisor()?;
+                self.num_operation(instruction, |l, r| Value::Num(l % r))?
+            }
+
+            Instruction::Not => {
+                let value = self.pop_bool(instruction)?;
+                self.stack.push(Value::Bool(!value));
+            }
+
+            Instruction::Convert { to: checking::Type::Num, .. } => {
+                match self.pop()? {
+                    Value::Char(x) => self.stack.push(Value::Num(x as u32 as f64)),
+                    Value::Bool(x) => self.stack.push(Value::Num(if x { 1.0 } else { 0.0 })),
+                    value => return Err(super::Failure::UnexpectedValue(instruction.clone(), value))
+                }
+            }
+
+            Instruction::Convert { from: checking::Type::Num, to: checking::Type::Char } => {
+                let value = self.pop_num(instruction)?;
+                self.stack.push(Value::Char(num_to_char(value)));
+            }
+
+            Instruction::Convert { .. } => {
+                let value = self.pop()?;
+                return Err(super::Failure::UnexpectedValue(instruction.clone(), value));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_output(&mut self, text: &str) -> super::Result<()> {
+        self.output.write_all(text.as_bytes()).map_err(|e| super::Failure::OutputFailed(e.to_string()))
+    }
+
+    fn function_index(&self, label: &str) -> super::Result<usize> {
+        self.function_indices.get(label).copied().ok_or_else(|| super::Failure::UndefinedLabel(label.to_string()))
+    }
+
+    /// Begin executing the function at the given instruction index, continuing
+    /// from the current instruction once that function returns.
+    fn call(&mut self, function_index: usize) -> super::Result<()> {
+        if self.frames.len() >= MAX_CALL_DEPTH {
+            return Err(super::Failure::StackOverflow { index: self.index - 1, depth: MAX_CALL_DEPTH });
+        }
+
+        self.frames.push(Frame { return_index: Some(self.index), variables: HashMap::new() });
+        self.index = function_index;
+        Ok(())
+    }
+
+    /// Should division checks be enabled, ensure that the divisor on top of the
+    /// stack is not zero.
+    fn check_divisor(&self) -> super::Result<()> {
+        match self.stack.as_slice() {
+            [.., Value::Num(dividend), Value::Num(divisor)] if self.check_division && *divisor == 0.0 =>
+                Err(super::Failure::DivisionByZero { index: self.index - 1, dividend: *dividend }),
+            _ => Ok(())
+        }
+    }
+
+    fn current_frame(&mut self) -> &mut Frame {
+        self.frames.last_mut().unwrap()
+    }
+
+    /// Discard the frame of the current function and continue execution from
+    /// where that function was called.
+    fn return_from_function(&mut self) {
+        if let Some(Frame { return_index: Some(i), .. }) = self.frames.pop() {
+            self.index = i;
+        }
+    }
+
+    fn jump(&mut self, id: checking::Id) -> super::Result<()> {
+        self.index = *self.label_indices.get(&id)
+            .ok_or_else(|| super::Failure::UndefinedLabel(checking::LabelKind::Jump.label(id)))?;
+        Ok(())
+    }
+
+    fn pop(&mut self) -> super::Result<Value> {
+        self.stack.pop().ok_or(super::Failure::StackUnderflow)
+    }
+
+    fn pop_bool(&mut self, instruction: &Instruction) -> super::Result<bool> {
+        match self.pop()? {
+            Value::Bool(x) => Ok(x),
+            value => Err(super::Failure::UnexpectedValue(instruction.clone(), value))
+        }
+    }
+
+    fn pop_num(&mut self, instruction: &Instruction) -> super::Result<f64> {
+        match self.pop()? {
+            Value::Num(x) => Ok(x),
+            value => Err(super::Failure::UnexpectedValue(instruction.clone(), value))
+        }
+    }
+
+    /// Pop two Num values off the stack and push the result of the given
+    /// operation on them.
+    fn num_operation(&mut self, instruction: &Instruction, operation: fn(f64, f64) -> Value) -> super::Result<()> {
+        let right = self.pop_num(instruction)?;
+        let left = self.pop_num(instruction)?;
+        self.stack.push(operation(left, right));
+        Ok(())
+    }
+}
+
+/// Convert a number to the character with the character code given by its
+/// integer part, giving the replacement character should there be no such
+/// character (see `Instruction::Convert`).
+fn num_to_char(x: f64) -> char {
+    let code = x.trunc();
+
+    // Comparisons are false for NaN:
+    if (0.0..=0x10FFFF as f64).contains(&code) { char::from_u32(code as u32).unwrap_or(char::REPLACEMENT_CHARACTER) }
+    else { char::REPLACEMENT_CHARACTER }
+}
+
+/// Format a number in the same manner as the `%f` printf conversion.
+fn format_fixed(x: f64) -> String {
+    if x.is_finite() { format!("{:.6}", x) }
+    else { format_non_finite(x) }
+}
+
+/// Format a number in the same manner as the `%g` printf conversion (6
+/// significant figures, trailing zeros removed, and scientific notation for
+/// very large or small numbers).
+fn format_general(x: f64) -> String {
+    const PRECISION: i32 = 6;
+
+    if !x.is_finite() { return format_non_finite(x); }
+    if x == 0.0 { return if x.is_sign_negative() { "-0" } else { "0" }.to_string(); }
+
+    // Determine the exponent after rounding to the required precision:
+    let scientific = format!("{:.*e}", (PRECISION - 1) as usize, x);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+
+    if !(-4..PRECISION).contains(&exponent) {
+        format!("{}e{}{:02}", strip_trailing_zeros(mantissa), if exponent < 0 { '-' } else { '+' }, exponent.abs())
+    }
+    else { strip_trailing_zeros(&format!("{:.*}", (PRECISION - 1 - exponent) as usize, x)) }
+}
+
+fn strip_trailing_zeros(s: &str) -> String {
+    if s.contains('.') { s.trim_end_matches('0').trim_end_matches('.').to_string() }
+    else { s.to_string() }
+}
+
+fn format_non_finite(x: f64) -> String {
+    if x.is_nan() { "nan".to_string() }
+    else if x > 0.0 { "inf".to_string() }
+    else { "-inf".to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ lexing::lexer, parsing, checking, stream::Stream };
+    use super::super::{ Value, ExecStep, Failure, Breakpoint, displayed_values };
+
+    fn quick_check(inp: &str) -> Vec<checking::Instruction> {
+        quick_check_with_options(inp, checking::Options::default())
+    }
+
+    fn quick_check_with_options(inp: &str, options: checking::Options) -> Vec<checking::Instruction> {
+        let final_inp = inp.trim().replace("    ", "\t");
+        let tokens = lexer::input(Stream::from_str(&final_inp)).map(Result::unwrap);
+        let stmts = parsing::parser::input(tokens).map(Result::unwrap);
+        checking::checker::input_with_options(stmts, options).unwrap()
+    }
+
+    #[test]
+    fn breakpoints() -> Result<(), Failure> {
+        let instructions = quick_check_with_options("
+double(Num x) -> Num
+    return x * 2
+
+main()
+    Num a = 5
+    Num b = double(a)
+    display a + b", checking::Options { mark_lines: true, ..Default::default() });
+
+        let mut interp = super::Interpreter::new(instructions)?;
+        interp.add_breakpoint(Breakpoint::Line(7));
+        interp.add_breakpoint(Breakpoint::Label("till.func0".to_string()));
+
+        // Paused upon calling double with the argument on the stack:
+        assert_eq!(interp.run_until_breakpoint()?, Some(Breakpoint::Label("till.func0".to_string())));
+        assert_eq!(interp.stack(), &[Value::Num(5.0)]);
+
+        // Paused before the display statement on line 7:
+        assert_eq!(interp.run_until_breakpoint()?, Some(Breakpoint::Line(7)));
+        let mut values: Vec<Value> = interp.variables().values().copied().collect();
+        values.sort_by(|x, y| format!("{}", x).cmp(&format!("{}", y)));
+        assert_eq!(values, vec![Value::Num(10.0), Value::Num(5.0)]);
+        assert!(interp.stack().is_empty());
+
+        assert_eq!(interp.run_until_breakpoint()?, None);
+        assert!(interp.is_finished());
+
+        Ok(())
+    }
+
+    #[test]
+    fn trace_executed_instructions() {
+        let steps = super::trace(quick_check("main()\n    display 1 + 2")).unwrap();
+
+        assert_eq!(steps, vec![
+            ExecStep { index: 0, instruction: checking::Instruction::Function { label: "main".to_string(), local_variable_count: 0 }, stack: vec![] },
+            ExecStep { index: 1, instruction: checking::Instruction::Push(checking::Value::Num(1.0)), stack: vec![Value::Num(1.0)] },
+            ExecStep { index: 2, instruction: checking::Instruction::Push(checking::Value::Num(2.0)), stack: vec![Value::Num(1.0), Value::Num(2.0)] },
+            ExecStep { index: 3, instruction: checking::Instruction::Add, stack: vec![Value::Num(3.0)] },
+            ExecStep { index: 4, instruction: checking::Instruction::Display { value_type: checking::Type::Num, line_number: 2 }, stack: vec![] },
+            ExecStep { index: 5, instruction: checking::Instruction::ReturnVoid, stack: vec![] }
+        ]);
+    }
+
+    #[test]
+    fn function_calls_and_loops() {
+        let steps = super::trace(quick_check("
+factorial(Num n) -> Num
+    if n < 2
+        return 1
+    return n * factorial(n - 1)
+
+main()
+    Num i = 0
+    Num total = 0
+    while i < 4
+        i = i + 1
+        total = total + factorial(i)
+    display total")).unwrap();
+
+        // Value on the stack immediately before being displayed:
+        assert_eq!(displayed_values(&steps), vec![vec![Value::Num(33.0)]]);
+
+        assert_eq!(steps.last().unwrap().instruction, checking::Instruction::ReturnVoid);
+    }
+
+    #[test]
+    fn modulo() {
+        let remainder = |expr: &str| {
+            let steps = super::trace(quick_check(&format!("main()\n    display {}", expr))).unwrap();
+            displayed_values(&steps).remove(0)
+        };
+
+        // Result takes the sign of the dividend:
+        assert_eq!(remainder("(~7) % 3"), vec![Value::Num(-1.0)]);
+        assert_eq!(remainder("7 % (~3)"), vec![Value::Num(1.0)]);
+        assert_eq!(remainder("(~7) % (~3)"), vec![Value::Num(-1.0)]);
+        assert_eq!(remainder("7.5 % 2"), vec![Value::Num(1.5)]);
+    }
+
+    #[test]
+    fn function_values() {
+        let steps = super::trace(quick_check("
+double(Num x) -> Num
+    return x * 2
+
+apply(Fn(Num) -> Num f, Num x) -> Num
+    return f(f(x))
+
+main()
+    display apply(double, 3)")).unwrap();
+
+        assert_eq!(displayed_values(&steps), vec![vec![Value::Num(12.0)]]);
+    }
+
+    #[test]
+    fn many_parameters() {
+        let steps = super::trace(quick_check("
+sum(Num a, Num b, Num c, Num d, Num e) -> Num
+    return a + (b + (c + (d + e)))
+
+digits(Num a, Num b, Num c, Num d, Num e, Num f) -> Num
+    return ((((a * 10 + b) * 10 + c) * 10 + d) * 10 + e) * 10 + f
+
+main()
+    display sum(1, 2, 3, 4, 5)
+    display digits(1, 2, 3, 4, 5, 6)")).unwrap();
+
+        let displayed = displayed_values(&steps);
+
+        // Each argument is received by the corresponding parameter:
+        assert_eq!(displayed, vec![vec![Value::Num(15.0)], vec![Value::Num(123456.0)]]);
+    }
+
+    #[test]
+    fn num_to_char() {
+        let convert = |x: f64| {
+            let steps = super::trace(vec![
+                checking::Instruction::Function { label: "main".to_string(), local_variable_count: 0 },
+                checking::Instruction::Push(checking::Value::Num(x)),
+                checking::Instruction::Convert { from: checking::Type::Num, to: checking::Type::Char },
+                checking::Instruction::Display { value_type: checking::Type::Char, line_number: 1 },
+                checking::Instruction::ReturnVoid
+            ]).unwrap();
+            steps[2].stack.clone()
+        };
+
+        assert_eq!(convert(65.0), vec![Value::Char('A')]);
+        assert_eq!(convert(65.9), vec![Value::Char('A')]);
+        assert_eq!(convert(0x10FFFF as f64), vec![Value::Char('\u{10FFFF}')]);
+
+        // Values that are not Unicode scalar values give the replacement
+        // character:
+        assert_eq!(convert(0x110000 as f64), vec![Value::Char(char::REPLACEMENT_CHARACTER)]);
+        assert_eq!(convert(0xD800 as f64), vec![Value::Char(char::REPLACEMENT_CHARACTER)]);
+        assert_eq!(convert(-1.0), vec![Value::Char(char::REPLACEMENT_CHARACTER)]);
+        assert_eq!(convert(f64::NAN), vec![Value::Char(char::REPLACEMENT_CHARACTER)]);
+    }
+
+    #[test]
+    fn captured_output() {
+        let mut buffer = Vec::new();
+
+        super::Interpreter::with_output(quick_check("
+main()
+    display 1.5
+    print('a', true)"), "main", &mut buffer).unwrap().run().unwrap();
+
+        assert_eq!(String::from_utf8(buffer).unwrap(), "Line 2 number value: 1.500000\na true");
+    }
+
+    #[test]
+    fn arg_count() {
+        let instructions = quick_check("
+main()
+    display arg_count()");
+
+        let mut buffer = Vec::new();
+        {
+            let mut interp = super::Interpreter::with_output(instructions.clone(), "main", &mut buffer).unwrap();
+            interp.set_args(vec!["prog".to_string(), "a".to_string(), "b".to_string()]);
+            interp.run().unwrap();
+        }
+        assert_eq!(String::from_utf8(buffer).unwrap(), "Line 2 number value: 3.000000\n");
+
+        let mut buffer = Vec::new();
+        super::Interpreter::with_output(instructions, "main", &mut buffer).unwrap().run().unwrap();
+        assert_eq!(String::from_utf8(buffer).unwrap(), "Line 2 number value: 1.000000\n");
+    }
+
+    #[test]
+    fn xor() {
+        let steps = super::trace(quick_check("
+main()
+    display true ^^ false
+    display true ^^ true
+    display false ^^ false")).unwrap();
+
+        let displayed = displayed_values(&steps);
+
+        assert_eq!(displayed, vec![vec![Value::Bool(true)], vec![Value::Bool(false)], vec![Value::Bool(false)]]);
+    }
+
+    #[test]
+    fn approx_eq() {
+        let steps = super::trace(quick_check("
+main()
+    display 0.1 + 0.2 == 0.3
+    display approx_eq(0.1 + 0.2, 0.3, 0.000000001)
+    display approx_eq(0.3, 0.1 + 0.2, 0.000000001)
+    display approx_eq(1, 1.5, 0.5)
+    display approx_eq(1, 1.5, 0.6)")).unwrap();
+
+        let displayed = displayed_values(&steps);
+
+        assert_eq!(displayed, vec![
+            vec![Value::Bool(false)], vec![Value::Bool(true)], vec![Value::Bool(true)],
+            vec![Value::Bool(false)], vec![Value::Bool(true)]
+        ]);
+    }
+
+    #[test]
+    fn char_codes() {
+        let steps = super::trace(quick_check_with_options("
+main()
+    display '話' > 'a'
+    display '話' < 'a'
+    display cast('\u{10FFFF}', Num)", checking::Options { strict_types: false, ..Default::default() })).unwrap();
+
+        let displayed = displayed_values(&steps);
+
+        assert_eq!(displayed, vec![vec![Value::Bool(true)], vec![Value::Bool(false)], vec![Value::Num(1114111.0)]]);
+    }
+
+    #[test]
+    fn num_equality() {
+        let steps = super::trace(quick_check("
+main()
+    Num zero = 0
+    Num negative_zero = (~1) * zero
+    Num nan = zero / zero
+    display zero == negative_zero
+    display nan == nan
+    display zero == 0")).unwrap();
+
+        let displayed = displayed_values(&steps);
+
+        assert_eq!(displayed, vec![vec![Value::Bool(false)], vec![Value::Bool(true)], vec![Value::Bool(true)]]);
+    }
+
+    #[test]
+    fn char_arithmetic() {
+        let steps = super::trace(quick_check_with_options("
+main()
+    Char c = 'a'
+    display c + 1
+    display cast(c + 1, Char)", checking::Options { strict_types: false, ..Default::default() })).unwrap();
+
+        let displayed = displayed_values(&steps);
+
+        assert_eq!(displayed, vec![vec![Value::Num(98.0)], vec![Value::Char('b')]]);
+    }
+
+    #[test]
+    fn chained_assignment() {
+        let steps = super::trace(quick_check("
+main()
+    Num x = 1
+    Num y = 2
+    x = y = x + 4
+    Num z = y = y * 2
+    display x
+    display y
+    display z")).unwrap();
+
+        // The value stored by the inner assignment remains on the stack to be
+        // stored by the outer:
+        let keep = steps.iter().position(|step| matches!(step.instruction, checking::Instruction::StoreAndKeep(_))).unwrap();
+        assert_eq!(steps[keep].stack, vec![Value::Num(5.0)]);
+        assert_pattern!(steps[keep + 1].instruction, checking::Instruction::Store(_));
+        assert!(steps[keep + 1].stack.is_empty());
+
+        let displayed = displayed_values(&steps);
+
+        assert_eq!(displayed, vec![vec![Value::Num(5.0)], vec![Value::Num(10.0)], vec![Value::Num(10.0)]]);
+    }
+
+    #[test]
+    fn bool_to_num() {
+        let steps = super::trace(quick_check("
+main()
+    display cast(true, Num) + 1
+    display cast(false, Num)")).unwrap();
+
+        let displayed = displayed_values(&steps);
+
+        assert_eq!(displayed, vec![vec![Value::Num(2.0)], vec![Value::Num(0.0)]]);
+    }
+
+    #[test]
+    fn nested_returns() {
+        let steps = super::trace(quick_check("
+find(Num x) -> Num
+    Num i = 0
+    while i < x
+        Num j = i * 2
+        if j > 4
+            Num k = j
+            return k
+        i = i + 1
+    return 0
+
+main()
+    display find(10)")).unwrap();
+
+        // Only the returned value remains on the stack after the return from
+        // within the if statement nested in the loop:
+        let return_step = steps.iter().position(|step| step.instruction == checking::Instruction::ReturnValue).unwrap();
+        assert_eq!(steps[return_step].stack, vec![Value::Num(6.0)]);
+    }
+
+    #[test]
+    fn failures() {
+        assert_eq!(
+            super::input(vec![checking::Instruction::Function { label: "other".to_string(), local_variable_count: 0 }]).err(),
+            Some(Failure::UndefinedLabel("main".to_string()))
+        );
+
+        assert_eq!(
+            super::input(vec![
+                checking::Instruction::Function { label: "main".to_string(), local_variable_count: 1 },
+                checking::Instruction::Local(0),
+                checking::Instruction::Push(checking::Value::Variable(0))
+            ]),
+            Err(Failure::UninitialisedVariable(0))
+        );
+
+        assert_eq!(
+            super::input(vec![
+                checking::Instruction::Function { label: "main".to_string(), local_variable_count: 0 },
+                checking::Instruction::Add
+            ]),
+            Err(Failure::StackUnderflow)
+        );
+    }
+
+    #[test]
+    fn assertions() {
+        let mut buffer = Vec::new();
+        let result = super::Interpreter::with_output(quick_check("
+main()
+    assert(1 < 2)
+    assert_eq('a', 'a')
+    display 1
+    assert(false)
+    display 2"), "main", &mut buffer).and_then(|mut interp| interp.run());
+
+        assert_eq!(result, Err(Failure::AssertionFailed(5)));
+        assert_eq!(String::from_utf8(buffer).unwrap(), "Line 4 number value: 1.000000\n");
+
+        assert_eq!(super::input(quick_check("main()\n    assert_eq(1, 2)")), Err(Failure::AssertionFailed(2)));
+    }
+
+    #[test]
+    fn runtime_failures() {
+        let instructions = quick_check("
+divide(Num x, Num y) -> Num
+    return x / y
+
+main()
+    display divide(1, 4)
+    display divide(1, 0)");
+
+        // Follows IEEE-754 by default:
+        assert!(super::input(instructions.clone()).is_ok());
+
+        let mut interp = super::Interpreter::new(instructions).unwrap();
+        interp.enable_division_checks();
+        match interp.run() {
+            Err(Failure::DivisionByZero { index, dividend }) => {
+                assert_eq!(interp.instructions[index], checking::Instruction::Divide);
+                assert_eq!(dividend, 1.0);
+            }
+            other => panic!("{:?}", other)
+        }
+
+        assert_eq!(
+            super::input(quick_check("
+forever(Num x) -> Num
+    return forever(x + 1)
+
+main()
+    display forever(0)")),
+            Err(Failure::StackOverflow { index: 5, depth: super::MAX_CALL_DEPTH })
+        );
+    }
+
+    #[test]
+    fn number_formatting() {
+        assert_eq!(super::format_fixed(3.5), "3.500000");
+        assert_eq!(super::format_fixed(f64::NEG_INFINITY), "-inf");
+
+        assert_eq!(super::format_general(1.0), "1");
+        assert_eq!(super::format_general(0.1), "0.1");
+        assert_eq!(super::format_general(123456.0), "123456");
+        assert_eq!(super::format_general(1234567.0), "1.23457e+06");
+        assert_eq!(super::format_general(0.0001), "0.0001");
+        assert_eq!(super::format_general(0.00001), "1e-05");
+        assert_eq!(super::format_general(-2.5), "-2.5");
+        assert_eq!(super::format_general(999999.5), "1e+06");
+    }
+}
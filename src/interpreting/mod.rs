@@ -0,0 +1,127 @@
+//! Contains structures and enumerations used when directly executing the final
+//! immediate representation of a till program (rather than generating assembly
+//! code from it). For the actual interpreter, see submodule `interpreter`.
+
+pub mod interpreter;
+
+use crate::checking;
+use std::fmt;
+
+/// Represents the failures that can occur while interpreting the final IR of a
+/// program. As the IR will have already been checked, these generally indicate
+/// a fault in the compiler itself rather than in the input program.
+#[derive(Debug, PartialEq)]
+pub enum Failure {
+    /// Attempted to pop a value off of an empty stack.
+    StackUnderflow,
+    /// Attempted to call or jump to a label that does not exist.
+    UndefinedLabel(String),
+    /// Attempted to get the value of a variable that either does not exist in
+    /// the current function or has not yet been given a value.
+    UninitialisedVariable(checking::Id),
+    /// Encountered a value of the wrong type for the instruction being executed.
+    UnexpectedValue(checking::Instruction, Value),
+    /// Execution continued past the final instruction (i.e. a function did not
+    /// end with a return instruction).
+    EndOfInstructions,
+    /// Failed to write the output of a display or print instruction.
+    OutputFailed(String),
+    /// The condition of the assertion on the given line was false.
+    AssertionFailed(u64),
+    /// The divide or modulo instruction at the given index was executed with a
+    /// divisor of zero while division checks were enabled.
+    DivisionByZero { index: usize, dividend: f64 },
+    /// The call instruction at the given index would have made the call stack
+    /// deeper than the maximum number of frames (e.g. due to infinite
+    /// recursion).
+    StackOverflow { index: usize, depth: usize }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Failure::StackUnderflow =>
+                write!(f, "Attempted to take a value from the stack while it was empty"),
+
+            Failure::UndefinedLabel(label) =>
+                write!(f, "Attempted to go to label '{}' which does not exist", label),
+
+            Failure::UninitialisedVariable(id) =>
+                write!(f, "Attempted to use the value of variable with ID {} before it was given a value", id),
+
+            Failure::UnexpectedValue(instruction, value) =>
+                write!(f, "Encountered unexpected value {} while executing instruction {:?}", value, instruction),
+
+            Failure::EndOfInstructions =>
+                write!(f, "Reached the end of the instructions without returning from the main function"),
+
+            Failure::OutputFailed(e) =>
+                write!(f, "Failed to write output: {}", e),
+
+            Failure::AssertionFailed(line_number) =>
+                write!(f, "Assertion on line {} failed", line_number),
+
+            Failure::DivisionByZero { index, dividend } =>
+                write!(f, "Attempted to divide {} by zero at instruction {}", dividend, index),
+
+            Failure::StackOverflow { index, depth } =>
+                write!(f, "Exceeded the maximum call depth of {} at instruction {}", depth, index)
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, Failure>;
+
+/// A value held on the stack or in a variable during interpretation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Value {
+    Num(f64),
+    Char(char),
+    Bool(bool),
+    /// The function beginning at the given instruction index.
+    Function(usize)
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Num(x) => write!(f, "{}", x),
+            Value::Char(x) => write!(f, "{:?}", x),
+            Value::Bool(x) => write!(f, "{}", x),
+            Value::Function(index) => write!(f, "function at instruction {}", index)
+        }
+    }
+}
+
+/// A point at which the interpreter should pause execution. A line breakpoint
+/// requires the program to have been checked with the `mark_lines` option
+/// enabled as it is the `MarkLine` instructions that indicate where each line
+/// begins.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Breakpoint {
+    /// Pause before executing the statement that begins on the given line.
+    Line(u64),
+    /// Pause upon entering the function with the given label.
+    Label(String)
+}
+
+/// Record of the execution of a single instruction: the index of that
+/// instruction, the instruction itself, and the contents of the stack
+/// immediately after its execution (top of the stack last).
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExecStep {
+    pub index: usize,
+    pub instruction: checking::Instruction,
+    pub stack: Vec<Value>
+}
+
+/// The stack immediately before each display instruction of the given trace
+/// was executed (i.e. the value displayed by that instruction). Shared by the
+/// tests of passes whose effect is checked by interpreting the result.
+#[cfg(test)]
+pub(crate) fn displayed_values(steps: &[ExecStep]) -> Vec<Vec<Value>> {
+    steps.windows(2)
+        .filter(|pair| matches!(pair[1].instruction, checking::Instruction::Display { .. }))
+        .map(|pair| pair[0].stack.clone())
+        .collect()
+}
@@ -43,11 +43,16 @@ pub enum TokenType {
     WhileKeyword, // while
     TrueKeyword, // true
     DisplayKeyword,
+    PrintKeyword, // print
+    PrintlnKeyword, // println
+    AssertKeyword, // assert
+    AssertEqKeyword, // assert_eq
 
     BracketOpen, // (
     BracketClose, // )
 
     DoubleEquals, // ==
+    DoubleCaret, // ^^
     Arrow, // ->
 
     GreaterThan, // >
@@ -58,6 +63,7 @@ pub enum TokenType {
     Minus, // -
     Slash, // /
     Star, // *
+    Percent, // %
     Caret, // ^
     ExclaimationMark, // !
     Tilde // ~
@@ -73,6 +79,7 @@ pub enum StateKey {
     BeginChar, CharEnd, CharEscapeSequence, CharLiteral,
     Minus,
     Equals,
+    Caret,
     Other
 }
 
@@ -123,7 +130,11 @@ lazy_static::lazy_static! {
                         to: super::Dest::To(StateKey::Equals)
                     },
                     super::Transition {
-                        match_by: super::Match::ByChars(vec!['(', ')', '[', ']', '>', '<', ',', '+', '/', '*', '^', '!', '~']),
+                        match_by: super::Match::ByChar('^'),
+                        to: super::Dest::To(StateKey::Caret)
+                    },
+                    super::Transition {
+                        match_by: super::Match::ByChars(vec!['(', ')', '[', ']', '>', '<', ',', '+', '/', '*', '%', '!', '~']),
                         to: super::Dest::To(StateKey::Other)
                     }
                 ]
@@ -188,6 +199,10 @@ lazy_static::lazy_static! {
                         "false" => TokenType::FalseKeyword,
                         "return" => TokenType::ReturnKeyword,
                         "display" => TokenType::DisplayKeyword,
+                        "print" => TokenType::PrintKeyword,
+                        "println" => TokenType::PrintlnKeyword,
+                        "assert" => TokenType::AssertKeyword,
+                        "assert_eq" => TokenType::AssertEqKeyword,
                         x => TokenType::Identifier(x.to_string())
                     }
                 }),
@@ -221,7 +236,7 @@ lazy_static::lazy_static! {
             StateKey::Newline,
             super::State {
                 parse: super::Parse::ByFunction(&|lexeme| {
-                    let line = lexeme.split('\n').last().unwrap(); // Ignore any empty lines, only consider final populated line.
+                    let line = lexeme.rsplit('\n').next().unwrap(); // Ignore any empty lines, only consider final populated line.
                     TokenType::Newline(line.matches('\t').count())
                 }),
                 transitions: vec![
@@ -331,6 +346,20 @@ lazy_static::lazy_static! {
             }
         );
 
+        /* CARET */
+
+        states.insert(
+            StateKey::Caret,
+            super::State {
+                parse: super::Parse::To(TokenType::Caret),
+                transitions: vec![
+                    super::Transition {
+                        match_by: super::Match::ByChar('^'), // Lexeme will be: ^^
+                        to: super::Dest::To(StateKey::Other)
+                    }
+                ]
+            }
+        );
 
         /* OTHER TOKENS */
 
@@ -341,6 +370,7 @@ lazy_static::lazy_static! {
                     match lexeme {
                         "->" => TokenType::Arrow,
                         "==" => TokenType::DoubleEquals,
+                        "^^" => TokenType::DoubleCaret,
 
                         "(" => TokenType::BracketOpen,
                         ")" => TokenType::BracketClose,
@@ -350,7 +380,7 @@ lazy_static::lazy_static! {
                         "+" => TokenType::Plus,
                         "/" => TokenType::Slash,
                         "*" => TokenType::Star,
-                        "^" => TokenType::Caret,
+                        "%" => TokenType::Percent,
                         "!" => TokenType::ExclaimationMark,
                         "~" => TokenType::Tilde,
                         _ => panic!()
@@ -368,7 +398,7 @@ lazy_static::lazy_static! {
     };
 }
 
-fn match_digit(c: &char) -> bool { c.is_digit(10) }
+fn match_digit(c: &char) -> bool { c.is_ascii_digit() }
 
 fn match_alphanumeric_or_underscore(c: &char) -> bool { c.is_ascii_alphanumeric() || *c == '_' }
 
@@ -420,6 +450,23 @@ mod tests {
         }
     }
 
+    #[test]
+    fn tokens_to_json() {
+        let tokens: Vec<Token> = input(Stream::from_str("x = 'a'\ndisplay x")).map(Result::unwrap).collect();
+
+        assert_eq!(
+            super::super::tokens_to_json(&tokens[..3]),
+            "[{\"type\":\"Identifier\",\"lexeme\":\"x\",\"position\":1,\"line\":1,\"column\":1},\
+{\"type\":\"Equals\",\"lexeme\":\"=\",\"position\":3,\"line\":1,\"column\":3},\
+{\"type\":\"CharLiteral\",\"lexeme\":\"'a'\",\"position\":7,\"line\":1,\"column\":7}]"
+        );
+
+        assert_eq!(
+            tokens[3].to_json(),
+            "{\"type\":\"Newline\",\"lexeme\":\"\\n\",\"position\":8,\"line\":2,\"column\":0}"
+        );
+    }
+
     #[test]
     fn ignored_characters() {
         input(Stream::from_str("  5 6.2   "))
@@ -493,9 +540,18 @@ mod tests {
         .assert_next(TokenType::DoubleEquals);
     }
 
+    #[test]
+    fn caret_and_double_caret() {
+        input(Stream::from_str("^ ^^ ^^^"))
+        .assert_next(TokenType::Caret)
+        .assert_next(TokenType::DoubleCaret)
+        .assert_next(TokenType::DoubleCaret)
+        .assert_next(TokenType::Caret);
+    }
+
     #[test]
     fn other_tokens() {
-        input(Stream::from_str("() > < , + / * ^ ! ~"))
+        input(Stream::from_str("() > < , + / * % ^ ! ~"))
         .assert_next(TokenType::BracketOpen).assert_next(TokenType::BracketClose)
         .assert_next(TokenType::GreaterThan)
         .assert_next(TokenType::LessThan)
@@ -503,6 +559,7 @@ mod tests {
         .assert_next(TokenType::Plus)
         .assert_next(TokenType::Slash)
         .assert_next(TokenType::Star)
+        .assert_next(TokenType::Percent)
         .assert_next(TokenType::Caret)
         .assert_next(TokenType::ExclaimationMark)
         .assert_next(TokenType::Tilde);
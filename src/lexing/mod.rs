@@ -3,7 +3,7 @@
 
 pub mod lexer;
 
-use crate::stream;
+use crate::{ stream, diagnostics };
 use std::{ fmt, hash::Hash, collections::HashMap };
 
 /// Represents a token holding a token type and a lexeme.
@@ -13,6 +13,27 @@ pub struct GenericToken<TokenType> {
     pub lexeme: Lexeme
 }
 
+impl<TokenType: fmt::Debug> GenericToken<TokenType> {
+    /// Produce a JSON object with the fields `type` (the name of the token type
+    /// without any associated data), `lexeme`, `position`, `line`, and `column`.
+    pub fn to_json(&self) -> String {
+        let tok_type = format!("{:?}", self.tok_type);
+        let type_name = tok_type.split('(').next().unwrap_or(&tok_type);
+
+        format!(
+            "{{\"type\":{},\"lexeme\":{},\"position\":{},\"line\":{},\"column\":{}}}",
+            diagnostics::json_string(type_name), diagnostics::json_string(&self.lexeme.text),
+            self.lexeme.pos.position, self.lexeme.pos.line_number, self.lexeme.pos.line_position
+        )
+    }
+}
+
+/// Produce a JSON array containing the given tokens.
+pub fn tokens_to_json<TokenType: fmt::Debug>(tokens: &[GenericToken<TokenType>]) -> String {
+    let objects: Vec<String> = tokens.iter().map(GenericToken::to_json).collect();
+    format!("[{}]", objects.join(","))
+}
+
 /// Holds the raw lexeme string, as well as the position in the input stream the
 /// lexeme is from.
 #[derive(Debug, PartialEq)]
@@ -45,6 +66,23 @@ impl fmt::Display for Failure {
     }
 }
 
+impl Failure {
+    /// Position in the input stream at which the failure occurred.
+    pub fn pos(&self) -> Option<&stream::Position> {
+        match self {
+            Failure::UnexpectedChar(_, lexeme) | Failure::UnexpectedEof(lexeme) => Some(&lexeme.pos)
+        }
+    }
+
+    /// Short name identifying the kind of failure.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Failure::UnexpectedChar(_, _) => "UnexpectedChar",
+            Failure::UnexpectedEof(_) => "UnexpectedEof"
+        }
+    }
+}
+
 type Result<T> = std::result::Result<T, Failure>;
 
 /// Iterator that yields tokens.
@@ -129,7 +167,7 @@ where StateKey: Copy + fmt::Debug {
 
 fn attempt_parse_lexeme_to_token<TokenType, StateKey>(lexeme: Lexeme, next_chr: Option<char>, final_state: &State<TokenType, StateKey>) -> Result<GenericToken<TokenType>>
 where TokenType: fmt::Debug + Clone {
-    match final_state.parse.lexeme_string_to_token_type::<StateKey>(&lexeme.text) {
+    match final_state.parse.lexeme_string_to_token_type(&lexeme.text) {
         Some(tok_type) => {
             log::info!("Lexeme {} parsed to token type: {:?}", lexeme, tok_type);
             Ok(GenericToken { tok_type, lexeme })
@@ -193,7 +231,7 @@ enum Parse<'a, TokenType> {
 
 impl<TokenType> Parse<'_, TokenType>
 where TokenType: Clone {
-    fn lexeme_string_to_token_type<StateKey>(&self, lexeme_text: &str) -> Option<TokenType> {
+    fn lexeme_string_to_token_type(&self, lexeme_text: &str) -> Option<TokenType> {
         match self {
             Parse::To(tok) => Some(tok.clone()),
             Parse::ByFunction(func) => Some(func(lexeme_text)),
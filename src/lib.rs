@@ -0,0 +1,26 @@
+//! Compiler implemented from scratch in Rust for a toy language featuring static
+//! type checking.
+//! 
+//! [See on GitHub](https://github.com/WiredSound/till)
+
+#![macro_use]
+
+/// Debugging macro for checking whether an expression matches a given pattern.
+#[macro_export]
+#[cfg(debug_assertions)]
+macro_rules! assert_pattern {
+    ($x:expr, $y:pat) => {
+        match $x { $y => {}, _ => panic!("{:?}", $x) }
+    };
+}
+
+pub mod stream;
+pub mod lexing;
+pub mod parsing;
+pub mod checking;
+pub mod codegen;
+pub mod interpreting;
+pub mod diagnostics;
+pub mod pipeline;
+#[cfg(feature = "process")]
+pub mod toolchain;
@@ -1,65 +1,203 @@
-//! Compiler implemented from scratch in Rust for a toy language featuring static
-//! type checking.
-//! 
-//! [See on GitHub](https://github.com/WiredSound/till)
-
-#![macro_use]
-
-/// Debugging macro for checking whether an expression matches a given pattern.
-#[macro_export]
-#[cfg(debug_assertions)]
-macro_rules! assert_pattern {
-    ($x:expr, $y:pat) => {
-        match $x { $y => {}, _ => panic!("{:?}", $x) }
-    };
-}
-
-mod stream;
-mod lexing;
-mod parsing;
-mod checking;
-mod codegen;
+//! Command-line interface to the till compiler.
 
-use stream::Stream;
+use till::{ stream::Stream, lexing, parsing, checking, codegen, interpreting, diagnostics, pipeline };
+#[cfg(feature = "process")]
+use till::toolchain;
 use std::{
     io::prelude::*,
     env, fs, io, fmt,
     path::{ Path, PathBuf }
 };
 
+/// Command-line options (arguments beginning with `--`).
+#[derive(Clone)]
+struct Options {
+    /// Report failures as a JSON array of diagnostics (for consumption by
+    /// editors) rather than as human-readable messages. Enabled with
+    /// `--json-diagnostics`.
+    json_diagnostics: bool,
+    /// Declare Num constants in the output assembly by their exact bit pattern.
+    /// Enabled with `--hex-floats`.
+    hex_floats: bool,
+    /// Generate position-independent assembly code (as required to link a
+    /// PIE). Enabled with `--position-independent`.
+    position_independent: bool,
+    /// Place each function in its own section of the output assembly so that
+    /// the linker can discard unused functions. Enabled with
+    /// `--function-sections`.
+    function_sections: bool,
+    /// Set all local variables to zero upon entering each function. Enabled
+    /// with `--zero-locals`.
+    zero_locals: bool,
+    /// Represent Num values as 32-bit rather than 64-bit floats in the output
+    /// assembly. Enabled with `--single-precision`.
+    single_precision: bool,
+    /// End the program with an exit system call rather than by returning to
+    /// the C runtime, reporting failures without the C library. Enabled with
+    /// `--freestanding`.
+    freestanding: bool,
+    /// Assembler for which the output assembly is written (and which is then
+    /// also used to assemble any executable). Set with `--dialect=<nasm|gas>`
+    /// (`nasm` by default).
+    dialect: codegen::genelf64::Dialect,
+    /// Compile a library of functions with no entry point, making every
+    /// function global. Enabled with `--library`.
+    library: bool,
+    /// Precede each Num literal declared in the output assembly with a comment
+    /// giving its value and line. Enabled with `--literal-comments`.
+    literal_comments: bool,
+    /// Declare each distinct Num literal only once in the output assembly.
+    /// Enabled with `--pool-literals`.
+    pool_literals: bool,
+    /// End the program with an error upon division by zero (whether compiled
+    /// or interpreted). Enabled with `--check-division`.
+    check_division: bool,
+    /// Compile comparisons that directly precede conditional jumps as a single
+    /// jump. Enabled with `--fuse-comparisons`.
+    fuse_comparisons: bool,
+    /// Execute the input program directly rather than producing assembly code.
+    /// Enabled with `--interpret`.
+    interpret: bool,
+    /// Output the tokens produced by lexical analysis of the input program as
+    /// JSON rather than compiling it. Enabled with `--tokens`.
+    tokens: bool,
+    /// Output the call graph of the input program in the DOT language rather
+    /// than compiling it. Enabled with `--call-graph`.
+    call_graph: bool,
+    /// Fail compilation should any warnings be reported. Enabled with
+    /// `--warnings-as-errors`.
+    warnings_as_errors: bool,
+    /// Accept Char operands to arithmetic and comparison operators (converting
+    /// them to Num) by disabling the strict types of the checker. Enabled with
+    /// `--char-arithmetic`.
+    char_arithmetic: bool,
+    /// Identifier of the function at which execution begins. Set with
+    /// `--entry-point=<name>` (`main` by default).
+    entry_point: String,
+    /// Compiler pass after which to stop and output the intermediate result
+    /// rather than compiling. Set with `--stop-after=<pass>`.
+    stop_after: Option<pipeline::Pass>,
+    /// Platform for which to generate code, determining the backend and the
+    /// defaults of the other code generation options. Set with
+    /// `--target=<triple>` (`x86_64-linux-gnu` by default).
+    target: codegen::target::Target,
+    /// Command-line arguments given to an interpreted program: the path of the
+    /// input file followed by any further arguments after it.
+    program_args: Vec<String>
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            json_diagnostics: false, hex_floats: false, position_independent: false, function_sections: false,
+            zero_locals: false, single_precision: false, freestanding: false, dialect: codegen::genelf64::Dialect::Nasm, library: false,
+            literal_comments: false, pool_literals: false, check_division: false, fuse_comparisons: false,
+            interpret: false, tokens: false, call_graph: false,
+            warnings_as_errors: false, char_arithmetic: false, entry_point: "main".to_string(), stop_after: None,
+            target: codegen::target::Target::default(), program_args: Vec::new()
+        }
+    }
+}
+
 fn main() {
-    println!("-- Till Compiler {} --", env!("CARGO_PKG_VERSION"));
+    let (option_args, args): (Vec<String>, Vec<String>) = env::args().skip(1).partition(|arg| arg.starts_with("--"));
+
+    let mut options = Options::default();
+    for opt in option_args {
+        match opt.as_str() {
+            "--json-diagnostics" => options.json_diagnostics = true,
+            "--hex-floats" => options.hex_floats = true,
+            "--position-independent" => options.position_independent = true,
+            "--function-sections" => options.function_sections = true,
+            "--zero-locals" => options.zero_locals = true,
+            "--single-precision" => options.single_precision = true,
+            "--freestanding" => options.freestanding = true,
+            "--library" => options.library = true,
+            "--literal-comments" => options.literal_comments = true,
+            "--pool-literals" => options.pool_literals = true,
+            "--check-division" => options.check_division = true,
+            "--fuse-comparisons" => options.fuse_comparisons = true,
+            "--interpret" => options.interpret = true,
+            "--tokens" => options.tokens = true,
+            "--call-graph" => options.call_graph = true,
+            "--warnings-as-errors" => options.warnings_as_errors = true,
+            "--char-arithmetic" => options.char_arithmetic = true,
+            _ => if let Some(name) = opt.strip_prefix("--entry-point=") {
+                options.entry_point = name.to_string();
+            }
+            else if let Some(pass) = opt.strip_prefix("--stop-after=") {
+                match pass.parse() {
+                    Ok(pass) => options.stop_after = Some(pass),
+                    Err(e) => println!("Ignoring option {}: {}", opt, e)
+                }
+            }
+            else if let Some(dialect) = opt.strip_prefix("--dialect=") {
+                match dialect.parse() {
+                    Ok(dialect) => options.dialect = dialect,
+                    Err(e) => println!("Ignoring option {}: {}", opt, e)
+                }
+            }
+            else if let Some(triple) = opt.strip_prefix("--target=") {
+                match triple.parse() {
+                    Ok(target) => options.target = target,
+                    Err(e) => println!("Ignoring option {}: {}", opt, e)
+                }
+            }
+            else { println!("Ignoring unrecognised option: {}", opt); }
+        }
+    }
+
+    if !options.json_diagnostics && !options.tokens && !options.call_graph && options.stop_after.is_none() {
+        println!("-- Till Compiler {} --", env!("CARGO_PKG_VERSION"));
+    }
 
     // Only enable logging if debug build:
     #[cfg(debug_assertions)]
     pretty_env_logger::init_timed();
 
-    let args: Vec<String> = env::args().collect();
-
     match args.len() {
-        3 => read_compile_write(&args[1], &args[2]),
-        2 => read_compile_write(&args[1], "out.asm"),
-        _ => interactive()
+        1 if options.stop_after.is_some() => read_then(&args[0], &options, stop_after),
+        1 if options.tokens => read_then(&args[0], &options, tokenise),
+        1 if options.call_graph => read_then(&args[0], &options, call_graph),
+        n if n >= 1 && options.interpret => {
+            options.program_args = args.clone();
+            read_then(&args[0], &options, interpret)
+        }
+        2 => read_compile_write(&args[0], &args[1], &options),
+        1 => read_compile_write(&args[0], "out.asm", &options),
+        _ => interactive(&options)
     }
 }
 
 /// Read till code from the file at the specified input path, compile that code,
 /// and then write the resulting machine code to the file at the specified output
-/// path.
-fn read_compile_write(relative_in: &str, relative_out: &str) {
+/// path. Should the `process` feature be enabled and the output path not have
+/// an `.asm` extension, an executable is instead assembled and linked.
+fn read_compile_write(relative_in: &str, relative_out: &str, options: &Options) {
     let in_path = to_full_path(relative_in);
     let out_path = to_full_path(relative_out);
 
     match fs::File::open(&in_path) {
         Ok(file) => {
-            println!("Opening input file: {}", in_path.display());
+            if !options.json_diagnostics { println!("Opening input file: {}", in_path.display()); }
 
-            let asm = compile(Stream::from_file(file));
+            let asm = compile(Stream::from_file(file), options);
+
+            #[cfg(feature = "process")]
+            {
+                if out_path.extension().is_none_or(|ext| ext != "asm") {
+                    match toolchain::assemble_and_link(&asm, &codegen_options(options), &out_path) {
+                        Ok(_) => if !options.json_diagnostics { println!("Writing executable to: {}", out_path.display()) },
+                        Err(e) => println!("Failed to assemble and link executable: {}", e)
+                    }
+                    return;
+                }
+            }
 
             match fs::File::create(&out_path) {
                 Ok(mut out_file) => {
                     match out_file.write_all(asm.as_bytes()) {
-                        Ok(_) => println!("Writing to output file: {}", out_path.display()),
+                        Ok(_) => if !options.json_diagnostics { println!("Writing to output file: {}", out_path.display()) },
                         Err(e) => display_file_error(e, out_path.display())
                     }
                 }
@@ -71,40 +209,177 @@ fn read_compile_write(relative_in: &str, relative_out: &str) {
     }
 }
 
+/// Read till code from the file at the specified input path and then pass that
+/// code to the given function (e.g. `interpret` to execute it).
+fn read_then(relative_in: &str, options: &Options, then: fn(Stream, &Options)) {
+    let in_path = to_full_path(relative_in);
+
+    match fs::File::open(&in_path) {
+        Ok(file) => then(Stream::from_file(file), options),
+        Err(e) => display_file_error(e, in_path.display())
+    }
+}
+
 /// Read input from stdin until EOF encountered and then compile that input as
 /// till code.
-fn interactive() {
-    println!("Please type your code and then press Ctrl-D to compile...");
+fn interactive(options: &Options) {
+    if !options.json_diagnostics && !options.tokens && !options.call_graph && options.stop_after.is_none() { println!("Please type your code and then press Ctrl-D to compile..."); }
 
     let mut buf = String::new();
 
     match io::stdin().lock().read_to_string(&mut buf) {
         Ok(_) => {
-            let asm = compile(Stream::from_str(&buf));
-            println!("\n{}", asm);
+            if options.stop_after.is_some() { stop_after(Stream::from_str(&buf), options); }
+            else if options.tokens { tokenise(Stream::from_str(&buf), options); }
+            else if options.call_graph { call_graph(Stream::from_str(&buf), options); }
+            else if options.interpret { interpret(Stream::from_str(&buf), options); }
+            else {
+                let asm = compile(Stream::from_str(&buf), options);
+                println!("\n{}", asm);
+            }
         }
         Err(e) => display_file_error(e, "<stdin>")
     }
 }
 
 /// Perform lexical, syntactic, and semantic analysis on the till code from a
-/// given input stream and then generate elf64 Intel-syntax assembly code.
-fn compile(strm: Stream) -> String {
-    let tokens = lexing::lexer::input(strm).filter_map(|x| display_any_failures(x, "lexical"));
-    let syntax_tree = parsing::parser::input(tokens).filter_map(|x| display_any_failures(x, "syntax"));
-    let final_ir = display_any_failures(checking::checker::input(syntax_tree), "semantic").unwrap();
-    codegen::genelf64::input(final_ir)
+/// given input stream and then generate assembly code for the target.
+fn compile(strm: Stream, options: &Options) -> String {
+    let final_ir = check(strm, options);
+    options.target.compile(final_ir, codegen_options(options))
 }
 
-/// Helper function that displays any errors and exits should one be encountered.
-fn display_any_failures<T, E: fmt::Display>(value: Result<T, E>, compilation_stage: &str) -> Option<T> {
+/// The options of the elf64 backend as given by the conventions of the target
+/// and the command-line options.
+fn codegen_options(options: &Options) -> codegen::genelf64::Options {
+    // Options enabled on the command line are enabled regardless of target:
+    let target_options = options.target.elf64_options();
+
+    codegen::genelf64::Options {
+        hex_floats: options.hex_floats,
+        entry_point: options.entry_point.clone(),
+        position_independent: options.position_independent || target_options.position_independent,
+        function_sections: options.function_sections,
+        zero_locals: options.zero_locals,
+        freestanding: options.freestanding || target_options.freestanding,
+        library: options.library,
+        literal_comments: options.literal_comments,
+        pool_literals: options.pool_literals,
+        check_division: options.check_division,
+        fuse_comparisons: options.fuse_comparisons,
+        dialect: options.dialect,
+        num_precision: if options.single_precision { codegen::genelf64::Precision::Single } else { codegen::genelf64::Precision::Double },
+        ..target_options
+    }
+}
+
+/// Perform lexical, syntactic, and semantic analysis on the till code from a
+/// given input stream and then execute that code.
+fn interpret(strm: Stream, options: &Options) {
+    let final_ir = check(strm, options);
+
+    let result = interpreting::interpreter::Interpreter::with_entry_point(final_ir, &options.entry_point)
+        .and_then(|mut interp| {
+            if options.check_division { interp.enable_division_checks(); }
+            if !options.program_args.is_empty() { interp.set_args(options.program_args.clone()); }
+            interp.run()
+        });
+
+    if let Err(e) = result {
+        println!("RUNTIME ERROR: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Perform lexical analysis on the till code from a given input stream and then
+/// output the resulting tokens as JSON.
+fn tokenise(strm: Stream, options: &Options) {
+    let tokens: Vec<lexing::lexer::Token> = lexing::lexer::input(strm).filter_map(|x| display_any_failures(x, "lexical", options)).collect();
+    println!("{}", lexing::tokens_to_json(&tokens));
+}
+
+/// Run the passes of the compiler on the till code from a given input stream up
+/// to and including the pass specified by `--stop-after` and then output the
+/// result of that pass.
+fn stop_after(strm: Stream, options: &Options) {
+    let pipeline_options = pipeline::Options {
+        checking: checking_options(options), stop_after: options.stop_after, ..Default::default()
+    };
+
+    match pipeline::run(strm, pipeline_options) {
+        Ok(artifact) => println!("{}", artifact),
+        Err(pipeline::Failure::Lexical(e)) => display_all_failures(&[e], "lexical", options),
+        Err(pipeline::Failure::Syntax(e)) => display_all_failures(&[e], "syntax", options),
+        Err(pipeline::Failure::Semantic(failures)) => display_all_failures(&failures, "semantic", options)
+    }
+}
+
+/// Perform lexical, syntactic, and semantic analysis on the till code from a
+/// given input stream and then output the call graph of that code as DOT.
+fn call_graph(strm: Stream, options: &Options) {
+    let final_ir = check(strm, options);
+    println!("{}", checking::callgraph::build(&final_ir).to_dot());
+}
+
+/// Perform lexical, syntactic, and semantic analysis on the till code from a
+/// given input stream, producing the final IR of that code.
+fn check(strm: Stream, options: &Options) -> Vec<checking::Instruction> {
+    let tokens = lexing::lexer::input(strm).filter_map(|x| display_any_failures(x, "lexical", options));
+    let syntax_tree = parsing::parser::input(tokens).filter_map(|x| display_any_failures(x, "syntax", options));
+    let (final_ir, warnings) = checking::checker::input_with_all_failures(syntax_tree, checking_options(options))
+        .unwrap_or_else(|failures| display_all_failures(&failures, "semantic", options));
+
+    // No failures so only warnings are reported:
+    if options.json_diagnostics {
+        let warning_diagnostics: Vec<diagnostics::Diagnostic> = warnings.iter().map(Into::into).collect();
+        println!("{}", diagnostics::to_json(&warning_diagnostics));
+    }
+    else {
+        for warning in warnings { println!("SEMANTIC WARNING: {}", warning); }
+    }
+
+    final_ir
+}
+
+/// The options of the checker as given by the command-line options.
+fn checking_options(options: &Options) -> checking::Options {
+    checking::Options {
+        entry_point: options.entry_point.clone(),
+        warnings_as_errors: options.warnings_as_errors,
+        strict_types: !options.char_arithmetic,
+        library: options.library,
+        // Lines are needed to annotate literals with where they appear:
+        mark_lines: options.literal_comments,
+        ..Default::default()
+    }
+}
+
+/// Helper function that displays any errors and exits unsuccessfully should one
+/// be encountered.
+fn display_any_failures<T, E>(value: Result<T, E>, compilation_stage: &str, options: &Options) -> Option<T>
+where E: fmt::Display, for<'a> &'a E: Into<diagnostics::Diagnostic> {
     if let Err(e) = &value {
-        println!("{} ERROR: {}", compilation_stage.to_ascii_uppercase(), e);
-        std::process::exit(0);
+        if options.json_diagnostics { println!("{}", diagnostics::to_json(&[e.into()])); }
+        else { println!("{} ERROR: {}", compilation_stage.to_ascii_uppercase(), e); }
+        std::process::exit(1);
     }
     value.ok()
 }
 
+/// Helper function that displays every one of the given errors and then exits
+/// unsuccessfully.
+fn display_all_failures<E>(failures: &[E], compilation_stage: &str, options: &Options) -> !
+where E: fmt::Display, for<'a> &'a E: Into<diagnostics::Diagnostic> {
+    if options.json_diagnostics {
+        let diagnostics: Vec<diagnostics::Diagnostic> = failures.iter().map(Into::into).collect();
+        println!("{}", diagnostics::to_json(&diagnostics));
+    }
+    else {
+        for e in failures { println!("{} ERROR: {}", compilation_stage.to_ascii_uppercase(), e); }
+    }
+    std::process::exit(1);
+}
+
 /// Display a given file input/output error.
 fn display_file_error<T: fmt::Display>(e: std::io::Error, path: T) {
     match e.kind() {
@@ -3,6 +3,7 @@
 //! `parser`.
 
 pub mod parser;
+pub mod pretty;
 
 use crate::{ stream, lexing::lexer };
 use std::fmt;
@@ -11,10 +12,14 @@ use std::fmt;
 /// token, and the encountering of the end of the token stream when it is not
 /// expected.
 #[derive(Debug, PartialEq)]
+#[allow(clippy::enum_variant_names)]
 pub enum Failure {
     UnexpectedToken(lexer::Token, &'static str),
     UnexpectedStreamEnd(&'static str),
-    UnexpectedIndent { expected_indent: usize, encountered_indent: usize, pos: stream::Position }
+    UnexpectedIndent { expected_indent: usize, encountered_indent: usize, pos: stream::Position },
+    /// Expressions are nested within one another at the given position more
+    /// deeply than `parser::MAX_EXPRESSION_DEPTH`.
+    ExpressionTooDeep(stream::Position)
 }
 
 impl fmt::Display for Failure {
@@ -23,7 +28,32 @@ impl fmt::Display for Failure {
             Failure::UnexpectedToken(tok, expected) => write!(f, "Expected {} yet encountered unexpected {}", expected, tok),
             Failure::UnexpectedStreamEnd(expected) => write!(f, "Encountered the end of the token stream yet expected {}", expected),
             Failure::UnexpectedIndent { expected_indent, encountered_indent, pos } =>
-                write!(f, "Encountered an unexpected change in indentation from the expected level of {} to an indentation level of {} tabs at {}", expected_indent, encountered_indent, pos)
+                write!(f, "Encountered an unexpected change in indentation from the expected level of {} to an indentation level of {} tabs at {}", expected_indent, encountered_indent, pos),
+            Failure::ExpressionTooDeep(pos) =>
+                write!(f, "Expression at {} is nested more than {} levels deep - consider splitting it up using variables", pos, parser::MAX_EXPRESSION_DEPTH)
+        }
+    }
+}
+
+impl Failure {
+    /// Position in the token stream at which the failure occurred (not known
+    /// should the end of the stream have been encountered).
+    pub fn pos(&self) -> Option<&stream::Position> {
+        match self {
+            Failure::UnexpectedToken(tok, _) => Some(&tok.lexeme.pos),
+            Failure::UnexpectedStreamEnd(_) => None,
+            Failure::UnexpectedIndent { pos, .. } |
+            Failure::ExpressionTooDeep(pos) => Some(pos)
+        }
+    }
+
+    /// Short name identifying the kind of failure.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Failure::UnexpectedToken(_, _) => "UnexpectedToken",
+            Failure::UnexpectedStreamEnd(_) => "UnexpectedStreamEnd",
+            Failure::UnexpectedIndent { .. } => "UnexpectedIndent",
+            Failure::ExpressionTooDeep(_) => "ExpressionTooDeep"
         }
     }
 }
@@ -32,7 +62,7 @@ type Result<T> = std::result::Result<T, Failure>;
 
 /// Represents a parsed till statement. An AST is comprised of a collection of
 /// `Statement` instances.
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Statement {
     If {
         condition: Expression,
@@ -53,25 +83,51 @@ pub enum Statement {
     },
 
     VariableDeclaration {
+        pos: stream::Position,
         var_type: String,
         identifier: String,
         value: Option<Expression>
     },
 
     VariableAssignment {
+        pos: stream::Position,
         identifier: String,
         assign_to: Expression
     },
 
-    Return(Option<Expression>),
+    Return { pos: stream::Position, value: Option<Expression> },
+
+    Display(Expression),
+
+    /// Print the given values, followed by a newline should `newline` be true.
+    Print { pos: stream::Position, args: Vec<Expression>, newline: bool },
+
+    /// End the program unsuccessfully should the condition be false.
+    Assert { pos: stream::Position, condition: Expression },
 
-    Display(Expression)
+    /// End the program unsuccessfully should the two values not be equal.
+    AssertEq { pos: stream::Position, left: Expression, right: Expression }
+}
+
+impl Statement {
+    /// Position of the beginning of the statement.
+    pub fn pos(&self) -> &stream::Position {
+        match self {
+            Statement::If { condition, .. } | Statement::While { condition, .. } => condition.pos(),
+            Statement::FunctionDefinition { pos, .. } |
+            Statement::VariableDeclaration { pos, .. } |
+            Statement::VariableAssignment { pos, .. } |
+            Statement::Return { pos, .. } | Statement::Print { pos, .. } |
+            Statement::Assert { pos, .. } | Statement::AssertEq { pos, .. } => pos,
+            Statement::Display(expr) => expr.pos()
+        }
+    }
 }
 
 pub type Block = Vec<Statement>;
 
 /// Parameter for a function definition.
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Parameter {
     pub param_type: String,
     pub identifier: String,
@@ -79,15 +135,18 @@ pub struct Parameter {
 }
 
 /// Represents a till expression.
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Expression {
     Equal(Box<Expression>, Box<Expression>),
+    /// Boolean exclusive or (`^^`), true when exactly one operand is true.
+    Xor(Box<Expression>, Box<Expression>),
     GreaterThan(Box<Expression>, Box<Expression>),
     LessThan(Box<Expression>, Box<Expression>),
     Add(Box<Expression>, Box<Expression>),
     Subtract(Box<Expression>, Box<Expression>),
     Multiply(Box<Expression>, Box<Expression>),
     Divide(Box<Expression>, Box<Expression>),
+    Modulo(Box<Expression>, Box<Expression>),
 
     BooleanNot(Box<Expression>),
     UnaryMinus(Box<Expression>),
@@ -96,5 +155,30 @@ pub enum Expression {
     CharLiteral { pos: stream::Position, value: char },
     BooleanLiteral { pos: stream::Position, value: bool },
     Variable { pos: stream::Position, identifier: String },
-    FunctionCall { pos: stream::Position, identifier: String, args: Vec<Expression> }
+    FunctionCall { pos: stream::Position, identifier: String, args: Vec<Expression> },
+    /// Explicit conversion of the value of an expression to the given type
+    /// (e.g. `cast(true, Num)`).
+    Cast { pos: stream::Position, expr: Box<Expression>, target_type: String },
+    /// Assignment of the value of an expression to a variable that itself
+    /// evaluates to the value assigned. Only found as the value assigned to
+    /// another variable (e.g. `y = 5` in `x = y = 5`).
+    Assignment { pos: stream::Position, identifier: String, value: Box<Expression> }
+}
+
+impl Expression {
+    /// Position of the left-most operand of the expression.
+    pub fn pos(&self) -> &stream::Position {
+        match self {
+            Expression::Equal(l, _) | Expression::Xor(l, _) | Expression::GreaterThan(l, _) | Expression::LessThan(l, _) |
+            Expression::Add(l, _) | Expression::Subtract(l, _) |
+            Expression::Multiply(l, _) | Expression::Divide(l, _) | Expression::Modulo(l, _) => l.pos(),
+
+            Expression::BooleanNot(expr) | Expression::UnaryMinus(expr) => expr.pos(),
+
+            Expression::NumberLiteral { pos, .. } | Expression::CharLiteral { pos, .. } |
+            Expression::BooleanLiteral { pos, .. } | Expression::Variable { pos, .. } |
+            Expression::FunctionCall { pos, .. } | Expression::Cast { pos, .. } |
+            Expression::Assignment { pos, .. } => pos
+        }
+    }
 }
\ No newline at end of file
@@ -4,14 +4,27 @@
 use crate::{ stream, lexing::lexer };
 use std::iter;
 
+/// Function that constructs a binary expression from its left and right
+/// sub-expressions.
+type MakeExprFunc = fn(Box<super::Expression>, Box<super::Expression>) -> super::Expression;
+
+/// Maximum number of expressions that may be nested within one another by
+/// means of brackets, unary operators, function call arguments, and casts.
+/// Expressions are parsed by recursive descent, so deeper nesting is rejected
+/// with `Failure::ExpressionTooDeep` rather than overflowing the stack (which
+/// also bounds the recursion of every later stage over the syntax tree).
+pub const MAX_EXPRESSION_DEPTH: usize = 100;
+
 /// Returns an iterator that yields abstract syntax representations for each
 /// TILL statement parsed from the given token stream.
 pub fn input<T: Iterator<Item=lexer::Token>>(tokens: T) -> StatementStream<T> {
-    StatementStream { tokens: tokens.peekable() }
+    StatementStream { tokens: tokens.peekable(), expression_depth: 0 }
 }
 
 pub struct StatementStream<T: Iterator<Item=lexer::Token>> {
-    tokens: iter::Peekable<T>
+    tokens: iter::Peekable<T>,
+    /// Number of expressions currently being parsed within one another.
+    expression_depth: usize
 }
 
 impl<T: Iterator<Item=lexer::Token>> Iterator for StatementStream<T> {
@@ -22,6 +35,10 @@ impl<T: Iterator<Item=lexer::Token>> Iterator for StatementStream<T> {
     fn next(&mut self) -> Option<Self::Item> {
         log::info!("Attempting to parse next statement from token stream...");
 
+        // Skip any blank lines before the next top-level statement (ignore
+        // result as end of stream should not cause error here):
+        while self.consume_token_if_type(&lexer::TokenType::Newline(0), "top-level statement").unwrap_or(None).is_some() {}
+
         if self.more_tokens_in_stream() {
             let stmt = self.statement(0, "top-level statement");
             
@@ -103,7 +120,7 @@ impl<T: Iterator<Item=lexer::Token>> StatementStream<T> {
 
     /// Parse a TILL statement.
     ///
-    /// `<stmt> ::= <if> | <while> | <function> | <declaration> | <assignment> | <return> | <display>`
+    /// `<stmt> ::= <if> | <while> | <function> | <declaration> | <assignment> | <return> | <display> | <print> | <assert>`
     fn statement(&mut self, current_indent: usize, stmt_type_name: &'static str) -> super::Result<super::Statement> {
         log::trace!("Parsing statement...");
 
@@ -126,7 +143,7 @@ impl<T: Iterator<Item=lexer::Token>> StatementStream<T> {
                     self.define_function_stmt(current_indent, identifier, pos)
                 }
                 else if self.check_type_of_peeked_token(&lexer::TokenType::Equals, "statement")? {
-                    self.assignment_stmt(identifier)
+                    self.assignment_stmt(identifier, pos)
                 }
                 else { Err(super::Failure::UnexpectedToken(self.consume_token("statement")?, "statement")) }
             }
@@ -140,6 +157,12 @@ impl<T: Iterator<Item=lexer::Token>> StatementStream<T> {
             // Display:
             lexer::TokenType::DisplayKeyword => self.display_stmt(),
 
+            // Print:
+            lexer::TokenType::PrintKeyword | lexer::TokenType::PrintlnKeyword => self.print_stmt(),
+
+            // Assertion:
+            lexer::TokenType::AssertKeyword | lexer::TokenType::AssertEqKeyword => self.assert_stmt(),
+
             _ => Err(super::Failure::UnexpectedToken(self.consume_token("statement")?, stmt_type_name))
         }
     }
@@ -152,7 +175,7 @@ impl<T: Iterator<Item=lexer::Token>> StatementStream<T> {
         self.consume_token_of_expected_type(&lexer::TokenType::IfKeyword, "if keyword")?;
 
         Ok(super::Statement::If {
-            condition: self.expression()?,
+            condition: self.condition()?,
             block: self.block(current_indent)?
         })
     }
@@ -164,11 +187,28 @@ impl<T: Iterator<Item=lexer::Token>> StatementStream<T> {
         self.consume_token_of_expected_type(&lexer::TokenType::WhileKeyword, "while keyword")?;
 
         Ok(super::Statement::While {
-            condition: self.expression()?,
+            condition: self.condition()?,
             block: self.block(current_indent)?
         })
     }
 
+    /// Parse the condition expression of an if or while statement. As
+    /// assignments cannot be used as conditions, encountering an equals = token
+    /// following the condition (e.g. `if x = 5`) is reported as an error
+    /// suggesting the equality operator was likely intended.
+    fn condition(&mut self) -> super::Result<super::Expression> {
+        let expr = self.expression()?;
+
+        if self.check_type_of_peeked_token(&lexer::TokenType::Equals, "condition").unwrap_or(false) {
+            return Err(super::Failure::UnexpectedToken(
+                self.consume_token("condition")?,
+                "a condition (assignment = cannot be used as a condition - did you mean the equality operator ==?)"
+            ));
+        }
+
+        Ok(expr)
+    }
+
     /// Parse a function definition statement. The function name identifier is
     /// assumed to have already have been consumed.
     ///
@@ -205,41 +245,61 @@ impl<T: Iterator<Item=lexer::Token>> StatementStream<T> {
     /// Parse a variable declaration statement that may optionally include an
     /// initial assignment value for that variable.
     ///
-    /// `<declaration> ::= <type> identifier ("=" <expr>)?`
+    /// `<declaration> ::= <type> identifier ("=" <assigned>)?`
     fn variable_declaration_stmt(&mut self) -> super::Result<super::Statement> {
         let var_type = self.consume_type_identifier("variable type")?;
-        let (identifier, _) = self.consume_identifier("variable identifier")?;
+        let (identifier, pos) = self.consume_identifier("variable identifier")?;
 
         // Variable declaration can optionally include a value for said variable:
         let value = if self.consume_token_if_type(&lexer::TokenType::Equals, "").unwrap_or(None).is_some() {
-            Some(self.expression()?)
+            Some(self.assigned_value()?)
         }
         else { None };
 
-        Ok(super::Statement::VariableDeclaration { var_type, identifier, value })
+        Ok(super::Statement::VariableDeclaration { pos, var_type, identifier, value })
     }
 
     /// Parse a variable assignment statement. The identifier token is already
-    /// assumed to have been consumed and the identifier string from said token
-    /// passed to this method.
+    /// assumed to have been consumed and the identifier string and stream
+    /// position from said token passed to this method.
     ///
-    /// `<assignment> ::= identifier "=" <expr>`
-    fn assignment_stmt(&mut self, identifier: String) -> super::Result<super::Statement> {
+    /// `<assignment> ::= identifier "=" <assigned>`
+    fn assignment_stmt(&mut self, identifier: String, pos: stream::Position) -> super::Result<super::Statement> {
         self.consume_token_of_expected_type(&lexer::TokenType::Equals, "equals = after identifier")?;
 
         Ok(super::Statement::VariableAssignment {
-            identifier,
-            assign_to: self.expression()?
+            pos, identifier,
+            assign_to: self.assigned_value()?
         })
     }
 
+    /// Parse the value assigned to a variable, which may itself be an
+    /// assignment to another variable (e.g. `x = y = 5` assigns 5 to both `y`
+    /// and `x`).
+    ///
+    /// `<assigned> ::= (identifier "=")* <expr>`
+    fn assigned_value(&mut self) -> super::Result<super::Expression> {
+        let expr = self.expression()?;
+
+        if let super::Expression::Variable { pos, identifier } = &expr {
+            if self.consume_token_if_type(&lexer::TokenType::Equals, "").unwrap_or(None).is_some() {
+                return Ok(super::Expression::Assignment {
+                    pos: pos.clone(), identifier: identifier.clone(),
+                    value: Box::new(self.assigned_value()?)
+                });
+            }
+        }
+
+        Ok(expr)
+    }
+
     /// Parse a function return statement.
     ///
     /// `<return> ::= "return" <expr>?`
     fn return_stmt(&mut self) -> super::Result<super::Statement> {
-        self.consume_token_of_expected_type(&lexer::TokenType::ReturnKeyword, "return keyword")?;
+        let pos = self.consume_token_of_expected_type(&lexer::TokenType::ReturnKeyword, "return keyword")?.lexeme.pos;
 
-        Ok(super::Statement::Return(self.expression().ok()))
+        Ok(super::Statement::Return { pos, value: self.expression().ok() })
     }
 
     /// Display the resulting value of an expression.
@@ -251,6 +311,51 @@ impl<T: Iterator<Item=lexer::Token>> StatementStream<T> {
         Ok(super::Statement::Display(self.expression()?))
     }
 
+    /// Print the resulting values of any number of expressions, followed by a
+    /// newline in the case of `println`.
+    ///
+    /// `<print> ::= ("print" | "println") "(" <exprs>? ")"`
+    fn print_stmt(&mut self) -> super::Result<super::Statement> {
+        let (newline, pos) = match self.consume_token("print or println keyword")? {
+            lexer::Token { tok_type: lexer::TokenType::PrintKeyword, lexeme } => (false, lexeme.pos),
+            lexer::Token { tok_type: lexer::TokenType::PrintlnKeyword, lexeme } => (true, lexeme.pos),
+            tok => return Err(super::Failure::UnexpectedToken(tok, "print or println keyword"))
+        };
+        self.consume_token_of_expected_type(&lexer::TokenType::BracketOpen, "print opening bracket ( token")?;
+
+        let args = if self.check_type_of_peeked_token(&lexer::TokenType::BracketClose, "print")? { vec![] }
+                   else { self.expressions()? };
+
+        self.consume_token_of_expected_type(&lexer::TokenType::BracketClose, "print closing bracket ) token")?;
+
+        Ok(super::Statement::Print { pos, args, newline })
+    }
+
+    /// Assert that a condition is true or, in the case of `assert_eq`, that two
+    /// values are equal.
+    ///
+    /// `<assert> ::= "assert" "(" <expr> ")" | "assert_eq" "(" <expr> "," <expr> ")"`
+    fn assert_stmt(&mut self) -> super::Result<super::Statement> {
+        let (equal, pos) = match self.consume_token("assert or assert_eq keyword")? {
+            lexer::Token { tok_type: lexer::TokenType::AssertKeyword, lexeme } => (false, lexeme.pos),
+            lexer::Token { tok_type: lexer::TokenType::AssertEqKeyword, lexeme } => (true, lexeme.pos),
+            tok => return Err(super::Failure::UnexpectedToken(tok, "assert or assert_eq keyword"))
+        };
+        self.consume_token_of_expected_type(&lexer::TokenType::BracketOpen, "assert opening bracket ( token")?;
+
+        let first = self.expression()?;
+
+        let stmt = if equal {
+            self.consume_token_of_expected_type(&lexer::TokenType::Comma, "comma , token seperating assert_eq arguments")?;
+            super::Statement::AssertEq { pos, left: first, right: self.expression()? }
+        }
+        else { super::Statement::Assert { pos, condition: first } };
+
+        self.consume_token_of_expected_type(&lexer::TokenType::BracketClose, "assert closing bracket ) token")?;
+
+        Ok(stmt)
+    }
+
     /// `<param> ::= <type> identifier`
     fn parse_parameter(&mut self) -> super::Result<super::Parameter> {
         let param_type = self.consume_type_identifier("function parameter type")?;
@@ -325,7 +430,7 @@ impl<T: Iterator<Item=lexer::Token>> StatementStream<T> {
     /// 'multiplcation', etc. in grammar file). This method is present to reduce
     /// the amount of repeated code required.
     fn left_right_expr(&mut self, sub_expr_func: fn(&mut Self) -> super::Result<super::Expression>,
-    seperators: &[(lexer::TokenType, fn(Box<super::Expression>, Box<super::Expression>) -> super::Expression)])
+    seperators: &[(lexer::TokenType, MakeExprFunc)])
     -> super::Result<super::Expression> {
         let mut expr = sub_expr_func(self);
         
@@ -344,14 +449,28 @@ impl<T: Iterator<Item=lexer::Token>> StatementStream<T> {
     /// Parse a TILL expression. Will return Failure should the token stream be
     /// at its end or if an expected token is encountered.
     ///
-    /// `<expr> ::= <comparison> ("==" <comparison>)*`
+    /// `<expr> ::= <comparison> (("=="|"^^") <comparison>)*`
     fn expression(&mut self) -> super::Result<super::Expression> {
         log::trace!("Parsing expression...");
 
-        self.left_right_expr(
+        if self.expression_depth >= MAX_EXPRESSION_DEPTH {
+            let pos = self.peek_token("expression")?.lexeme.pos.clone();
+            return Err(super::Failure::ExpressionTooDeep(pos));
+        }
+
+        self.expression_depth += 1;
+        let expr = self.left_right_expr(
             Self::comparison_expr,
-            &[(lexer::TokenType::DoubleEquals, |l, r| super::Expression::Equal(l, r))]
-        )
+            &[
+                (lexer::TokenType::DoubleEquals,
+                |l, r| super::Expression::Equal(l, r)),
+                (lexer::TokenType::DoubleCaret,
+                |l, r| super::Expression::Xor(l, r))
+            ]
+        );
+        self.expression_depth -= 1;
+
+        expr
     }
 
     /// `<comparison> ::= <addition> (("<"|">") <addition>)*`
@@ -380,7 +499,7 @@ impl<T: Iterator<Item=lexer::Token>> StatementStream<T> {
         )
     }
 
-    /// `<multiplication> ::= <unary> (("*"|"/") <unary>)*`
+    /// `<multiplication> ::= <unary> (("*"|"/"|"%") <unary>)*`
     fn multiplication_expr(&mut self) -> super::Result<super::Expression> {
         self.left_right_expr(
             Self::unary_expr,
@@ -388,7 +507,9 @@ impl<T: Iterator<Item=lexer::Token>> StatementStream<T> {
                 (lexer::TokenType::Star,
                 |l, r| super::Expression::Multiply(l, r)),
                 (lexer::TokenType::Slash,
-                |l, r| super::Expression::Divide(l, r))
+                |l, r| super::Expression::Divide(l, r)),
+                (lexer::TokenType::Percent,
+                |l, r| super::Expression::Modulo(l, r))
             ]
         )
     }
@@ -407,9 +528,10 @@ impl<T: Iterator<Item=lexer::Token>> StatementStream<T> {
     /// Parse a primary expression (a literal, expression enclosed in brackets,
     /// or variable identifier).
     ///
-    /// ```
+    /// ```text
     /// <primary> ::= number | string | character | "true" | "false"
     ///             | "[" <exprs>? "]" | "(" <expr> ")"
+    ///             | "cast" "(" <expr> "," <type> ")"
     ///             | identifier ("(" <exprs>? ")")?
     /// ```
     fn primary_expr(&mut self) -> super::Result<super::Expression> {
@@ -425,6 +547,17 @@ impl<T: Iterator<Item=lexer::Token>> StatementStream<T> {
                 Ok(expr)
             }
 
+            lexer::TokenType::Identifier(identifier) if identifier == "cast" &&
+                self.check_type_of_peeked_token(&lexer::TokenType::BracketOpen, "cast expression").unwrap_or(false) => {
+                self.consume_token_of_expected_type(&lexer::TokenType::BracketOpen, "cast opening bracket ( token")?;
+                let expr = self.expression()?;
+                self.consume_token_of_expected_type(&lexer::TokenType::Comma, "comma , token seperating cast expression and type")?;
+                let target_type = self.consume_type_identifier("cast target type")?;
+                self.consume_token_of_expected_type(&lexer::TokenType::BracketClose, "cast closing bracket ) token")?;
+
+                Ok(super::Expression::Cast { pos: tok.lexeme.pos, expr: Box::new(expr), target_type })
+            }
+
             lexer::TokenType::Identifier(identifier) => {
                 // If open bracket follows identifier, then this must be a function
                 // call:
@@ -478,22 +611,58 @@ impl<T: Iterator<Item=lexer::Token>> StatementStream<T> {
         }
     }
 
+    /// Parse a type. Function types are returned in the form `Fn(A, B) -> C`
+    /// regardless of the spacing used in the input.
+    ///
+    /// `<type> ::= type_identifier | "Fn" "(" (<type> ("," <type>)*)? ")" ("->" <type>)?`
     fn consume_type_identifier(&mut self, msg: &'static str) -> super::Result<String> {
         let tok = self.consume_token(msg)?;
 
         match tok.tok_type {
-            lexer::TokenType::TypeIdentifier(ident) => Ok(ident),
+            lexer::TokenType::TypeIdentifier(ident) => {
+                if ident == "Fn" && self.check_type_of_peeked_token(&lexer::TokenType::BracketOpen, msg).unwrap_or(false) {
+                    self.function_type()
+                }
+                else { Ok(ident) }
+            }
             _ => Err(super::Failure::UnexpectedToken(tok, msg))
         }
     }
+
+    /// Parse the parameter and return types of a function type. The `Fn` type
+    /// identifier is assumed to have already been consumed.
+    fn function_type(&mut self) -> super::Result<String> {
+        self.consume_token_of_expected_type(&lexer::TokenType::BracketOpen, "function type opening bracket ( token")?;
+
+        let mut params = Vec::new();
+
+        if !self.check_type_of_peeked_token(&lexer::TokenType::BracketClose, "function type")? {
+            loop {
+                params.push(self.consume_type_identifier("function type parameter type")?);
+
+                if self.consume_token_if_type(&lexer::TokenType::Comma,
+                    "comma , token to seperate function type parameters")?.is_none() { break }
+            }
+        }
+
+        self.consume_token_of_expected_type(&lexer::TokenType::BracketClose, "function type closing bracket ) token")?;
+
+        let mut ident = format!("Fn({})", params.join(", "));
+
+        if self.consume_token_if_type(&lexer::TokenType::Arrow, "function type").unwrap_or(None).is_some() {
+            ident.push_str(" -> ");
+            ident.push_str(&self.consume_type_identifier("function type return type")?);
+        }
+
+        Ok(ident)
+    }
 }
 
 
 
 #[cfg(test)]
-#[allow(illegal_floating_point_literal_pattern)]
 mod tests {
-    use crate::{ assert_pattern, parsing, lexing::lexer, stream::Stream };
+    use crate::{ parsing, lexing::lexer, stream::Stream };
 
     fn quick_parse(inp: &str) -> super::StatementStream<impl Iterator<Item=lexer::Token>> {
         let final_inp = inp.trim().replace("    ", "\t");
@@ -541,6 +710,17 @@ mod tests {
             }
             _ => panic!()
         }
+
+        match quick_parse("cast(x > 1, Num)").primary_expr() {
+            Ok(parsing::Expression::Cast { pos: _, expr, target_type }) => {
+                assert_pattern!(*expr, parsing::Expression::GreaterThan(_, _));
+                assert_eq!(target_type, "Num".to_string());
+            }
+            _ => panic!()
+        }
+
+        // A variable may still be named cast:
+        assert_pattern!(quick_parse("cast").primary_expr(), Ok(parsing::Expression::Variable { .. }));
     }
 
     #[test]
@@ -566,7 +746,9 @@ mod tests {
         assert_pattern!(quick_parse("2 + 3 - 4").expression(), Ok(parsing::Expression::Subtract(_, _)));
         assert_pattern!(quick_parse("1 + 3 > 2").expression(), Ok(parsing::Expression::GreaterThan(_, _)));
         assert_pattern!(quick_parse(" 1 > 2 == 3 < 4").expression(), Ok(parsing::Expression::Equal(_, _)));
+        assert_pattern!(quick_parse("1 > 2 ^^ 3 < 4").expression(), Ok(parsing::Expression::Xor(_, _)));
         assert_pattern!(quick_parse("3 * (4 + 2)").expression(), Ok(parsing::Expression::Multiply(_, _)));
+        assert_pattern!(quick_parse("1 + 7 % 3").expression(), Ok(parsing::Expression::Add(_, _)));
     }
 
     #[test]
@@ -574,7 +756,7 @@ mod tests {
         let mut prsr = quick_parse("x = 10\nx =");
         
         match prsr.next().unwrap() {
-            Ok(parsing::Statement::VariableAssignment { identifier, assign_to }) => {
+            Ok(parsing::Statement::VariableAssignment { pos: _, identifier, assign_to }) => {
                 assert_eq!(identifier, "x".to_string());
                 assert_pattern!(assign_to, parsing::Expression::NumberLiteral { pos: _, value: 10.0 });
             }
@@ -584,13 +766,27 @@ mod tests {
         assert_pattern!(prsr.next().unwrap(), Err(parsing::Failure::UnexpectedStreamEnd(_)));
 
         assert!(prsr.next().is_none());
+
+        match quick_parse("x = y = z == 1").next().unwrap() {
+            Ok(parsing::Statement::VariableAssignment { identifier, assign_to: parsing::Expression::Assignment { identifier: inner, value, .. }, .. }) => {
+                assert_eq!((identifier.as_str(), inner.as_str()), ("x", "y"));
+                assert_pattern!(*value, parsing::Expression::Equal(_, _));
+            }
+            other => panic!("{:?}", other)
+        }
+
+        // Only variables can be assigned to:
+        assert_pattern!(quick_parse("x = 1 = 2").next().unwrap(), Ok(parsing::Statement::VariableAssignment { assign_to: parsing::Expression::NumberLiteral { .. }, .. }));
+        assert_pattern!(quick_parse("Num x = y = 3").next().unwrap(), Ok(parsing::Statement::VariableDeclaration {
+            value: Some(parsing::Expression::Assignment { .. }), ..
+        }));
     }
 
     #[test]
     fn variable_declaration_stmts() {
         match quick_parse("Char x").next().unwrap() {
             Ok(parsing::Statement::VariableDeclaration {
-                value: None, var_type, identifier
+                value: None, var_type, identifier, pos: _
             }) => {
                 assert_eq!(identifier, "x".to_string());
                 assert_eq!(var_type, "Char".to_string());
@@ -601,13 +797,20 @@ mod tests {
         match quick_parse("Num x = 2.5\n\n").next().unwrap() {
             Ok(parsing::Statement::VariableDeclaration {
                 value: Some(parsing::Expression::NumberLiteral { pos: _, value: 2.5}),
-                var_type, identifier
+                var_type, identifier, pos: _
             }) => {
                 assert_eq!(identifier, "x".to_string());
                 assert_eq!(var_type, "Num".to_string());
             }
             _ => panic!()
         }
+
+        match quick_parse("Fn(Num,Fn() -> Bool)->Char f").next().unwrap() {
+            Ok(parsing::Statement::VariableDeclaration { var_type, .. }) => {
+                assert_eq!(var_type, "Fn(Num, Fn() -> Bool) -> Char".to_string());
+            }
+            _ => panic!()
+        }
     }
 
     #[test]
@@ -628,6 +831,19 @@ if x == 10
         }));
     }
 
+    #[test]
+    fn assignment_as_condition() {
+        match quick_parse("if x = 5\n\tx = 1").next().unwrap() {
+            Err(parsing::Failure::UnexpectedToken(tok, _)) => assert_eq!(tok.tok_type, lexer::TokenType::Equals),
+            _ => panic!()
+        }
+
+        assert_pattern!(
+            quick_parse("while x = true\n\tx = false").next().unwrap(),
+            Err(parsing::Failure::UnexpectedToken(_, _))
+        );
+    }
+
     #[test]
     fn while_stmts() {
         let mut prsr = quick_parse("while x < 10\n\tx = x + func(2)\n") ;
@@ -683,16 +899,77 @@ no_args()
 
     #[test]
     fn return_stmts() {
-        assert_eq!(
+        assert_pattern!(
             quick_parse("return\n\n").next().unwrap(),
-            Ok(parsing::Statement::Return(None))
+            Ok(parsing::Statement::Return { pos: _, value: None })
         );
 
         match quick_parse("return 2.5").next().unwrap() {
-            Ok(parsing::Statement::Return(Some(
-                parsing::Expression::NumberLiteral { pos: _, value: 2.5 }
-            ))) => {}
+            Ok(parsing::Statement::Return {
+                pos: _, value: Some(parsing::Expression::NumberLiteral { pos: _, value: 2.5 })
+            }) => {}
             _ => panic!()
         }
     }
+
+    #[test]
+    fn print_stmts() {
+        assert!(matches!(quick_parse("print()").next().unwrap(), Ok(parsing::Statement::Print { args, newline: false, .. }) if args.is_empty()));
+        assert!(matches!(quick_parse("println()").next().unwrap(), Ok(parsing::Statement::Print { args, newline: true, .. }) if args.is_empty()));
+
+        match quick_parse("print('a', 1, true)").next().unwrap() {
+            Ok(parsing::Statement::Print { args, newline: false, .. }) => {
+                assert_pattern!(args.as_slice(), [
+                    parsing::Expression::CharLiteral { pos: _, value: 'a' },
+                    parsing::Expression::NumberLiteral { pos: _, value: _ },
+                    parsing::Expression::BooleanLiteral { pos: _, value: true }
+                ]);
+            }
+            _ => panic!()
+        }
+
+        assert_pattern!(quick_parse("print 'a'").next().unwrap(), Err(parsing::Failure::UnexpectedToken(_, _)));
+    }
+
+    #[test]
+    fn assert_stmts() {
+        assert_pattern!(
+            quick_parse("assert(x > 1)").next().unwrap(),
+            Ok(parsing::Statement::Assert { condition: parsing::Expression::GreaterThan(_, _), .. })
+        );
+        assert_pattern!(
+            quick_parse("assert_eq(x, 'a')").next().unwrap(),
+            Ok(parsing::Statement::AssertEq { left: parsing::Expression::Variable { .. }, right: parsing::Expression::CharLiteral { value: 'a', .. }, .. })
+        );
+
+        assert_pattern!(quick_parse("assert(true, false)").next().unwrap(), Err(parsing::Failure::UnexpectedToken(_, _)));
+        assert_pattern!(quick_parse("assert_eq(1)").next().unwrap(), Err(parsing::Failure::UnexpectedToken(_, _)));
+    }
+
+    #[test]
+    fn blank_input() {
+        let parse = |inp: &str| super::input(lexer::input(Stream::from_str(inp)).map(Result::unwrap)).collect::<Vec<_>>();
+
+        assert!(parse("").is_empty());
+        assert!(parse("\n\n   \n").is_empty());
+
+        let stmts = parse("\n\nmain()\n\tdisplay 1\n");
+        assert_eq!(stmts.len(), 1);
+        assert_pattern!(stmts[0], Ok(parsing::Statement::FunctionDefinition { .. }));
+    }
+
+    #[test]
+    fn expression_depth() {
+        let depth = super::MAX_EXPRESSION_DEPTH;
+        let parse_display = |expr: String| quick_parse(&format!("main()\n    display {}", expr)).next().unwrap();
+
+        // Nesting far beyond the limit fails rather than overflowing the stack:
+        assert_pattern!(parse_display(format!("{}true", "!".repeat(100000))), Err(parsing::Failure::ExpressionTooDeep(_)));
+        assert_pattern!(parse_display(format!("{}1{}", "(".repeat(100000), ")".repeat(100000))), Err(parsing::Failure::ExpressionTooDeep(_)));
+        assert_pattern!(parse_display(format!("{}1{}", "f(".repeat(100000), ")".repeat(100000))), Err(parsing::Failure::ExpressionTooDeep(_)));
+
+        // Expression displayed is itself one level of nesting:
+        assert_pattern!(parse_display(format!("{}true", "!".repeat(depth - 1))), Ok(parsing::Statement::FunctionDefinition { .. }));
+        assert_pattern!(parse_display(format!("{}true", "!".repeat(depth))), Err(parsing::Failure::ExpressionTooDeep(_)));
+    }
 }
@@ -0,0 +1,203 @@
+//! Contains code for converting a till AST back into till source code (i.e.
+//! pretty-printing).
+
+use super::{ Statement, Expression };
+
+/// Options that alter how source code is laid out by the pretty-printer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrettyOptions {
+    /// Indent each level of nesting with tabs rather than spaces (enabled by
+    /// default). Note that the till lexer only accepts indentation by tabs.
+    pub use_tabs: bool,
+    /// Number of tabs or spaces making up a single level of indentation (1 by
+    /// default).
+    pub indent_width: usize
+}
+
+impl Default for PrettyOptions {
+    fn default() -> Self {
+        PrettyOptions { use_tabs: true, indent_width: 1 }
+    }
+}
+
+impl PrettyOptions {
+    fn indentation(&self, level: usize) -> String {
+        let unit = if self.use_tabs { "\t" } else { " " };
+        unit.repeat(self.indent_width * level)
+    }
+}
+
+/// Produce the source code of the given statements. Function definitions are
+/// separated by a blank line.
+pub fn to_source(stmts: &[Statement], options: &PrettyOptions) -> String {
+    let mut src = String::new();
+
+    for (index, stmt) in stmts.iter().enumerate() {
+        if index > 0 && matches!(stmt, Statement::FunctionDefinition { .. }) { src.push('\n'); }
+        write_stmt(&mut src, stmt, 0, options);
+    }
+
+    src
+}
+
+fn write_stmt(src: &mut String, stmt: &Statement, level: usize, options: &PrettyOptions) {
+    src.push_str(&options.indentation(level));
+
+    match stmt {
+        Statement::If { condition, block } => {
+            src.push_str(&format!("if {}\n", expr_to_source(condition)));
+            write_block(src, block, level, options);
+        }
+
+        Statement::While { condition, block } => {
+            src.push_str(&format!("while {}\n", expr_to_source(condition)));
+            write_block(src, block, level, options);
+        }
+
+        Statement::FunctionDefinition { identifier, parameters, return_type, body, .. } => {
+            let params: Vec<String> = parameters.iter().map(|param| format!("{} {}", param.param_type, param.identifier)).collect();
+            src.push_str(&format!("{}({})", identifier, params.join(", ")));
+
+            if let Some(ret) = return_type { src.push_str(&format!(" -> {}", ret)); }
+
+            src.push('\n');
+            write_block(src, body, level, options);
+        }
+
+        Statement::VariableDeclaration { var_type, identifier, value, .. } => {
+            src.push_str(&format!("{} {}", var_type, identifier));
+
+            if let Some(expr) = value { src.push_str(&format!(" = {}", expr_to_source(expr))); }
+
+            src.push('\n');
+        }
+
+        Statement::VariableAssignment { identifier, assign_to, .. } =>
+            src.push_str(&format!("{} = {}\n", identifier, expr_to_source(assign_to))),
+
+        Statement::Return { value: None, .. } => src.push_str("return\n"),
+        Statement::Return { value: Some(expr), .. } => src.push_str(&format!("return {}\n", expr_to_source(expr))),
+
+        Statement::Display(expr) => src.push_str(&format!("display {}\n", expr_to_source(expr))),
+
+        Statement::Print { args, newline, .. } => {
+            let keyword = if *newline { "println" } else { "print" };
+            src.push_str(&format!("{}({})\n", keyword, exprs_to_source(args)));
+        }
+
+        Statement::Assert { condition, .. } => src.push_str(&format!("assert({})\n", expr_to_source(condition))),
+
+        Statement::AssertEq { left, right, .. } =>
+            src.push_str(&format!("assert_eq({}, {})\n", expr_to_source(left), expr_to_source(right)))
+    }
+}
+
+fn write_block(src: &mut String, block: &[Statement], level: usize, options: &PrettyOptions) {
+    for stmt in block { write_stmt(src, stmt, level + 1, options); }
+}
+
+/// Produce the source code of the given expression. Brackets are placed around
+/// sub-expressions wherever they are needed for the source to be parsed back
+/// into the same expression.
+pub fn expr_to_source(expr: &Expression) -> String {
+    match expr {
+        Expression::Equal(l, r) => binary_to_source(l, "==", r, 0),
+        Expression::Xor(l, r) => binary_to_source(l, "^^", r, 0),
+        Expression::GreaterThan(l, r) => binary_to_source(l, ">", r, 1),
+        Expression::LessThan(l, r) => binary_to_source(l, "<", r, 1),
+        Expression::Add(l, r) => binary_to_source(l, "+", r, 2),
+        Expression::Subtract(l, r) => binary_to_source(l, "-", r, 2),
+        Expression::Multiply(l, r) => binary_to_source(l, "*", r, 3),
+        Expression::Divide(l, r) => binary_to_source(l, "/", r, 3),
+        Expression::Modulo(l, r) => binary_to_source(l, "%", r, 3),
+
+        // Unary operators apply to the entire expression that follows them:
+        Expression::BooleanNot(x) => format!("!{}", expr_to_source(x)),
+        Expression::UnaryMinus(x) => format!("~{}", expr_to_source(x)),
+
+        Expression::NumberLiteral { value, .. } => value.to_string(),
+        Expression::CharLiteral { value, .. } => format!("'{}'", escape_char(*value)),
+        Expression::BooleanLiteral { value, .. } => value.to_string(),
+        Expression::Variable { identifier, .. } => identifier.clone(),
+        Expression::FunctionCall { identifier, args, .. } => format!("{}({})", identifier, exprs_to_source(args)),
+        Expression::Cast { expr, target_type, .. } => format!("cast({}, {})", expr_to_source(expr), target_type),
+        Expression::Assignment { identifier, value, .. } => format!("{} = {}", identifier, expr_to_source(value))
+    }
+}
+
+fn exprs_to_source(exprs: &[Expression]) -> String {
+    exprs.iter().map(expr_to_source).collect::<Vec<String>>().join(", ")
+}
+
+/// Produce the source code of a binary operation with the given precedence
+/// (higher binding more tightly). As operators of the same precedence cannot
+/// be chained without brackets, an operand is bracketed should it be a binary
+/// operation of the same or lower precedence, or a unary operation.
+fn binary_to_source(left: &Expression, operator: &str, right: &Expression, precedence: usize) -> String {
+    let operand = |x: &Expression| {
+        let needs_brackets = match precedence_of(x) {
+            Some(operand_precedence) => operand_precedence <= precedence,
+            None => matches!(x, Expression::BooleanNot(_) | Expression::UnaryMinus(_))
+        };
+
+        if needs_brackets { format!("({})", expr_to_source(x)) }
+        else { expr_to_source(x) }
+    };
+
+    format!("{} {} {}", operand(left), operator, operand(right))
+}
+
+/// Precedence of the given expression should it be a binary operation.
+fn precedence_of(expr: &Expression) -> Option<usize> {
+    match expr {
+        Expression::Equal(_, _) | Expression::Xor(_, _) => Some(0),
+        Expression::GreaterThan(_, _) | Expression::LessThan(_, _) => Some(1),
+        Expression::Add(_, _) | Expression::Subtract(_, _) => Some(2),
+        Expression::Multiply(_, _) | Expression::Divide(_, _) | Expression::Modulo(_, _) => Some(3),
+        _ => None
+    }
+}
+
+fn escape_char(chr: char) -> String {
+    match chr {
+        '\n' => "\\n".to_string(),
+        '\t' => "\\t".to_string(),
+        '\\' => "\\\\".to_string(),
+        '\'' => "\\'".to_string(),
+        _ => chr.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ lexing::lexer, parsing, stream::Stream };
+
+    fn quick_parse(inp: &str) -> Vec<parsing::Statement> {
+        let tokens = lexer::input(Stream::from_str(inp)).map(Result::unwrap);
+        parsing::parser::input(tokens).map(Result::unwrap).collect()
+    }
+
+    #[test]
+    fn indentation() {
+        let src = "main()\n\tNum x = 3\n\twhile x > 0\n\t\tif x == 2\n\t\t\tdisplay 'y'\n\t\tx = x - 1\n";
+        let stmts = quick_parse(src);
+
+        let spaces = |indent_width| super::to_source(&stmts, &super::PrettyOptions { use_tabs: false, indent_width });
+
+        assert_eq!(spaces(2), "main()\n  Num x = 3\n  while x > 0\n    if x == 2\n      display 'y'\n    x = x - 1\n");
+        assert_eq!(spaces(4), "main()\n    Num x = 3\n    while x > 0\n        if x == 2\n            display 'y'\n        x = x - 1\n");
+
+        // Tabs by default so that the output can be parsed again:
+        assert_eq!(super::to_source(&stmts, &super::PrettyOptions::default()), src);
+    }
+
+    #[test]
+    fn round_trip() {
+        let src = "double(Num x) -> Num\n\treturn x * 2\n\nmain()\n\tBool b = (1 + 2) * 3 > 4 == (!false)\n\tb = (b ^^ true) ^^ 1 < 2\n\tprintln('\\n', double(~1.5), cast(b, Num) - (2 - 1))\n\tprint()\n\tassert(b)\n\tassert_eq(double(1), 2)\n\tNum x = b = y = true\n\treturn\n";
+        let stmts = quick_parse(src);
+
+        let output = super::to_source(&stmts, &super::PrettyOptions::default());
+        assert_eq!(output, src);
+        assert_eq!(quick_parse(&output), stmts);
+    }
+}
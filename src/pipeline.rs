@@ -0,0 +1,198 @@
+//! Runs each stage of compilation in turn, optionally stopping after a given
+//! pass so that the intermediate state of the compiler can be inspected (e.g.
+//! when debugging or testing the compiler itself).
+
+use crate::{ stream, lexing, parsing, checking, codegen };
+use std::{ fmt, str::FromStr };
+
+/// A stage of compilation after which the pipeline may be stopped.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pass {
+    /// Lexical analysis, producing tokens.
+    Lex,
+    /// Syntactic analysis, producing an AST.
+    Parse,
+    /// Semantic analysis, producing the AST once it has been checked alongside
+    /// any warnings.
+    Check,
+    /// Conversion of the checked AST to final IR without any of the optional
+    /// optimisations of the checker being performed.
+    Lower,
+    /// Conversion of the checked AST to final IR with the optimisations
+    /// enabled in the checking options performed.
+    Optimise
+}
+
+impl FromStr for Pass {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lex" => Ok(Pass::Lex),
+            "parse" => Ok(Pass::Parse),
+            "check" => Ok(Pass::Check),
+            "lower" => Ok(Pass::Lower),
+            "optimize" | "optimise" => Ok(Pass::Optimise),
+            _ => Err(format!("Unknown compiler pass '{}' (expected lex, parse, check, lower, or optimize)", s))
+        }
+    }
+}
+
+/// The result of running the pipeline: the output of the last pass performed.
+#[derive(Debug, PartialEq)]
+pub enum Artifact {
+    Tokens(Vec<lexing::lexer::Token>),
+    Ast(Vec<parsing::Statement>),
+    CheckedAst(Vec<parsing::Statement>, Vec<checking::Warning>),
+    Ir(Vec<checking::Instruction>),
+    OptimisedIr(Vec<checking::Instruction>),
+    Assembly(String)
+}
+
+impl fmt::Display for Artifact {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Artifact::Tokens(tokens) => write!(f, "{}", lexing::tokens_to_json(tokens)),
+
+            Artifact::Ast(stmts) => write!(f, "{}", parsing::pretty::to_source(stmts, &Default::default())),
+
+            Artifact::CheckedAst(stmts, warnings) => {
+                for warning in warnings { writeln!(f, "SEMANTIC WARNING: {}", warning)?; }
+                write!(f, "{}", parsing::pretty::to_source(stmts, &Default::default()))
+            }
+
+            Artifact::Ir(instructions) | Artifact::OptimisedIr(instructions) => {
+                for instruction in instructions { writeln!(f, "{:?}", instruction)?; }
+                Ok(())
+            }
+
+            Artifact::Assembly(asm) => write!(f, "{}", asm)
+        }
+    }
+}
+
+/// A failure encountered during any stage of the pipeline.
+#[derive(Debug)]
+pub enum Failure {
+    Lexical(lexing::Failure),
+    Syntax(parsing::Failure),
+    Semantic(Vec<checking::Failure>)
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Failure::Lexical(e) => write!(f, "LEXICAL ERROR: {}", e),
+            Failure::Syntax(e) => write!(f, "SYNTAX ERROR: {}", e),
+            Failure::Semantic(failures) => {
+                let messages: Vec<String> = failures.iter().map(|e| format!("SEMANTIC ERROR: {}", e)).collect();
+                write!(f, "{}", messages.join("\n"))
+            }
+        }
+    }
+}
+
+/// Options for every stage of the pipeline as well as the pass after which to
+/// stop (should none be given, every pass is performed and the assembly code
+/// generated).
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    pub checking: checking::Options,
+    pub codegen: codegen::genelf64::Options,
+    pub stop_after: Option<Pass>
+}
+
+/// Run the pipeline on the till code from the given input stream, generating
+/// elf64 assembly code should `stop_after` not be set.
+pub fn run(strm: stream::Stream, options: Options) -> Result<Artifact, Failure> {
+    let codegen_options = options.codegen.clone();
+    run_with_backend(strm, options, |final_ir| codegen::genelf64::input_with_options(final_ir, codegen_options))
+}
+
+/// Run the pipeline on the till code from the given input stream, passing the
+/// final IR to the given backend should `stop_after` not be set.
+pub fn run_with_backend<F>(strm: stream::Stream, options: Options, backend: F) -> Result<Artifact, Failure>
+where F: FnOnce(Vec<checking::Instruction>) -> String {
+    let tokens = lexing::lexer::input(strm).collect::<Result<Vec<_>, _>>().map_err(Failure::Lexical)?;
+    if options.stop_after == Some(Pass::Lex) { return Ok(Artifact::Tokens(tokens)); }
+
+    let stmts = parsing::parser::input(tokens.into_iter()).collect::<Result<Vec<_>, _>>().map_err(Failure::Syntax)?;
+    if options.stop_after == Some(Pass::Parse) { return Ok(Artifact::Ast(stmts)); }
+
+    if options.stop_after == Some(Pass::Check) {
+        let (_, warnings) = checking::checker::input_with_all_failures(stmts.clone().into_iter(), options.checking)
+            .map_err(Failure::Semantic)?;
+        return Ok(Artifact::CheckedAst(stmts, warnings));
+    }
+
+    if options.stop_after == Some(Pass::Lower) {
+        let unoptimised = checking::Options {
+            remove_dead_branches: false, hoist_loop_invariants: false, fold_boolean_identities: false,
+            propagate_constants: false, ..options.checking
+        };
+        let (final_ir, _) = checking::checker::input_with_all_failures(stmts.into_iter(), unoptimised)
+            .map_err(Failure::Semantic)?;
+        return Ok(Artifact::Ir(final_ir));
+    }
+
+    let (final_ir, _) = checking::checker::input_with_all_failures(stmts.into_iter(), options.checking)
+        .map_err(Failure::Semantic)?;
+    if options.stop_after == Some(Pass::Optimise) { return Ok(Artifact::OptimisedIr(final_ir)); }
+
+    Ok(Artifact::Assembly(backend(final_ir)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ checking, stream::Stream };
+    use super::{ Pass, Artifact, Failure };
+
+    fn run(src: &str, stop_after: Option<Pass>, checking: checking::Options) -> Result<Artifact, Failure> {
+        let options = super::Options { checking, stop_after, ..Default::default() };
+        super::run_with_backend(Stream::from_str(src), options, |_| panic!("Backend should not be invoked"))
+    }
+
+    #[test]
+    fn passes() {
+        assert_eq!("lex".parse(), Ok(Pass::Lex));
+        assert_eq!("optimize".parse(), Ok(Pass::Optimise));
+        assert!("codegen".parse::<Pass>().is_err());
+
+        let src = "main()\n\tNum x = 1 + 2\n\tdisplay x";
+
+        assert!(matches!(run(src, Some(Pass::Lex), Default::default()), Ok(Artifact::Tokens(tokens)) if tokens.len() > 10));
+        assert!(matches!(run(src, Some(Pass::Parse), Default::default()), Ok(Artifact::Ast(stmts)) if stmts.len() == 1));
+        assert!(matches!(run(src, Some(Pass::Check), Default::default()), Ok(Artifact::CheckedAst(_, warnings)) if warnings.is_empty()));
+
+        assert!(matches!(run("main()\n\tdisplay x", Some(Pass::Parse), Default::default()), Ok(Artifact::Ast(_))));
+        assert!(matches!(run("main()\n\tdisplay x", Some(Pass::Check), Default::default()), Err(Failure::Semantic(_))));
+        assert!(matches!(run("main()\n\tdisplay 'a", Some(Pass::Parse), Default::default()), Err(Failure::Lexical(_))));
+    }
+
+    #[test]
+    fn stop_after_lower() {
+        let src = "main()\n\tNum x = 1 + 2\n\tdisplay x";
+        let options = checking::Options { propagate_constants: true, ..Default::default() };
+
+        let lowered = match run(src, Some(Pass::Lower), options.clone()) {
+            Ok(Artifact::Ir(final_ir)) => final_ir,
+            other => panic!("{:?}", other)
+        };
+        assert!(lowered.contains(&checking::Instruction::Add));
+        assert_eq!(lowered, checking::checker::input(crate::parsing::parser::input(
+            crate::lexing::lexer::input(Stream::from_str(src)).map(Result::unwrap)).map(Result::unwrap)).unwrap());
+
+        let optimised = match run(src, Some(Pass::Optimise), options) {
+            Ok(Artifact::OptimisedIr(final_ir)) => final_ir,
+            other => panic!("{:?}", other)
+        };
+        assert!(!optimised.contains(&checking::Instruction::Add));
+    }
+
+    #[test]
+    fn backend() {
+        let options = super::Options::default();
+        let artifact = super::run_with_backend(Stream::from_str("main()\n\tdisplay 1"), options, |final_ir| format!("{}", final_ir.len()));
+        assert!(matches!(artifact, Ok(Artifact::Assembly(_))));
+    }
+}
@@ -17,6 +17,10 @@ impl Position {
     }
 }
 
+impl Default for Position {
+    fn default() -> Self { Position::new() }
+}
+
 impl fmt::Display for Position {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "character {} of line {}", self.line_position, self.line_number)
@@ -31,7 +35,7 @@ pub struct Stream {
 }
 
 impl Stream {
-    #[allow(dead_code)]
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Stream {
         Stream {
             char_stream: CharStream::from(s),
@@ -0,0 +1,160 @@
+//! Invoke the external assembler (NASM or GNU as) and the C compiler driver
+//! (cc, used only to link) in order to turn the assembly code produced by the
+//! compiler into an executable. Only available when the `process` feature is
+//! enabled.
+
+use crate::codegen::genelf64::{ self, Dialect };
+use std::{
+    fs, io,
+    path::Path,
+    process::Command
+};
+
+/// Write the given assembly code, generated with the given options, to a file
+/// alongside the output path, assemble that file into an elf64 object file
+/// using the assembler of the dialect of the code (NASM or GNU as), and then
+/// link the object file into an executable at the output path using cc.
+///
+/// Linking through cc rather than ld directly leaves finding the C runtime
+/// startup files and dynamic linker to the toolchain of the system. Code that
+/// is not position independent is linked with `-no-pie`, while freestanding
+/// code is linked statically without the C runtime (entered at the entry
+/// point). The linker discards any sections that are never referred to so
+/// that, should the assembly code have been generated with each function in
+/// its own section, the code of unused functions is not included in the
+/// executable.
+pub fn assemble_and_link(asm: &str, options: &genelf64::Options, output_path: &Path) -> io::Result<()> {
+    let asm_path = output_path.with_extension(match options.dialect { Dialect::Nasm => "asm", Dialect::Gas => "s" });
+    let object_path = output_path.with_extension("o");
+
+    fs::write(&asm_path, asm)?;
+
+    match options.dialect {
+        Dialect::Nasm => run_tool(Command::new("nasm")
+            .arg("-f").arg("elf64")
+            .arg(&asm_path)
+            .arg("-o").arg(&object_path))?,
+
+        Dialect::Gas => run_tool(Command::new("as")
+            .arg(&asm_path)
+            .arg("-o").arg(&object_path))?
+    }
+
+    let mut link = Command::new("cc");
+    link.arg("-Wl,--gc-sections");
+
+    if options.freestanding {
+        link.arg("-static").arg("-nostdlib").arg(format!("-Wl,--entry={}", options.entry_point));
+    }
+    else if !options.position_independent { link.arg("-no-pie"); }
+
+    run_tool(link.arg(&object_path).arg("-o").arg(output_path))?;
+
+    fs::remove_file(&object_path)
+}
+
+/// Run an external tool to completion. Produces a descriptive error should the
+/// tool not be installed or should it exit unsuccessfully.
+fn run_tool(command: &mut Command) -> io::Result<()> {
+    let tool = command.get_program().to_string_lossy().into_owned();
+    log::info!("Running external tool: {:?}", command);
+
+    let output = command.output().map_err(|e| {
+        if e.kind() == io::ErrorKind::NotFound {
+            io::Error::new(io::ErrorKind::NotFound,
+                format!("Could not find '{}' - please ensure it is installed and available on your PATH", tool))
+        }
+        else { e }
+    })?;
+
+    if output.status.success() { Ok(()) }
+    else {
+        Err(io::Error::other(format!(
+            "'{}' failed ({}) with output:\n{}",
+            tool, output.status, String::from_utf8_lossy(&output.stderr).trim_end()
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ lexing::lexer, parsing, checking, codegen::genelf64::{ self, Dialect }, interpreting, stream::Stream };
+    use std::{ env, process::{ Command, Output } };
+
+    fn quick_check(inp: &str) -> Vec<checking::Instruction> {
+        let tokens = lexer::input(Stream::from_str(inp)).map(Result::unwrap);
+        let stmts = parsing::parser::input(tokens).map(Result::unwrap);
+        checking::checker::input(stmts).unwrap()
+    }
+
+    // Tests that run the external tools are ignored by default as those tools
+    // may not be installed - run with `cargo test --features process -- --ignored`.
+
+    /// Assemble, link, and run the given till code, returning its output.
+    fn run(name: &str, inp: &str) -> String {
+        let output = run_with_options(name, inp, Default::default());
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    /// Compile the given till code to assembly with the given options and then
+    /// assemble, link, and run it.
+    fn run_with_options(name: &str, inp: &str, options: genelf64::Options) -> Output {
+        let asm = genelf64::input_with_options(quick_check(inp), options.clone());
+
+        let exe_path = env::temp_dir().join(format!("till_toolchain_test_{}_{}", name, std::process::id()));
+        super::assemble_and_link(&asm, &options, &exe_path).unwrap();
+
+        Command::new(&exe_path).output().unwrap()
+    }
+
+    #[test]
+    #[ignore]
+    fn assemble_and_link_program() {
+        assert_eq!(run("display", "main()\n\tdisplay 'x'\n"), "Line 2 character value: 'x'\n");
+    }
+
+    const FIVE_PARAMETERS_SRC: &str = "sum(Num a, Num b, Num c, Num d, Num e) -> Num\n\treturn a + (b * 10 + (c * 100 + (d * 1000 + e * 10000)))\n\nmain()\n\tdisplay sum(1, 2, 3, 4, 5)\n";
+
+    /// Value calculated by the interpreter is that displayed by the executable
+    /// in `five_parameters`.
+    #[test]
+    fn five_parameters_interpreted() {
+        let steps = interpreting::interpreter::trace(quick_check(FIVE_PARAMETERS_SRC)).unwrap();
+        assert_eq!(interpreting::displayed_values(&steps), vec![vec![interpreting::Value::Num(54321.0)]]);
+    }
+
+    #[test]
+    #[ignore]
+    fn five_parameters() {
+        assert_eq!(run("five_parameters", FIVE_PARAMETERS_SRC), "Line 5 number value: 54321.000000\n");
+    }
+
+    #[test]
+    #[ignore]
+    fn gas_directives() {
+        let src = "main()\n\tNum x = 1.5\n\tdisplay x * 4 - 1\n\tdisplay 'x'\n";
+        let expected = "Line 3 number value: 5.000000\nLine 4 character value: 'x'\n";
+
+        let output = run_with_options("gas_directives", src, genelf64::Options { dialect: Dialect::Gas, ..Default::default() });
+        assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+
+        // Position-independent code is linked as a position-independent
+        // executable:
+        let options = genelf64::Options { dialect: Dialect::Gas, position_independent: true, ..Default::default() };
+        let output = run_with_options("gas_directives_pie", src, options);
+        assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+    }
+
+    #[test]
+    #[ignore]
+    fn freestanding() {
+        let options = genelf64::Options { dialect: Dialect::Gas, freestanding: true, ..Default::default() };
+        let output = run_with_options("freestanding", "main()\n\tNum x = 1\n\tx = x + 1\n", options.clone());
+        assert!(output.status.success() && output.stdout.is_empty());
+
+        // Failures are reported without the C library:
+        let output = run_with_options("freestanding_assertion", "main()\n\tNum x = 1\n\tassert(x == 2)\n", options);
+        assert_eq!(output.status.code(), Some(1));
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "Line 3 assertion failed\n");
+    }
+}